@@ -6,22 +6,28 @@ in vec3 Normal;
 
 in vec3 FragPos;
 
-uniform vec3 viewPos;
-
-uniform float uWidth;
-uniform float uHeight;
-
-layout (location = 0) out vec3 gPosition;
-layout (location = 1) out vec3 gNormal;
+// the alphas hold the material: the model, roughness or shininess, metallic or specular
+layout (location = 0) out vec4 gPosition;
+layout (location = 1) out vec4 gNormal;
 layout (location = 2) out vec4 gAlbedoSpec;
+// emissive color and ambient occlusion
+layout (location = 3) out vec4 gEmissive;
 
 uniform sampler2D texture0;
 
 struct Material {
+    // 0 phong, 1 metallic roughness
+    int model;
     vec3 ambient;
     vec3 diffuse;
     float specular;
     float shininess;
+    float dissolve;
+    vec3 baseColor;
+    float metallic;
+    float roughness;
+    float ambientOcclusion;
+    vec3 emissive;
 }; 
 
 uniform Material material;
@@ -29,8 +35,9 @@ uniform Material material;
 void main()
 {
     vec3 normal = normalize(Normal);
-    gPosition = FragPos;
-    gNormal = normal;
+    gPosition = vec4(FragPos, float(material.model));
+    gNormal = vec4(normal, material.shininess);
+    gEmissive = vec4(material.emissive, material.ambientOcclusion);
     float u = length(max(vec2(0,0),(abs(fract(TexCoords)-0.5)*2.0)*3.0-2.0))*(1.0/3.0)-(1.0/2.0);
     gAlbedoSpec.rgb = texture(texture0, TexCoords).rgb+VertColor*vec3(1-u);
     gAlbedoSpec.a = material.specular;
@@ -3,36 +3,85 @@ in vec3 VertColor;
 in vec2 TexCoords;
 in vec3 VertPos;
 in vec3 Normal;
+in vec3 Tangent;
+in float BitangentSign;
 
 in vec3 FragPos;
 
-uniform vec3 viewPos;
-
-uniform float uWidth;
-uniform float uHeight;
-
-layout (location = 0) out vec3 gPosition;
-layout (location = 1) out vec3 gNormal;
+// the alphas hold the material: the model, roughness or shininess, metallic or specular
+layout (location = 0) out vec4 gPosition;
+layout (location = 1) out vec4 gNormal;
 layout (location = 2) out vec4 gAlbedoSpec;
+// emissive color and ambient occlusion
+layout (location = 3) out vec4 gEmissive;
 
 uniform sampler2D texture0;
+uniform sampler2D metallicRoughnessMap;
+uniform sampler2D occlusionMap;
+uniform sampler2D emissiveMap;
+uniform sampler2D normalMap;
+uniform bool hasMetallicRoughnessMap;
+uniform bool hasOcclusionMap;
+uniform bool hasEmissiveMap;
+uniform bool hasNormalMap;
 
 struct Material {
+    // 0 phong, 1 metallic roughness
+    int model;
     vec3 ambient;
     vec3 diffuse;
     float specular;
     float shininess;
+    float dissolve;
+    vec3 baseColor;
+    float metallic;
+    float roughness;
+    float ambientOcclusion;
+    vec3 emissive;
 }; 
 
 uniform Material material;
 
 void main()
 {
+    // the g-buffer has no blending, so mostly see-through materials are cut out instead
+    if (material.dissolve < 0.5)
+        discard;
     vec3 normal = normalize(Normal);
-    gPosition = FragPos;
-    gNormal = normal;
-    gAlbedoSpec.rgb = texture(texture0, TexCoords).rgb;
-    gAlbedoSpec.a = material.specular;
+    if (hasNormalMap) {
+        // decoded the way MikkTSpace expects, the bitangent comes from the interpolated vectors
+        // before either is normalized and the result is normalized once at the end
+        vec3 tangentNormal = texture(normalMap, TexCoords).xyz * 2.0 - 1.0;
+        vec3 bitangent = BitangentSign * cross(Normal, Tangent);
+        normal = normalize(tangentNormal.x * Tangent + tangentNormal.y * bitangent + tangentNormal.z * Normal);
+    }
+    // color textures are stored in srgb, lighting is done in linear
+    vec3 albedo = pow(texture(texture0, TexCoords).rgb, vec3(2.2));
+    gPosition = vec4(FragPos, float(material.model));
+
+    vec3 emissive = material.emissive;
+    if (hasEmissiveMap)
+        emissive *= pow(texture(emissiveMap, TexCoords).rgb, vec3(2.2));
+    float occlusion = material.ambientOcclusion;
+    if (hasOcclusionMap)
+        occlusion *= texture(occlusionMap, TexCoords).r;
+    gEmissive = vec4(emissive, occlusion);
+
+    if (material.model == 0) {
+        gNormal = vec4(normal, material.shininess);
+        gAlbedoSpec = vec4(albedo, material.specular);
+    } else {
+        float metallic = material.metallic;
+        float roughness = material.roughness;
+        // gltf packs roughness into green and metallic into blue
+        if (hasMetallicRoughnessMap) {
+            vec4 metallicRoughness = texture(metallicRoughnessMap, TexCoords);
+            roughness *= metallicRoughness.g;
+            metallic *= metallicRoughness.b;
+        }
+        gNormal = vec4(normal, roughness);
+        gAlbedoSpec = vec4(albedo * material.baseColor, metallic);
+    }
         
     //float debug = (norm.x > 0.5) ? 1 : 0;
     //FragColor = vec4(debug,debug,debug,1.0);
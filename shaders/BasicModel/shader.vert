@@ -3,10 +3,22 @@ layout (location = 0) in vec3 aPos;
 layout (location = 1) in vec3 aColor;
 layout (location = 2) in vec3 aNormal;
 layout (location = 3) in vec2 aTexCoord;
+// w is the sign of the bitangent
+layout (location = 4) in vec4 aTangent;
+// only read when instanced, on top of model
+layout (location = 5) in mat4 aInstanceModel;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+uniform bool instanced;
+
+layout (std140) uniform Camera {
+    mat4 view;
+    mat4 projection;
+    mat4 inverseView;
+    vec3 viewPos;
+    float time;
+    vec2 screenSize;
+} camera;
 
 uniform mat4 light_projection;
 uniform mat4 light_view;
@@ -15,18 +27,24 @@ out vec3 VertColor;
 out vec2 TexCoords;
 out vec3 VertPos;
 out vec3 Normal;
+out vec3 Tangent;
+out float BitangentSign;
 
 out vec3 FragPos;
 out vec4 FragPosLightSpace;
 
 void main()
 {
-    gl_Position = projection * view * model * vec4(aPos, 1.0);
-    FragPos = vec3(model * vec4(aPos, 1.0));
+    mat4 modelMatrix = instanced ? model * aInstanceModel : model;
+    gl_Position = camera.projection * camera.view * modelMatrix * vec4(aPos, 1.0);
+    FragPos = vec3(modelMatrix * vec4(aPos, 1.0));
     FragPosLightSpace = light_projection * light_view * vec4(FragPos,1.0);
 
     VertColor = aColor;
     VertPos = aPos;
     TexCoords = aTexCoord;
-    Normal = mat3(transpose(inverse(model))) * aNormal;
+    Normal = mat3(transpose(inverse(modelMatrix))) * aNormal;
+    // tangents lie along the surface, so they move with the model matrix itself
+    Tangent = mat3(modelMatrix) * aTangent.xyz;
+    BitangentSign = aTangent.w;
 }
\ No newline at end of file
@@ -3,11 +3,15 @@ layout (location = 0) in vec3 aPos;
 layout (location = 1) in vec3 aColor;
 layout (location = 2) in vec3 aNormal;
 layout (location = 3) in vec2 aTexCoord;
+// only read when instanced, on top of model
+layout (location = 5) in mat4 aInstanceModel;
 
 uniform mat4 model;
+uniform bool instanced;
 uniform mat4 view;
 uniform mat4 projection;
 void main()
 {
-    gl_Position = projection * view * model * vec4(aPos, 1.0);
+    mat4 modelMatrix = instanced ? model * aInstanceModel : model;
+    gl_Position = projection * view * modelMatrix * vec4(aPos, 1.0);
 }
\ No newline at end of file
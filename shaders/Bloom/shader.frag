@@ -0,0 +1,18 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+// the hdr scene
+uniform sampler2D image;
+// the blurred bright parts of it, at half size
+uniform sampler2D bloom;
+
+uniform float intensity;
+
+void main()
+{
+    vec3 color = texture(image, TexCoords).rgb + texture(bloom, TexCoords).rgb * intensity;
+
+    FragColor = vec4(color, 1.0);
+}
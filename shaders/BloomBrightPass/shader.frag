@@ -0,0 +1,25 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+// the hdr scene
+uniform sampler2D image;
+
+// colors brighter than this bloom
+uniform float threshold;
+// how far below the threshold the bloom fades in, as a fraction of it
+uniform float knee;
+
+void main()
+{
+    vec3 color = texture(image, TexCoords).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    // a soft knee so bloom doesn't pop in at the threshold
+    float softness = threshold * knee + 0.00001;
+    float soft = clamp(brightness - threshold + softness, 0.0, 2.0 * softness);
+    soft = soft * soft / (4.0 * softness);
+    float contribution = max(soft, brightness - threshold) / max(brightness, 0.00001);
+
+    FragColor = vec4(color * contribution, 1.0);
+}
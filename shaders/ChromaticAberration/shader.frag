@@ -0,0 +1,19 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+
+// how far red and blue move apart at the corners, as a fraction of the screen
+uniform float strength;
+
+void main()
+{
+    vec2 offset = (TexCoords - 0.5) * strength;
+    float red = texture(image, clamp(TexCoords + offset, 0.0, 1.0)).r;
+    float green = texture(image, TexCoords).g;
+    float blue = texture(image, clamp(TexCoords - offset, 0.0, 1.0)).b;
+
+    FragColor = vec4(red, green, blue, 1.0);
+}
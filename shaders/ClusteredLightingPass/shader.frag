@@ -0,0 +1,74 @@
+#version 430 core
+#include "../Common/Lighting.glsl"
+out vec4 FragColor;
+  
+in vec2 TexCoords;
+
+struct Light {
+    vec3 Position;
+    float Radius;
+    vec3 Color;
+    float ShadowBias;
+};
+
+layout (std140) uniform Camera {
+    mat4 view;
+    mat4 projection;
+    mat4 inverseView;
+    vec3 viewPos;
+    float time;
+    vec2 screenSize;
+} camera;
+
+layout (std430, binding = 0) readonly buffer ClusterLights {
+    Light lights[];
+};
+layout (std430, binding = 1) readonly buffer ClusterLightCounts {
+    uint lightCounts[];
+};
+layout (std430, binding = 2) readonly buffer ClusterLightIndices {
+    uint lightIndices[];
+};
+
+uniform uvec3 clusterGrid;
+uniform uint maxLightsPerCluster;
+uniform float zNear;
+uniform float zFar;
+// tints every fragment by how many lights its cluster has
+uniform bool debugClusters;
+
+void main()
+{             
+    // retrieve data from G-buffer
+    Surface surface = ReadSurface(TexCoords);
+    vec3 FragPos = surface.Position;
+
+    // find the cluster, the inverse of SliceDepth in the culling shader
+    float viewDepth = max(-(camera.view * vec4(FragPos, 1.0)).z, zNear);
+    float slice = floor(log(viewDepth / zNear) / log(zFar / zNear) * float(clusterGrid.z));
+    uvec3 cluster = uvec3(
+        clamp(gl_FragCoord.xy / camera.screenSize * vec2(clusterGrid.xy), vec2(0.0), vec2(clusterGrid.xy) - 1.0),
+        clamp(slice, 0.0, float(clusterGrid.z) - 1.0)
+    );
+    uint clusterIndex = cluster.x + cluster.y * clusterGrid.x + cluster.z * clusterGrid.x * clusterGrid.y;
+    uint count = lightCounts[clusterIndex];
+
+    vec3 viewDir = normalize(camera.viewPos - FragPos);
+    vec3 lighting = vec3(0.0);
+    for (uint i = 0u; i < count; i++) {
+        Light light = lights[lightIndices[clusterIndex * maxLightsPerCluster + i]];
+        // the same lighting as the point lighting pass, without shadows
+        vec3 lightDir = normalize(light.Position - FragPos);
+        float distance = length(light.Position - FragPos);
+        float attenuation = clamp(1-distance*distance/(light.Radius*light.Radius), 0.0, 1.0);
+        attenuation *= attenuation;
+        lighting += Shade(surface, lightDir, viewDir, light.Color * attenuation);
+    }
+    if (debugClusters) {
+        // full red at 16 lights
+        float heat = clamp(float(count) / 16.0, 0.0, 1.0);
+        lighting += vec3(heat, 1.0 - heat, 0.0) * (count > 0u ? 0.25 : 0.0);
+    }
+
+    FragColor = vec4(lighting, 1.0);
+} 
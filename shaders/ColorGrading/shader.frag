@@ -0,0 +1,22 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+// maps a color to its graded color, red along x, green along y and blue along z
+uniform sampler3D lut;
+
+// 0 is the image as it is, 1 fully graded
+uniform float strength;
+
+void main()
+{
+    vec3 color = clamp(texture(image, TexCoords).rgb, 0.0, 1.0);
+    // sample the middle of the first and last texels so 0 and 1 map exactly onto them
+    float size = float(textureSize(lut, 0).x);
+    vec3 position = color * ((size - 1.0) / size) + 0.5 / size;
+    vec3 graded = texture(lut, position).rgb;
+
+    FragColor = vec4(mix(color, graded, strength), 1.0);
+}
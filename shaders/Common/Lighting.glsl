@@ -0,0 +1,75 @@
+// the g-buffer and shading every lighting pass shares, shaders add it with #include right after
+// their #version line
+
+uniform sampler2D gPosition;
+uniform sampler2D gNormal;
+uniform sampler2D gAlbedoSpec;
+
+const float PI = 3.14159265359;
+
+// what the geometry pass wrote into the g-buffer for one pixel
+struct Surface {
+    vec3 Position;
+    vec3 Normal;
+    vec3 Albedo;
+    // 0 phong, 1 metallic roughness
+    int Model;
+    // roughness, or the shininess for phong
+    float Roughness;
+    // metallic, or the specular strength for phong
+    float Metallic;
+};
+
+Surface ReadSurface(vec2 uv)
+{
+    vec4 position = texture(gPosition, uv);
+    vec4 normal = texture(gNormal, uv);
+    vec4 albedo = texture(gAlbedoSpec, uv);
+    return Surface(position.xyz, normal.xyz, albedo.rgb, int(position.a + 0.5), normal.a, albedo.a);
+}
+
+float DistributionGGX(float NdotH, float roughness)
+{
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float denom = NdotH * NdotH * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denom * denom);
+}
+
+float GeometrySmith(float NdotV, float NdotL, float roughness)
+{
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    return NdotV / (NdotV * (1.0 - k) + k) * NdotL / (NdotL * (1.0 - k) + k);
+}
+
+vec3 FresnelSchlick(float cosTheta, vec3 F0)
+{
+    return F0 + (1.0 - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+// light reflected towards viewDir from a light of color lightColor shining along -lightDir
+vec3 Shade(Surface surface, vec3 lightDir, vec3 viewDir, vec3 lightColor)
+{
+    vec3 N = surface.Normal;
+    vec3 H = normalize(lightDir + viewDir);
+    float NdotL = max(dot(N, lightDir), 0.0);
+    if (surface.Model == 0) {
+        // blinn-phong
+        vec3 diffuse = NdotL * lightColor;
+        float spec = pow(max(dot(N, H), 0.0), surface.Roughness);
+        vec3 specular = lightColor * spec * surface.Metallic;
+        return (diffuse + specular) * surface.Albedo;
+    }
+    // cook-torrance with a ggx distribution, light colors are scaled by pi so a white light
+    // on a white rough surface is as bright as it is with phong
+    vec3 radiance = lightColor * PI;
+    float NdotV = max(dot(N, viewDir), 0.0001);
+    float roughness = max(surface.Roughness, 0.04);
+    vec3 F0 = mix(vec3(0.04), surface.Albedo, surface.Metallic);
+    vec3 F = FresnelSchlick(max(dot(H, viewDir), 0.0), F0);
+    float D = DistributionGGX(max(dot(N, H), 0.0), roughness);
+    float G = GeometrySmith(NdotV, NdotL, roughness);
+    vec3 specular = D * G * F / (4.0 * NdotV * NdotL + 0.0001);
+    vec3 kD = (vec3(1.0) - F) * (1.0 - surface.Metallic);
+    return (kD * surface.Albedo / PI + specular) * radiance * NdotL;
+}
@@ -1,14 +1,97 @@
 #version 330 core
-in vec3 VertColor;
-in vec2 TexCoord;
-in vec3 VertPos;
-
 out vec4 FragColor;
 
-uniform sampler2D texture0;
-uniform vec3 color;
+in vec2 TexCoords;
+
+uniform sampler2D gPosition;
+uniform sampler2D gNormal;
+uniform sampler2D gAlbedoSpec;
+uniform sampler2D gEmissive;
+uniform sampler2D gLighting;
+// shadow maps are read through the raw depth sampler, so these are depths and not comparisons
+uniform sampler2D shadowMap;
+uniform sampler2DArray shadowMapArray;
+uniform int shadowLayer;
+// point light shadow cubemaps already hold the distance to the light divided by its radius
+uniform samplerCube shadowCubemap;
+// in the order of GL_TEXTURE_CUBE_MAP_POSITIVE_X + face
+uniform int cubemapFace;
+
+// the buffer that is shown, has to match DebugBuffer::shader_mode
+uniform int mode;
+// positions from -positionRange to positionRange meters go from black to full color
+uniform float positionRange;
+// the distance from the camera that is white in the depth view
+uniform float depthRange;
+// the planes of the spot light projection, its depth has to be linearized
+uniform float nearPlane;
+uniform float farPlane;
+
+layout (std140) uniform Camera {
+    mat4 view;
+    mat4 projection;
+    mat4 inverseView;
+    vec3 viewPos;
+    float time;
+    vec2 screenSize;
+} camera;
+
+float LinearizeDepth(float depth)
+{
+    float z = depth * 2.0 - 1.0;
+    return (2.0 * nearPlane * farPlane) / (farPlane + nearPlane - z * (farPlane - nearPlane));
+}
+
+// the direction that samples the texel at uv of a face, from the cube map face selection table of the gl spec
+vec3 CubemapDirection(int face, vec2 uv)
+{
+    vec2 st = uv * 2.0 - 1.0;
+    if (face == 0) return vec3(1.0, -st.y, -st.x);
+    if (face == 1) return vec3(-1.0, -st.y, st.x);
+    if (face == 2) return vec3(st.x, 1.0, st.y);
+    if (face == 3) return vec3(st.x, -1.0, -st.y);
+    if (face == 4) return vec3(st.x, -st.y, 1.0);
+    return vec3(-st.x, -st.y, -1.0);
+}
 
 void main()
 {
-    FragColor = vec4(color, 1.0); // set all 4 vector values to 1.0
-}
\ No newline at end of file
+    vec3 FragPos = texture(gPosition, TexCoords).rgb;
+    vec3 Normal = texture(gNormal, TexCoords).rgb;
+    vec4 AlbedoSpec = texture(gAlbedoSpec, TexCoords);
+    vec4 Emissive = texture(gEmissive, TexCoords);
+    // nothing was drawn where the normal is zero
+    bool background = dot(Normal, Normal) == 0.0;
+
+    vec3 color = vec3(0.0);
+    if (mode == 0) {
+        color = clamp(FragPos / positionRange * 0.5 + 0.5, 0.0, 1.0);
+    } else if (mode == 1) {
+        color = Normal * 0.5 + 0.5;
+    } else if (mode == 2) {
+        color = AlbedoSpec.rgb;
+    } else if (mode == 3) {
+        color = vec3(AlbedoSpec.a);
+    } else if (mode == 4) {
+        color = Emissive.rgb;
+    } else if (mode == 5) {
+        color = vec3(Emissive.a);
+    } else if (mode == 6) {
+        color = texture(gLighting, TexCoords).rgb;
+    } else if (mode == 7) {
+        float depth = -(camera.view * vec4(FragPos, 1.0)).z;
+        color = vec3(background ? 1.0 : clamp(depth / depthRange, 0.0, 1.0));
+    } else if (mode == 8) {
+        color = vec3(LinearizeDepth(texture(shadowMap, TexCoords).r) / farPlane);
+    } else if (mode == 9) {
+        // directional shadow maps are orthographic, their depth is already linear
+        color = vec3(texture(shadowMapArray, vec3(TexCoords, shadowLayer)).r);
+    } else if (mode == 10) {
+        color = vec3(texture(shadowCubemap, CubemapDirection(cubemapFace, TexCoords)).r);
+    }
+    if (background && mode < 7) {
+        color = vec3(0.0);
+    }
+
+    FragColor = vec4(color, 1.0);
+}
@@ -1,20 +1,11 @@
 #version 330 core
 layout (location = 0) in vec3 aPos;
-layout (location = 1) in vec3 aColor;
-layout (location = 2) in vec2 aTexCoord;
+layout (location = 3) in vec2 aTexCoord;
 
-uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
-
-out vec3 VertColor;
-out vec2 TexCoord;
-out vec3 VertPos;
+out vec2 TexCoords;
 
 void main()
 {
-    gl_Position = projection * view * model * vec4(aPos, 1.0);
-    VertColor = aColor;
-    VertPos = aPos;
-    TexCoord = aTexCoord;
-}
\ No newline at end of file
+    gl_Position = vec4(aPos, 1.0);
+    TexCoords = aTexCoord;
+}
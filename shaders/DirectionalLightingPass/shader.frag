@@ -1,65 +1,206 @@
 #version 330 core
+#include "../Common/Lighting.glsl"
 out vec4 FragColor;
 
   
 in vec2 TexCoords;
 
+#define MAX_CASCADES 8
+#define MAX_DIRECTIONAL_LIGHTS 4
 
-uniform sampler2D gPosition;
-uniform sampler2D gNormal;
-uniform sampler2D gAlbedoSpec;
 uniform sampler2D gLighting;
-uniform sampler2D shadowMap;
+uniform sampler2DArrayShadow shadowMap;
+uniform sampler2DArray shadowMapRaw;
 
 struct Light {
+    mat4 LightSpaceMatrices[MAX_CASCADES];
+    // four distances to a vec4, a float array would be padded to 16 bytes per element
+    vec4 CascadePlaneDistances[MAX_CASCADES / 4];
     vec3 Position;
-    vec3 Color;
+    int CascadeCount;
     vec3 Direction;
+    bool DebugCascades;
+    vec3 Color;
+};
+
+// filterMode: 0 hard, 1 pcf, 2 poisson pcf, 3 pcss. filter is a reserved word in glsl
+struct ShadowSettings {
+    int filterMode;
+    int kernelSize;
+    float lightSize;
+    float constantBias;
+    float slopeBias;
+    float normalOffset;
+};
+
+layout (std140) uniform Camera {
+    mat4 view;
+    mat4 projection;
+    mat4 inverseView;
+    vec3 viewPos;
+    float time;
+    vec2 screenSize;
+} camera;
+
+layout (std140) uniform DirectionalLights {
+    Light directionalLights[MAX_DIRECTIONAL_LIGHTS];
+    int directionalLightCount;
 };
 
-uniform Light light;
-uniform vec3 viewPos;
-uniform mat4 lightSpaceMatrix;
+uniform ShadowSettings shadowSettings;
+// which of the directional lights this pass draws
+uniform int lightIndex;
+
+Light light;
+
+const vec3 cascadeColors[MAX_CASCADES] = vec3[](
+    vec3(1.0, 0.2, 0.2),
+    vec3(0.2, 1.0, 0.2),
+    vec3(0.2, 0.2, 1.0),
+    vec3(1.0, 1.0, 0.2),
+    vec3(1.0, 0.2, 1.0),
+    vec3(0.2, 1.0, 1.0),
+    vec3(1.0, 0.6, 0.2),
+    vec3(0.6, 0.2, 1.0)
+);
 
+int SelectCascade(vec3 fragPos)
+{
+    // pick the first cascade whose far plane is past the fragment
+    float depth = abs((camera.view * vec4(fragPos, 1.0)).z);
+    for (int i = 0; i < light.CascadeCount; ++i) {
+        if (depth < light.CascadePlaneDistances[i / 4][i % 4]) {
+            return i;
+        }
+    }
+    return light.CascadeCount;
+}
+
+const vec2 poissonDisk[16] = vec2[](
+    vec2(-0.94201624, -0.39906216),
+    vec2(0.94558609, -0.76890725),
+    vec2(-0.094184101, -0.92938870),
+    vec2(0.34495938, 0.29387760),
+    vec2(-0.91588581, 0.45771432),
+    vec2(-0.81544232, -0.87912464),
+    vec2(-0.38277543, 0.27676845),
+    vec2(0.97484398, 0.75648379),
+    vec2(0.44323325, -0.97511554),
+    vec2(0.53742981, -0.47373420),
+    vec2(-0.26496911, -0.41893023),
+    vec2(0.79197514, 0.19090188),
+    vec2(-0.24188840, 0.99706507),
+    vec2(-0.81409955, 0.91437590),
+    vec2(0.19984126, 0.78641367),
+    vec2(0.14383161, -0.14100790)
+);
 
-float ShadowCalculation(vec4 fragPosLightSpace)
+float SampleHard(vec2 uv, int cascade, float depth)
 {
+    return depth > texture(shadowMapRaw, vec3(uv, cascade)).r ? 1.0 : 0.0;
+}
+
+float SamplePcf(vec2 uv, int cascade, float depth)
+{
+    vec2 texelSize = 1.0 / vec2(textureSize(shadowMapRaw, 0).xy);
+    float offset = float(shadowSettings.kernelSize - 1) / 2.0;
+    float lit = 0.0;
+    for (int x = 0; x < shadowSettings.kernelSize; ++x) {
+        for (int y = 0; y < shadowSettings.kernelSize; ++y) {
+            vec2 sampleUv = uv + (vec2(x, y) - offset) * texelSize;
+            lit += texture(shadowMap, vec4(sampleUv, cascade, depth));
+        }
+    }
+    return 1.0 - lit / float(shadowSettings.kernelSize * shadowSettings.kernelSize);
+}
+
+float SamplePoisson(vec2 uv, int cascade, float depth, float radius)
+{
+    float lit = 0.0;
+    for (int i = 0; i < 16; ++i) {
+        lit += texture(shadowMap, vec4(uv + poissonDisk[i] * radius, cascade, depth));
+    }
+    return 1.0 - lit / 16.0;
+}
+
+// average depth of the occluders between the light and the fragment, -1 when nothing occludes it
+float FindBlockerDepth(vec2 uv, int cascade, float depth, float searchRadius)
+{
+    float blockerSum = 0.0;
+    int blockerCount = 0;
+    for (int i = 0; i < 16; ++i) {
+        float sampleDepth = texture(shadowMapRaw, vec3(uv + poissonDisk[i] * searchRadius, cascade)).r;
+        if (sampleDepth < depth) {
+            blockerSum += sampleDepth;
+            blockerCount++;
+        }
+    }
+    if (blockerCount == 0) {
+        return -1.0;
+    }
+    return blockerSum / float(blockerCount);
+}
+
+float SamplePcss(vec2 uv, int cascade, float depth)
+{
+    // the light is infinitely far away, so the penumbra only depends on the receiver to blocker distance,
+    // the widest possible penumbra is the one cast by a blocker at depth 0
+    float searchRadius = clamp(shadowSettings.lightSize * depth, 0.0, 0.05);
+    float blockerDepth = FindBlockerDepth(uv, cascade, depth, searchRadius);
+    if (blockerDepth < 0.0) {
+        return 0.0;
+    }
+    float penumbra = (depth - blockerDepth) * shadowSettings.lightSize;
+    return SamplePoisson(uv, cascade, depth, clamp(penumbra, 0.0, 0.05));
+}
+
+float ShadowCalculation(vec3 fragPos, vec3 normal, vec3 lightDir, int cascade)
+{
+    // fragments past the last cascade are never shadowed
+    if (cascade >= light.CascadeCount) {
+        return 0.0;
+    }
+    // push the lookup position along the normal so surfaces don't shadow themselves
+    vec4 fragPosLightSpace = light.LightSpaceMatrices[cascade] * vec4(fragPos + normal * shadowSettings.normalOffset, 1.0);
     // perform perspective divide
     vec3 projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
     // transform to [0,1] range
     projCoords = projCoords * 0.5 + 0.5;
-    // get closest depth value from light's perspective (using [0,1] range fragPosLight as coords)
-    float closestDepth = texture(shadowMap, projCoords.xy).r; 
-    // get depth of current fragment from light's perspective
-    float currentDepth = projCoords.z;
-    // check whether current frag pos is in shadow
-    float shadow = currentDepth > closestDepth  ? 1.0 : 0.0;
-
-    return shadow;
+    // bias grows with the slope of the surface relative to the light
+    float cosTheta = clamp(dot(normal, lightDir), 0.001, 1.0);
+    float tanTheta = sqrt(1.0 - cosTheta * cosTheta) / cosTheta;
+    float bias = shadowSettings.constantBias + shadowSettings.slopeBias * min(tanTheta, 10.0);
+    float currentDepth = projCoords.z - bias;
+
+    if (shadowSettings.filterMode == 1) {
+        return SamplePcf(projCoords.xy, cascade, currentDepth);
+    } else if (shadowSettings.filterMode == 2) {
+        vec2 texelSize = 1.0 / vec2(textureSize(shadowMapRaw, 0).xy);
+        return SamplePoisson(projCoords.xy, cascade, currentDepth, texelSize.x * float(shadowSettings.kernelSize));
+    } else if (shadowSettings.filterMode == 3) {
+        return SamplePcss(projCoords.xy, cascade, currentDepth);
+    }
+    return SampleHard(projCoords.xy, cascade, currentDepth);
 }  
 
 void main()
 {             
+    light = directionalLights[lightIndex];
     // retrieve data from G-buffer
-    vec3 FragPos = texture(gPosition, TexCoords).rgb;
-    vec3 Normal = texture(gNormal, TexCoords).rgb;
-    vec3 Albedo = texture(gAlbedoSpec, TexCoords).rgb;
-    float Specular = texture(gAlbedoSpec, TexCoords).a;
-    vec4 FragPosLightSpace = lightSpaceMatrix * vec4(FragPos,1.0);
+    Surface surface = ReadSurface(TexCoords);
+    vec3 FragPos = surface.Position;
+    vec3 Normal = surface.Normal;
     
-    // diffuse
     vec3 lightDir = normalize(light.Position - FragPos);
-    float diff = max(dot(lightDir, Normal), 0.0);
-    vec3 diffuse = diff * light.Color;
-    // specular
-    vec3 viewDir = normalize(viewPos - FragPos);
-    float spec = 0.0;
-    vec3 halfwayDir = normalize(lightDir + viewDir);  
-    spec = pow(max(dot(Normal, halfwayDir), 0.0), 64.0);
-    vec3 specular = spec * light.Color;   
+    vec3 viewDir = normalize(camera.viewPos - FragPos);
     // calculate shadow
-    float shadow = ShadowCalculation(FragPosLightSpace);       
-    vec3 lighting = ((1.0 - shadow) * (diffuse + specular)); 
+    int cascade = SelectCascade(FragPos);
+    float shadow = ShadowCalculation(FragPos, Normal, lightDir, cascade);
+    vec3 lighting = (1.0 - shadow) * Shade(surface, lightDir, viewDir, light.Color);
+
+    if (light.DebugCascades && cascade < light.CascadeCount) {
+        lighting = mix(lighting, cascadeColors[cascade], 0.35);
+    }
 
     FragColor = vec4(lighting.rgb, 1.0);
-} 
\ No newline at end of file
+} 
@@ -0,0 +1,29 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+uniform vec2 screenSize;
+uniform float time;
+
+uniform float intensity;
+// grain in the dark parts is strongest, bright parts get this much of it
+uniform float highlights;
+
+float Random(vec2 position)
+{
+    return fract(sin(dot(position, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main()
+{
+    vec3 color = texture(image, TexCoords).rgb;
+    // a new pattern every frame
+    vec2 pixel = floor(TexCoords * screenSize) + fract(time * vec2(17.0, 31.0)) * 100.0;
+    float noise = Random(pixel) - 0.5;
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    float amount = intensity * mix(1.0, highlights, luminance);
+
+    FragColor = vec4(clamp(color + noise * amount, 0.0, 1.0), 1.0);
+}
@@ -6,18 +6,24 @@ in vec2 TexCoords;
 uniform sampler2D gPosition;
 uniform sampler2D gNormal;
 uniform sampler2D gAlbedoSpec;
+uniform sampler2D gEmissive;
 uniform sampler2D gLighting;
 
+// light that reaches everything, scaled by the ambient occlusion
+uniform vec3 ambientLight;
+
 void main()
 {             
     // retrieve data from G-buffer
     vec3 FragPos = texture(gPosition, TexCoords).rgb;
     vec3 Normal = texture(gNormal, TexCoords).rgb;
     vec3 Albedo = texture(gAlbedoSpec, TexCoords).rgb;
-    float Specular = texture(gAlbedoSpec, TexCoords).a;
+    vec3 Emissive = texture(gEmissive, TexCoords).rgb;
+    float AmbientOcclusion = texture(gEmissive, TexCoords).a;
+    // the lighting passes already multiplied in the albedo
     vec3 Lighting = texture(gLighting, TexCoords).rgb;
 
-    vec3 color = Albedo * Lighting;
+    vec3 color = Lighting + ambientLight * Albedo * AmbientOcclusion + Emissive;
     
     FragColor = vec4(color, 1.0);
-} 
\ No newline at end of file
+}
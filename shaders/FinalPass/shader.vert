@@ -4,10 +4,6 @@ layout (location = 1) in vec3 aColor;
 layout (location = 2) in vec3 aNormal;
 layout (location = 3) in vec2 aTexCoord;
 
-uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
-
 out vec3 VertColor;
 out vec2 TexCoords;
 out vec3 VertPos;
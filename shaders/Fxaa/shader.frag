@@ -0,0 +1,134 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+// the tone mapped image, fxaa works on the colors the screen shows
+uniform sampler2D image;
+uniform vec2 screenSize;
+
+// the contrast an edge needs, relative to the brightest neighbour
+uniform float edgeThreshold;
+// contrast darker than this is never an edge
+uniform float edgeThresholdMin;
+// how much single pixel details are smoothed
+uniform float subpixelQuality;
+
+const int SEARCH_STEPS = 10;
+
+float Luma(vec2 position)
+{
+    return dot(texture(image, position).rgb, vec3(0.299, 0.587, 0.114));
+}
+
+void main()
+{
+    vec2 texel = 1.0 / screenSize;
+    vec3 color = texture(image, TexCoords).rgb;
+
+    float lumaCenter = Luma(TexCoords);
+    float lumaDown = Luma(TexCoords + vec2(0.0, -texel.y));
+    float lumaUp = Luma(TexCoords + vec2(0.0, texel.y));
+    float lumaLeft = Luma(TexCoords + vec2(-texel.x, 0.0));
+    float lumaRight = Luma(TexCoords + vec2(texel.x, 0.0));
+
+    float lumaMin = min(lumaCenter, min(min(lumaDown, lumaUp), min(lumaLeft, lumaRight)));
+    float lumaMax = max(lumaCenter, max(max(lumaDown, lumaUp), max(lumaLeft, lumaRight)));
+    float lumaRange = lumaMax - lumaMin;
+    if (lumaRange < max(edgeThresholdMin, lumaMax * edgeThreshold)) {
+        FragColor = vec4(color, 1.0);
+        return;
+    }
+
+    float lumaDownLeft = Luma(TexCoords + vec2(-texel.x, -texel.y));
+    float lumaUpRight = Luma(TexCoords + vec2(texel.x, texel.y));
+    float lumaUpLeft = Luma(TexCoords + vec2(-texel.x, texel.y));
+    float lumaDownRight = Luma(TexCoords + vec2(texel.x, -texel.y));
+
+    float lumaDownUp = lumaDown + lumaUp;
+    float lumaLeftRight = lumaLeft + lumaRight;
+    float lumaLeftCorners = lumaDownLeft + lumaUpLeft;
+    float lumaDownCorners = lumaDownLeft + lumaDownRight;
+    float lumaRightCorners = lumaDownRight + lumaUpRight;
+    float lumaUpCorners = lumaUpRight + lumaUpLeft;
+
+    // whether the edge runs along x or along y
+    float edgeHorizontal = abs(-2.0 * lumaLeft + lumaLeftCorners)
+        + abs(-2.0 * lumaCenter + lumaDownUp) * 2.0
+        + abs(-2.0 * lumaRight + lumaRightCorners);
+    float edgeVertical = abs(-2.0 * lumaUp + lumaUpCorners)
+        + abs(-2.0 * lumaCenter + lumaLeftRight) * 2.0
+        + abs(-2.0 * lumaDown + lumaDownCorners);
+    bool isHorizontal = edgeHorizontal >= edgeVertical;
+
+    // which side of the pixel the edge is on
+    float luma1 = isHorizontal ? lumaDown : lumaLeft;
+    float luma2 = isHorizontal ? lumaUp : lumaRight;
+    float gradient1 = luma1 - lumaCenter;
+    float gradient2 = luma2 - lumaCenter;
+    bool is1Steepest = abs(gradient1) >= abs(gradient2);
+    float gradientScaled = 0.25 * max(abs(gradient1), abs(gradient2));
+
+    float stepLength = isHorizontal ? texel.y : texel.x;
+    float lumaLocalAverage;
+    if (is1Steepest) {
+        stepLength = -stepLength;
+        lumaLocalAverage = 0.5 * (luma1 + lumaCenter);
+    } else {
+        lumaLocalAverage = 0.5 * (luma2 + lumaCenter);
+    }
+
+    // walk along the edge both ways until it ends
+    vec2 edgePosition = TexCoords;
+    if (isHorizontal) {
+        edgePosition.y += stepLength * 0.5;
+    } else {
+        edgePosition.x += stepLength * 0.5;
+    }
+    vec2 offset = isHorizontal ? vec2(texel.x, 0.0) : vec2(0.0, texel.y);
+    vec2 position1 = edgePosition - offset;
+    vec2 position2 = edgePosition + offset;
+    float lumaEnd1 = Luma(position1) - lumaLocalAverage;
+    float lumaEnd2 = Luma(position2) - lumaLocalAverage;
+    bool reached1 = abs(lumaEnd1) >= gradientScaled;
+    bool reached2 = abs(lumaEnd2) >= gradientScaled;
+    for (int i = 0; i < SEARCH_STEPS && !(reached1 && reached2); i++) {
+        if (!reached1) {
+            position1 -= offset;
+            lumaEnd1 = Luma(position1) - lumaLocalAverage;
+            reached1 = abs(lumaEnd1) >= gradientScaled;
+        }
+        if (!reached2) {
+            position2 += offset;
+            lumaEnd2 = Luma(position2) - lumaLocalAverage;
+            reached2 = abs(lumaEnd2) >= gradientScaled;
+        }
+    }
+
+    float distance1 = isHorizontal ? (TexCoords.x - position1.x) : (TexCoords.y - position1.y);
+    float distance2 = isHorizontal ? (position2.x - TexCoords.x) : (position2.y - TexCoords.y);
+    bool isDirection1 = distance1 < distance2;
+    float distanceFinal = min(distance1, distance2);
+    float edgeThickness = distance1 + distance2;
+
+    // only blend when the end of the edge that is closest goes the other way to the center
+    bool isLumaCenterSmaller = lumaCenter < lumaLocalAverage;
+    bool correctVariation = ((isDirection1 ? lumaEnd1 : lumaEnd2) < 0.0) != isLumaCenterSmaller;
+    float pixelOffset = correctVariation ? -distanceFinal / edgeThickness + 0.5 : 0.0;
+
+    // single pixel details are blended by how much they stand out from their neighbours
+    float lumaAverage = (1.0 / 12.0) * (2.0 * (lumaDownUp + lumaLeftRight) + lumaLeftCorners + lumaRightCorners);
+    float subPixelOffset1 = clamp(abs(lumaAverage - lumaCenter) / lumaRange, 0.0, 1.0);
+    float subPixelOffset2 = (-2.0 * subPixelOffset1 + 3.0) * subPixelOffset1 * subPixelOffset1;
+    float subPixelOffset = subPixelOffset2 * subPixelOffset2 * subpixelQuality;
+    pixelOffset = max(pixelOffset, subPixelOffset);
+
+    vec2 finalPosition = TexCoords;
+    if (isHorizontal) {
+        finalPosition.y += pixelOffset * stepLength;
+    } else {
+        finalPosition.x += pixelOffset * stepLength;
+    }
+
+    FragColor = vec4(texture(image, finalPosition).rgb, 1.0);
+}
@@ -4,8 +4,15 @@ layout (location = 1) in vec3 aColor;
 layout (location = 2) in vec2 aTexCoord;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+
+layout (std140) uniform Camera {
+    mat4 view;
+    mat4 projection;
+    mat4 inverseView;
+    vec3 viewPos;
+    float time;
+    vec2 screenSize;
+} camera;
 
 out vec3 VertColor;
 out vec2 TexCoord;
@@ -13,7 +20,7 @@ out vec3 VertPos;
 
 void main()
 {
-    gl_Position = projection * view * model * vec4(aPos, 1.0);
+    gl_Position = camera.projection * camera.view * model * vec4(aPos, 1.0);
     VertColor = aColor;
     VertPos = aPos;
     TexCoord = aTexCoord;
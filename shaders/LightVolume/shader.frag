@@ -0,0 +1,6 @@
+#version 330 core
+
+// only marks the stencil buffer, nothing is written to the color attachments
+void main()
+{
+}
@@ -0,0 +1,12 @@
+#version 330 core
+in vec4 FragPos;
+
+uniform vec3 lightPos;
+uniform float farPlane;
+
+void main()
+{
+    // store the linear distance to the light, mapped to [0,1]
+    float lightDistance = length(FragPos.xyz - lightPos);
+    gl_FragDepth = lightDistance / farPlane;
+}
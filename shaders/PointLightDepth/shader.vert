@@ -0,0 +1,16 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aColor;
+layout (location = 2) in vec3 aNormal;
+layout (location = 3) in vec2 aTexCoord;
+// only read when instanced, on top of model
+layout (location = 5) in mat4 aInstanceModel;
+
+uniform mat4 model;
+uniform bool instanced;
+
+void main()
+{
+    mat4 modelMatrix = instanced ? model * aInstanceModel : model;
+    gl_Position = modelMatrix * vec4(aPos, 1.0);
+}
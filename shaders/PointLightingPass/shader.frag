@@ -1,46 +1,71 @@
 #version 330 core
+#include "../Common/Lighting.glsl"
 out vec4 FragColor;
   
-in vec2 TexCoords;
 
-uniform sampler2D gPosition;
-uniform sampler2D gNormal;
-uniform sampler2D gAlbedoSpec;
+uniform samplerCube shadowMap;
+
+#define MAX_POINT_LIGHTS 64
 
 struct Light {
     vec3 Position;
+    float Radius;
     vec3 Color;
+    float ShadowBias;
+};
 
-    float Radius;
+layout (std140) uniform Camera {
+    mat4 view;
+    mat4 projection;
+    mat4 inverseView;
+    vec3 viewPos;
+    float time;
+    vec2 screenSize;
+} camera;
+
+layout (std140) uniform PointLights {
+    Light pointLights[MAX_POINT_LIGHTS];
+    int pointLightCount;
 };
 
-uniform Light light;
-uniform vec3 viewPos;
+// which of the point lights this pass draws
+uniform int lightIndex;
+// lights without a shadow cubemap skip the shadow lookup
+uniform bool castShadows;
+
+Light light;
+
+float ShadowCalculation(vec3 fragPos)
+{
+    // the cubemap is sampled with the direction from the light to the fragment
+    vec3 fragToLight = fragPos - light.Position;
+    // closest depth is stored as linear distance in [0,1], bring it back to world units
+    // the shadow pass uses the light radius as the far plane
+    float closestDepth = texture(shadowMap, fragToLight).r * light.Radius;
+    float currentDepth = length(fragToLight);
+    float shadow = currentDepth - light.ShadowBias > closestDepth ? 1.0 : 0.0;
+
+    return shadow;
+}
 
 void main()
 {             
+    light = pointLights[lightIndex];
+    // light volumes don't cover the screen, so the gbuffer is read at the pixel being shaded
+    vec2 TexCoords = gl_FragCoord.xy / camera.screenSize;
     // retrieve data from G-buffer
-    vec3 FragPos = texture(gPosition, TexCoords).rgb;
-    vec3 Normal = texture(gNormal, TexCoords).rgb;
-    vec3 Albedo = texture(gAlbedoSpec, TexCoords).rgb;
-    float Specular = texture(gAlbedoSpec, TexCoords).a;
+    Surface surface = ReadSurface(TexCoords);
+    vec3 FragPos = surface.Position;
     
-    // then calculate lighting as usual
-    vec3 viewDir = normalize(viewPos - FragPos);
-    // diffuse
+    vec3 viewDir = normalize(camera.viewPos - FragPos);
     vec3 lightDir = normalize(light.Position - FragPos);
-    vec3 diffuse = max(dot(Normal, lightDir), 0.0) * light.Color;
-    // specular
-    vec3 halfwayDir = normalize(lightDir + viewDir);  
-    float spec = pow(max(dot(Normal, halfwayDir), 0.0), 16.0);
-    vec3 specular = light.Color * spec * Specular;
     // attenuation
     float distance = length(light.Position - FragPos);
     float attenuation = clamp(1-distance*distance/(light.Radius*light.Radius), 0.0, 1.0);
     attenuation *= attenuation;
-    diffuse *= attenuation;
-    specular *= attenuation;
-    vec3 lighting = diffuse + specular;    
+    // calculate shadow
+    float shadow = castShadows ? ShadowCalculation(FragPos) : 0.0;
+    vec3 lighting = (1.0 - shadow) * Shade(surface, lightDir, viewDir, light.Color * attenuation);
     
     FragColor = vec4(lighting, 1.0);
-} 
\ No newline at end of file
+} 
@@ -6,22 +6,37 @@ in vec3 Normal;
 
 in vec3 FragPos;
 
-uniform vec3 viewPos;
-
-uniform float uWidth;
-uniform float uHeight;
-
-layout (location = 0) out vec3 gPosition;
-layout (location = 1) out vec3 gNormal;
+layout (std140) uniform Camera {
+    mat4 view;
+    mat4 projection;
+    mat4 inverseView;
+    vec3 viewPos;
+    float time;
+    vec2 screenSize;
+} camera;
+
+// the alphas hold the material: the model, roughness or shininess, metallic or specular
+layout (location = 0) out vec4 gPosition;
+layout (location = 1) out vec4 gNormal;
 layout (location = 2) out vec4 gAlbedoSpec;
+// emissive color and ambient occlusion
+layout (location = 3) out vec4 gEmissive;
 
 uniform sampler2D texture0;
 
 struct Material {
+    // 0 phong, 1 metallic roughness
+    int model;
     vec3 ambient;
     vec3 diffuse;
     float specular;
     float shininess;
+    float dissolve;
+    vec3 baseColor;
+    float metallic;
+    float roughness;
+    float ambientOcclusion;
+    vec3 emissive;
 }; 
 
 uniform Material material;
@@ -29,13 +44,15 @@ uniform Material material;
 void main()
 {
     vec3 normal = normalize(Normal);
-    float uvx = gl_FragCoord.x/uWidth;
-    float uvy = gl_FragCoord.y/uHeight;
+    float uvx = gl_FragCoord.x/camera.screenSize.x;
+    float uvy = gl_FragCoord.y/camera.screenSize.y;
     vec2 uv = vec2(uvx,uvy);
-    gPosition = FragPos;
-    gNormal = normal;
+    // lit like an unshiny phong surface
+    gPosition = vec4(FragPos, 0.0);
+    gNormal = vec4(normal, 16.0);
     gAlbedoSpec.rgb = texture(texture0, uv).rgb;
     gAlbedoSpec.a = 0;
+    gEmissive = vec4(0.0, 0.0, 0.0, 1.0);
         
     //float debug = (norm.x > 0.5) ? 1 : 0;
     //FragColor = vec4(debug,debug,debug,1.0);
@@ -5,8 +5,15 @@ layout (location = 2) in vec3 aNormal;
 layout (location = 3) in vec2 aTexCoord;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+
+layout (std140) uniform Camera {
+    mat4 view;
+    mat4 projection;
+    mat4 inverseView;
+    vec3 viewPos;
+    float time;
+    vec2 screenSize;
+} camera;
 
 uniform mat4 light_projection;
 uniform mat4 light_view;
@@ -21,7 +28,7 @@ out vec4 FragPosLightSpace;
 
 void main()
 {
-    gl_Position = projection * view * model * vec4(aPos, 1.0);
+    gl_Position = camera.projection * camera.view * model * vec4(aPos, 1.0);
     FragPos = vec3(model * vec4(aPos, 1.0));
     FragPosLightSpace = light_projection * light_view * vec4(FragPos,1.0);
 
@@ -1,54 +1,174 @@
 #version 330 core
+#include "../Common/Lighting.glsl"
 out vec4 FragColor;
 
   
-in vec2 TexCoords;
 
 
-uniform sampler2D gPosition;
-uniform sampler2D gNormal;
-uniform sampler2D gAlbedoSpec;
 uniform sampler2D gLighting;
-uniform sampler2D shadowMap;
+uniform sampler2DShadow shadowMap;
+uniform sampler2D shadowMapRaw;
+
+#define MAX_SPOT_LIGHTS 16
 
 struct Light {
+    mat4 LightSpaceMatrix;
     vec3 Position;
-    vec3 Color;
+    float Radius;
     vec3 Direction;
-
     float Fov;
-    float Radius;
+    vec3 Color;
+    float NearPlane;
+};
+
+// filterMode: 0 hard, 1 pcf, 2 poisson pcf, 3 pcss. filter is a reserved word in glsl
+struct ShadowSettings {
+    int filterMode;
+    int kernelSize;
+    float lightSize;
+    float constantBias;
+    float slopeBias;
+    float normalOffset;
 };
 
-uniform Light light;
-uniform vec3 viewPos;
-uniform mat4 lightSpaceMatrix;
+layout (std140) uniform Camera {
+    mat4 view;
+    mat4 projection;
+    mat4 inverseView;
+    vec3 viewPos;
+    float time;
+    vec2 screenSize;
+} camera;
 
+layout (std140) uniform SpotLights {
+    Light spotLights[MAX_SPOT_LIGHTS];
+    int spotLightCount;
+};
+
+uniform ShadowSettings shadowSettings;
+// which of the spot lights this pass draws
+uniform int lightIndex;
+
+Light light;
+
+const vec2 poissonDisk[16] = vec2[](
+    vec2(-0.94201624, -0.39906216),
+    vec2(0.94558609, -0.76890725),
+    vec2(-0.094184101, -0.92938870),
+    vec2(0.34495938, 0.29387760),
+    vec2(-0.91588581, 0.45771432),
+    vec2(-0.81544232, -0.87912464),
+    vec2(-0.38277543, 0.27676845),
+    vec2(0.97484398, 0.75648379),
+    vec2(0.44323325, -0.97511554),
+    vec2(0.53742981, -0.47373420),
+    vec2(-0.26496911, -0.41893023),
+    vec2(0.79197514, 0.19090188),
+    vec2(-0.24188840, 0.99706507),
+    vec2(-0.81409955, 0.91437590),
+    vec2(0.19984126, 0.78641367),
+    vec2(0.14383161, -0.14100790)
+);
+
+// turns a [0,1] depth from the perspective shadow map back into a distance from the light
+float LinearizeDepth(float depth)
+{
+    float z = depth * 2.0 - 1.0;
+    return (2.0 * light.NearPlane * light.Radius) / (light.Radius + light.NearPlane - z * (light.Radius - light.NearPlane));
+}
+
+float SampleHard(vec2 uv, float depth)
+{
+    return depth > texture(shadowMapRaw, uv).r ? 1.0 : 0.0;
+}
 
-float ShadowCalculation(vec4 fragPosLightSpace)
+float SamplePcf(vec2 uv, float depth)
 {
+    vec2 texelSize = 1.0 / vec2(textureSize(shadowMapRaw, 0));
+    float offset = float(shadowSettings.kernelSize - 1) / 2.0;
+    float lit = 0.0;
+    for (int x = 0; x < shadowSettings.kernelSize; ++x) {
+        for (int y = 0; y < shadowSettings.kernelSize; ++y) {
+            vec2 sampleUv = uv + (vec2(x, y) - offset) * texelSize;
+            lit += texture(shadowMap, vec3(sampleUv, depth));
+        }
+    }
+    return 1.0 - lit / float(shadowSettings.kernelSize * shadowSettings.kernelSize);
+}
+
+float SamplePoisson(vec2 uv, float depth, float radius)
+{
+    float lit = 0.0;
+    for (int i = 0; i < 16; ++i) {
+        lit += texture(shadowMap, vec3(uv + poissonDisk[i] * radius, depth));
+    }
+    return 1.0 - lit / 16.0;
+}
+
+// average distance of the occluders between the light and the fragment, -1 when nothing occludes it
+float FindBlockerDistance(vec2 uv, float depth, float searchRadius)
+{
+    float blockerSum = 0.0;
+    int blockerCount = 0;
+    for (int i = 0; i < 16; ++i) {
+        float sampleDepth = texture(shadowMapRaw, uv + poissonDisk[i] * searchRadius).r;
+        if (sampleDepth < depth) {
+            blockerSum += LinearizeDepth(sampleDepth);
+            blockerCount++;
+        }
+    }
+    if (blockerCount == 0) {
+        return -1.0;
+    }
+    return blockerSum / float(blockerCount);
+}
+
+float SamplePcss(vec2 uv, float depth)
+{
+    float receiverDistance = LinearizeDepth(depth);
+    float searchRadius = shadowSettings.lightSize * (receiverDistance - light.NearPlane) / receiverDistance;
+    float blockerDistance = FindBlockerDistance(uv, depth, searchRadius);
+    if (blockerDistance < 0.0) {
+        return 0.0;
+    }
+    float penumbra = (receiverDistance - blockerDistance) / blockerDistance * shadowSettings.lightSize;
+    return SamplePoisson(uv, depth, clamp(penumbra, 0.0, 0.05));
+}
+
+float ShadowCalculation(vec3 fragPos, vec3 normal, vec3 lightDir)
+{
+    // push the lookup position along the normal so surfaces don't shadow themselves
+    vec4 fragPosLightSpace = light.LightSpaceMatrix * vec4(fragPos + normal * shadowSettings.normalOffset, 1.0);
     // perform perspective divide
     vec3 projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
     // transform to [0,1] range
     projCoords = projCoords * 0.5 + 0.5;
-    // get closest depth value from light's perspective (using [0,1] range fragPosLight as coords)
-    float closestDepth = texture(shadowMap, projCoords.xy).r; 
-    // get depth of current fragment from light's perspective
-    float currentDepth = projCoords.z;
-    // check whether current frag pos is in shadow
-    float shadow = currentDepth > closestDepth  ? 1.0 : 0.0;
-
-    return shadow;
+    // bias grows with the slope of the surface relative to the light
+    float cosTheta = clamp(dot(normal, lightDir), 0.001, 1.0);
+    float tanTheta = sqrt(1.0 - cosTheta * cosTheta) / cosTheta;
+    float bias = shadowSettings.constantBias + shadowSettings.slopeBias * min(tanTheta, 10.0);
+    float currentDepth = projCoords.z - bias;
+
+    if (shadowSettings.filterMode == 1) {
+        return SamplePcf(projCoords.xy, currentDepth);
+    } else if (shadowSettings.filterMode == 2) {
+        vec2 texelSize = 1.0 / vec2(textureSize(shadowMapRaw, 0));
+        return SamplePoisson(projCoords.xy, currentDepth, texelSize.x * float(shadowSettings.kernelSize));
+    } else if (shadowSettings.filterMode == 3) {
+        return SamplePcss(projCoords.xy, currentDepth);
+    }
+    return SampleHard(projCoords.xy, currentDepth);
 }  
 
 void main()
 {             
+    light = spotLights[lightIndex];
+    // light volumes don't cover the screen, so the gbuffer is read at the pixel being shaded
+    vec2 TexCoords = gl_FragCoord.xy / camera.screenSize;
     // retrieve data from G-buffer
-    vec3 FragPos = texture(gPosition, TexCoords).rgb;
-    vec3 Normal = texture(gNormal, TexCoords).rgb;
-    vec3 Albedo = texture(gAlbedoSpec, TexCoords).rgb;
-    float Specular = texture(gAlbedoSpec, TexCoords).a;
-    vec4 FragPosLightSpace = lightSpaceMatrix * vec4(FragPos,1.0);
+    Surface surface = ReadSurface(TexCoords);
+    vec3 FragPos = surface.Position;
+    vec3 Normal = surface.Normal;
 
     vec3 lightDir = normalize(light.Position - FragPos);
     float theta = dot(lightDir, normalize(-light.Direction));
@@ -57,29 +177,19 @@ void main()
     float att = clamp(1-dst*dst/(1*1),0,1);
     att *= att;
     if (theta > cos(light.Fov / 2)) {
-        // then calculate lighting as usual
-        vec3 viewDir = normalize(viewPos - FragPos);
-        // diffuse
-        vec3 diffuse = max(dot(Normal, lightDir), 0.0) * light.Color;
-        // specular
-        vec3 halfwayDir = normalize(lightDir + viewDir);  
-        float spec = pow(max(dot(Normal, halfwayDir), 0.0), 16.0);
-        vec3 specular = light.Color * spec * Specular;
+        vec3 viewDir = normalize(camera.viewPos - FragPos);
         // attenuation
         float distance = length(light.Position - FragPos);
         float attenuation = clamp(1-distance*distance/(light.Radius*light.Radius), 0.0, 1.0);
         attenuation *= attenuation;
-        diffuse *= attenuation;
-        specular *= attenuation;
-        diffuse *= att;
-        specular *= att;
+        attenuation *= att;
         // calculate shadow
-        float shadow = ShadowCalculation(FragPosLightSpace);       
-        vec3 lighting = ((1.0 - shadow) * (diffuse + specular)); 
+        float shadow = ShadowCalculation(FragPos, Normal, lightDir);
+        vec3 lighting = (1.0 - shadow) * Shade(surface, lightDir, viewDir, light.Color * attenuation);
 
         FragColor = vec4(lighting.rgb, 1.0);
     } else {
         FragColor = vec4(vec3(0), 1.0);
     }
     
-} 
\ No newline at end of file
+} 
@@ -0,0 +1,64 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+// the scene from the final pass, in linear hdr
+uniform sampler2D hdrColor;
+
+// what the colors are multiplied by before they are mapped
+uniform float exposure;
+// raised to 1 / gamma after mapping
+uniform float gamma;
+// 0 none, 1 reinhard, 2 aces, 3 uncharted 2, has to match ToneMapper::shader_value
+uniform int toneMapper;
+
+vec3 Reinhard(vec3 color)
+{
+    return color / (1.0 + color);
+}
+
+// Krzysztof Narkowicz's fit of the aces filmic curve
+vec3 Aces(vec3 color)
+{
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+}
+
+vec3 Uncharted2Curve(vec3 x)
+{
+    const float A = 0.15;
+    const float B = 0.50;
+    const float C = 0.10;
+    const float D = 0.20;
+    const float E = 0.02;
+    const float F = 0.30;
+    return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+// John Hable's filmic curve, scaled so the white point maps to 1
+vec3 Uncharted2(vec3 color)
+{
+    const float exposureBias = 2.0;
+    const vec3 whitePoint = vec3(11.2);
+    return Uncharted2Curve(color * exposureBias) / Uncharted2Curve(whitePoint);
+}
+
+void main()
+{
+    vec3 color = texture(hdrColor, TexCoords).rgb * exposure;
+    if (toneMapper == 1) {
+        color = Reinhard(color);
+    } else if (toneMapper == 2) {
+        color = Aces(color);
+    } else if (toneMapper == 3) {
+        color = Uncharted2(color);
+    }
+    color = pow(clamp(color, 0.0, 1.0), vec3(1.0 / gamma));
+
+    FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,26 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+uniform vec2 screenSize;
+
+// how much the corners fade to the color
+uniform float intensity;
+// the distance from the center the fade starts at, 1 is the middle of an edge
+uniform float radius;
+// how far the fade goes on past the radius
+uniform float softness;
+uniform vec3 color;
+
+void main()
+{
+    vec3 sceneColor = texture(image, TexCoords).rgb;
+    // round on screens that aren't square
+    vec2 position = (TexCoords - 0.5) * 2.0 * vec2(screenSize.x / screenSize.y, 1.0);
+    float distance = length(position) / max(screenSize.x / screenSize.y, 1.0);
+    float fade = smoothstep(radius, radius + softness, distance) * intensity;
+
+    FragColor = vec4(mix(sceneColor, color, fade), 1.0);
+}
@@ -0,0 +1,311 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::{Hash, Hasher},
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+use egui_sdl2_gl::egui::{self, Widget};
+
+use crate::{
+    buffers::Texture,
+    errors::EngineError,
+    models::{Mesh, Model},
+    shaders::{create_program, Program},
+};
+
+/// the same file reached through ./ or not gets the same key
+fn asset_key(path: &Path) -> String {
+    let path: PathBuf = path
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect();
+    return path.to_string_lossy().into_owned();
+}
+
+struct CachedTexture {
+    texture: Rc<Texture>,
+    /// with the mip levels
+    bytes: usize,
+}
+
+impl CachedTexture {
+    fn new(texture: Texture) -> Self {
+        let (width, height) = texture.size();
+        // rgba8, the mip chain adds about a third
+        let bytes = width as usize * height as usize * 4 * 4 / 3;
+        Self {
+            texture: Rc::new(texture),
+            bytes,
+        }
+    }
+}
+
+/// one row of the asset statistics
+pub struct AssetStats {
+    pub kind: &'static str,
+    pub key: String,
+    /// gpu memory, 0 for programs since gl doesn't say
+    pub bytes: usize,
+    /// handles held outside of the cache
+    pub users: usize,
+}
+
+/// loads every mesh, texture and program once and hands out shared handles to them,
+/// so models loaded from the same files share their gpu objects
+pub struct AssetCache {
+    /// the models each model file loaded into, later loads of the file copy these
+    loaded_files: BTreeMap<String, Vec<Model>>,
+    meshes: BTreeMap<String, Rc<Mesh>>,
+    textures: BTreeMap<String, CachedTexture>,
+    programs: BTreeMap<String, Program>,
+}
+
+impl AssetCache {
+    pub fn new() -> Self {
+        Self {
+            loaded_files: BTreeMap::new(),
+            meshes: BTreeMap::new(),
+            textures: BTreeMap::new(),
+            programs: BTreeMap::new(),
+        }
+    }
+
+    /// copies of the models a file was loaded into before, if it was
+    pub fn loaded_file(&self, path: &Path) -> Option<Vec<Model>> {
+        self.loaded_files.get(&asset_key(path)).cloned()
+    }
+
+    pub fn add_loaded_file(&mut self, path: &Path, models: &[Model]) {
+        self.loaded_files.insert(asset_key(path), models.to_vec());
+    }
+
+    /// key is the file the mesh came from, with #n after it when a file has several,
+    /// or a name for meshes made in code
+    pub fn add_mesh(&mut self, key: String, mesh: Mesh) -> Rc<Mesh> {
+        let mesh = Rc::new(mesh);
+        self.meshes.insert(key, mesh.clone());
+        return mesh;
+    }
+
+    pub fn program(
+        &mut self,
+        vert_shader_path: &str,
+        frag_shader_path: &str,
+    ) -> Result<Program, EngineError> {
+        let key = format!(
+            "{} + {}",
+            asset_key(Path::new(vert_shader_path)),
+            asset_key(Path::new(frag_shader_path))
+        );
+        if let Some(program) = self.programs.get(&key) {
+            return Ok(program.clone());
+        }
+        let program = create_program(vert_shader_path, frag_shader_path)?;
+        self.programs.insert(key, program.clone());
+        return Ok(program);
+    }
+
+    /// a texture loaded from an image file, a file that fails isn't cached so it's tried again next time
+    pub fn texture(&mut self, path: &Path) -> Result<Rc<Texture>, EngineError> {
+        let key = asset_key(path);
+        if let Some(cached) = self.textures.get(&key) {
+            return Ok(cached.texture.clone());
+        }
+        let texture = Texture::new();
+        texture.load(path)?;
+        return Ok(self.insert_texture(key, texture));
+    }
+
+    /// an image inside of another file, like the ones in .glb files, told apart by a hash of its bytes
+    pub fn embedded_texture(
+        &mut self,
+        file: &Path,
+        bytes: &[u8],
+    ) -> Result<Rc<Texture>, EngineError> {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        let key = format!("{}#{:016x}", asset_key(file), hasher.finish());
+        if let Some(cached) = self.textures.get(&key) {
+            return Ok(cached.texture.clone());
+        }
+        let texture = Texture::new();
+        texture
+            .load_from_memory(bytes)
+            .map_err(|source| EngineError::Image {
+                path: file.to_path_buf(),
+                source,
+            })?;
+        return Ok(self.insert_texture(key, texture));
+    }
+
+    /// a 1x1 texture, for materials that only have a color
+    pub fn solid_color_texture(&mut self, color: [u8; 4]) -> Rc<Texture> {
+        let key = format!(
+            "solid color #{:02x}{:02x}{:02x}{:02x}",
+            color[0], color[1], color[2], color[3]
+        );
+        if let Some(cached) = self.textures.get(&key) {
+            return cached.texture.clone();
+        }
+        let texture = Texture::new();
+        texture.make_solid_color(color).unwrap();
+        return self.insert_texture(key, texture);
+    }
+
+    /// what textures that fail to load are replaced with
+    pub fn checkerboard_texture(&mut self) -> Rc<Texture> {
+        let key = "checkerboard".to_owned();
+        if let Some(cached) = self.textures.get(&key) {
+            return cached.texture.clone();
+        }
+        let texture = Texture::new();
+        texture.make_checkerboard().unwrap();
+        return self.insert_texture(key, texture);
+    }
+
+    fn insert_texture(&mut self, key: String, texture: Texture) -> Rc<Texture> {
+        let cached = CachedTexture::new(texture);
+        let texture = cached.texture.clone();
+        self.textures.insert(key, cached);
+        return texture;
+    }
+
+    /// how many handles to each asset the cache holds itself, in its map and its loaded files
+    fn own_handles(&self) -> (Vec<usize>, Vec<usize>, Vec<usize>) {
+        let models: Vec<&Model> = self.loaded_files.values().flatten().collect();
+        let meshes = self
+            .meshes
+            .values()
+            .map(|mesh| 1 + models.iter().filter(|m| Rc::ptr_eq(&m.mesh, mesh)).count())
+            .collect();
+        let textures = self
+            .textures
+            .values()
+            .map(|cached| {
+                1 + models
+                    .iter()
+                    .map(|model| {
+                        model
+                            .textures()
+                            .into_iter()
+                            .filter(|texture| Rc::ptr_eq(texture, &cached.texture))
+                            .count()
+                    })
+                    .sum::<usize>()
+            })
+            .collect();
+        let programs = self
+            .programs
+            .values()
+            .map(|program| {
+                1 + models
+                    .iter()
+                    .filter(|model| model.shader_program.is_same_program(program))
+                    .count()
+            })
+            .collect();
+        return (meshes, textures, programs);
+    }
+
+    pub fn stats(&self) -> Vec<AssetStats> {
+        let (mesh_handles, texture_handles, program_handles) = self.own_handles();
+        let mut stats: Vec<AssetStats> = Vec::new();
+        for ((key, mesh), own) in self.meshes.iter().zip(mesh_handles) {
+            stats.push(AssetStats {
+                kind: "Mesh",
+                key: key.clone(),
+                bytes: mesh.memory_size(),
+                users: Rc::strong_count(mesh) - own,
+            });
+        }
+        for ((key, cached), own) in self.textures.iter().zip(texture_handles) {
+            stats.push(AssetStats {
+                kind: "Texture",
+                key: key.clone(),
+                bytes: cached.bytes,
+                users: Rc::strong_count(&cached.texture) - own,
+            });
+        }
+        for ((key, program), own) in self.programs.iter().zip(program_handles) {
+            stats.push(AssetStats {
+                kind: "Program",
+                key: key.clone(),
+                bytes: 0,
+                users: program.ref_count() - own,
+            });
+        }
+        return stats;
+    }
+
+    /// drops the loaded files nothing uses anymore, then every asset only the cache holds
+    pub fn unload_unused(&mut self) {
+        let unused_meshes: Vec<Rc<Mesh>> = self
+            .meshes
+            .values()
+            .zip(self.own_handles().0)
+            .filter(|(mesh, own)| Rc::strong_count(mesh) == *own)
+            .map(|(mesh, _)| mesh.clone())
+            .collect();
+        self.loaded_files.retain(|_, models| {
+            !models.iter().all(|model| {
+                unused_meshes
+                    .iter()
+                    .any(|mesh| Rc::ptr_eq(mesh, &model.mesh))
+            })
+        });
+        drop(unused_meshes);
+        self.meshes.retain(|_, mesh| Rc::strong_count(mesh) > 1);
+        self.textures
+            .retain(|_, cached| Rc::strong_count(&cached.texture) > 1);
+        self.programs.retain(|_, program| program.ref_count() > 1);
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        return format!("{:.1} MiB", bytes as f32 / (1024.0 * 1024.0));
+    }
+    return format!("{:.1} KiB", bytes as f32 / 1024.0);
+}
+
+impl Widget for &mut AssetCache {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let stats = self.stats();
+        let count = |kind: &str| stats.iter().filter(|stat| stat.kind == kind).count();
+        let total_bytes: usize = stats.iter().map(|stat| stat.bytes).sum();
+        ui.vertical(|ui| {
+            ui.label(format!(
+                "{} meshes, {} textures, {} programs, {}",
+                count("Mesh"),
+                count("Texture"),
+                count("Program"),
+                format_bytes(total_bytes)
+            ));
+            if ui.button("Unload Unused").clicked() {
+                self.unload_unused();
+            }
+            egui::CollapsingHeader::new("Loaded Assets")
+                .id_source("loaded_assets")
+                .show(ui, |ui| {
+                    egui::Grid::new("asset_stats_grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label("Kind");
+                            ui.label("Asset");
+                            ui.label("Memory");
+                            ui.label("Users");
+                            ui.end_row();
+                            for stat in &stats {
+                                ui.label(stat.kind);
+                                ui.label(&stat.key);
+                                ui.label(format_bytes(stat.bytes));
+                                ui.label(stat.users.to_string());
+                                ui.end_row();
+                            }
+                        });
+                });
+        })
+        .response
+    }
+}
@@ -1,17 +1,23 @@
-use std::{os::raw::c_void, path::Path, ptr::null};
+use std::{marker::PhantomData, os::raw::c_void, path::Path, ptr::null};
 
-use cgmath::{Vector2, Vector3};
-use image::{EncodableLayout, ImageError};
+use crate::{
+    errors::EngineError,
+    vertex_layout::{Vertex, VertexFormat, VertexLayout},
+};
 
-pub struct VertexBuffer {
+use image::{imageops, EncodableLayout, ImageError, Rgba32FImage, RgbaImage};
+
+/// holds vertices of any VertexFormat, the vertex array gets told how to read them with V::layout()
+pub struct VertexBuffer<V: VertexFormat = Vertex> {
     id: u32,
+    vertex: PhantomData<V>,
 }
-impl VertexBuffer {
-    pub fn data(&self, data: &Vec<(Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector2<f32>)>) {
+impl<V: VertexFormat> VertexBuffer<V> {
+    pub fn data(&self, data: &[V]) {
         unsafe {
             gl::BufferData(
                 gl::ARRAY_BUFFER,
-                (data.len() * std::mem::size_of::<f32>() * 11) as isize,
+                std::mem::size_of_val(data) as isize,
                 data.as_ptr() as *const gl::types::GLvoid,
                 gl::DYNAMIC_DRAW,
             );
@@ -30,22 +36,25 @@ impl VertexBuffer {
         }
     }
 }
-impl Drop for VertexBuffer {
+impl<V: VertexFormat> Drop for VertexBuffer<V> {
     fn drop(&mut self) {
         self.unbind();
         self.delete();
     }
 }
-impl VertexBuffer {
+impl<V: VertexFormat> VertexBuffer<V> {
     pub fn new() -> Self {
         let mut id = 0;
         unsafe {
             gl::GenBuffers(1, &mut id);
         };
-        VertexBuffer { id }
+        VertexBuffer {
+            id,
+            vertex: PhantomData,
+        }
     }
 
-    pub fn set(&self, data: &Vec<(Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector2<f32>)>) {
+    pub fn set(&self, data: &[V]) {
         self.bind();
         self.data(data);
     }
@@ -132,48 +141,6 @@ impl Drop for VertexArrayBuffer {
         self.delete();
     }
 }
-impl VertexArrayBuffer {
-    fn setup(&self) {
-        unsafe {
-            gl::VertexAttribPointer(
-                0,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                (11 * std::mem::size_of::<f32>()) as i32,
-                0 as *const c_void,
-            );
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(
-                1,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                (11 * std::mem::size_of::<f32>()) as i32,
-                ((3 * std::mem::size_of::<f32>()) as i32) as *const c_void,
-            );
-            gl::EnableVertexAttribArray(1);
-            gl::VertexAttribPointer(
-                2,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                (11 * std::mem::size_of::<f32>()) as i32,
-                ((6 * std::mem::size_of::<f32>()) as i32) as *const c_void,
-            );
-            gl::EnableVertexAttribArray(2);
-            gl::VertexAttribPointer(
-                3,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                (11 * std::mem::size_of::<f32>()) as i32,
-                ((9 * std::mem::size_of::<f32>()) as i32) as *const c_void,
-            );
-            gl::EnableVertexAttribArray(3);
-        }
-    }
-}
 impl VertexArrayBuffer {
     pub fn new() -> Self {
         let mut id = 0;
@@ -183,9 +150,10 @@ impl VertexArrayBuffer {
         return VertexArrayBuffer { id };
     }
 
-    pub fn set(&self) {
+    /// the vertex buffer has to be bound, the layout is read from whichever one is
+    pub fn set(&self, layout: &VertexLayout) {
         self.bind();
-        self.setup();
+        layout.apply();
     }
 
     pub fn bind(&self) {
@@ -242,7 +210,45 @@ impl Texture {
             gl::DeleteTextures(1, [self.id].as_ptr());
         }
     }
-    pub fn load(&self, path: &Path) -> Result<(), ImageError> {
+    pub fn load(&self, path: &Path) -> Result<(), EngineError> {
+        let image = image::open(path).map_err(|source| EngineError::Image {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        self.load_image(image).map_err(|source| EngineError::Image {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+    /// loads an encoded image (png, jpeg...) that is already in memory, like the ones embedded in .glb files
+    pub fn load_from_memory(&self, bytes: &[u8]) -> Result<(), ImageError> {
+        self.load_image(image::load_from_memory(bytes)?)
+    }
+    /// makes a 1x1 texture of a single color, for materials that only have a color factor
+    pub fn make_solid_color(&self, color: [u8; 4]) -> Result<(), ImageError> {
+        self.load_image(image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba(color),
+        )))
+    }
+    /// magenta and black checks, used in place of textures that fail to load so they stand out
+    pub fn make_checkerboard(&self) -> Result<(), ImageError> {
+        const SIZE: u32 = 64;
+        const CHECK_SIZE: u32 = 8;
+        self.load_image(image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(
+            SIZE,
+            SIZE,
+            |x, y| {
+                if (x / CHECK_SIZE + y / CHECK_SIZE).is_multiple_of(2) {
+                    image::Rgba([255, 0, 255, 255])
+                } else {
+                    image::Rgba([0, 0, 0, 255])
+                }
+            },
+        )))
+    }
+    pub fn load_image(&self, image: image::DynamicImage) -> Result<(), ImageError> {
         self.bind();
         unsafe {
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
@@ -250,8 +256,7 @@ impl Texture {
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
         }
-        let img: image::ImageBuffer<image::Rgba<u8>, Vec<u8>> =
-            image::open(path)?.flipv().into_rgba8();
+        let img: image::ImageBuffer<image::Rgba<u8>, Vec<u8>> = image.flipv().into_rgba8();
         unsafe {
             gl::TexImage2D(
                 gl::TEXTURE_2D,
@@ -292,6 +297,55 @@ impl Texture {
         }
         Ok(())
     }
+    /// like make_empty but clamped to the edge, so passes that sample past the screen don't
+    /// wrap around to the other side
+    pub fn make_empty_clamped(&self, size: (u32, u32)) {
+        self.bind();
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        }
+        unsafe {
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as i32,
+                size.0 as i32,
+                size.1 as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                null(),
+            );
+        }
+    }
+    /// like make_empty but with half floats, so colors added up past 1.0 aren't clipped
+    pub fn make_empty_hdr(&self, size: (u32, u32)) {
+        self.bind();
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        }
+        unsafe {
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA16F as i32,
+                size.0 as i32,
+                size.1 as i32,
+                0,
+                gl::RGBA,
+                gl::FLOAT,
+                null(),
+            );
+        }
+    }
+    /// makes a depth texture that is sampled with hardware depth comparison (sampler2DShadow),
+    /// bind a [`Sampler`] made with [`Sampler::make_raw_depth`] to read the depth values themselves
     pub fn make_empty_depth_buffer(&self, size: (u32, u32)) -> Result<(), ImageError> {
         self.bind();
         unsafe {
@@ -299,6 +353,12 @@ impl Texture {
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_COMPARE_MODE,
+                gl::COMPARE_REF_TO_TEXTURE as i32,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as i32);
         }
         unsafe {
             gl::TexImage2D(
@@ -315,6 +375,60 @@ impl Texture {
         }
         Ok(())
     }
+    /// the width and height of the full size mip level, asked from gl
+    pub fn size(&self) -> (u32, u32) {
+        let (mut width, mut height) = (0, 0);
+        self.bind();
+        unsafe {
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_WIDTH, &mut width);
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_HEIGHT, &mut height);
+        }
+        return (width as u32, height as u32);
+    }
+    /// copies the first mip level back from the gpu, flipped so the first row is the top of the image
+    pub fn read_pixels(&self) -> RgbaImage {
+        let (width, height) = self.size();
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::GetTexImage(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut c_void,
+            );
+        }
+        let image = RgbaImage::from_raw(width, height, pixels).unwrap();
+        return imageops::flip_vertical(&image);
+    }
+    /// like read_pixels but as floats, so float textures keep values outside of 0-1
+    pub fn read_pixels_f32(&self) -> Rgba32FImage {
+        return self.read_mip_level_f32(0);
+    }
+    /// reads one mip level as floats, flipped like read_pixels
+    pub fn read_mip_level_f32(&self, level: i32) -> Rgba32FImage {
+        let (mut width, mut height) = (0, 0);
+        self.bind();
+        unsafe {
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, level, gl::TEXTURE_WIDTH, &mut width);
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, level, gl::TEXTURE_HEIGHT, &mut height);
+        }
+        let (width, height) = (width as u32, height as u32);
+        let mut pixels = vec![0.0f32; width as usize * height as usize * 4];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::GetTexImage(
+                gl::TEXTURE_2D,
+                level,
+                gl::RGBA,
+                gl::FLOAT,
+                pixels.as_mut_ptr() as *mut c_void,
+            );
+        }
+        let image = Rgba32FImage::from_raw(width, height, pixels).unwrap();
+        return imageops::flip_vertical(&image);
+    }
 }
 impl ModelTexture for Texture {
     fn bind_texture(&self) {
@@ -322,8 +436,135 @@ impl ModelTexture for Texture {
     }
 }
 
+/// a 3d texture, used for the lookup tables of color grading
+pub struct Texture3D {
+    pub id: u32,
+}
+impl Texture3D {
+    pub fn new() -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+        }
+        Self { id }
+    }
+    pub fn bind(&self) {
+        unsafe { gl::BindTexture(gl::TEXTURE_3D, self.id) }
+    }
+
+    pub fn unbind(&self) {
+        unsafe { gl::BindTexture(gl::TEXTURE_3D, 0) }
+    }
+    pub fn delete(&self) {
+        unsafe {
+            gl::DeleteTextures(1, [self.id].as_ptr());
+        }
+    }
+    /// uploads size * size * size rgba texels, red changes fastest and blue slowest
+    pub fn load_rgba(&self, size: u32, texels: &[u8]) {
+        self.bind();
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage3D(
+                gl::TEXTURE_3D,
+                0,
+                gl::RGBA8 as i32,
+                size as i32,
+                size as i32,
+                size as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                texels.as_ptr() as *const c_void,
+            );
+        }
+    }
+}
+impl Drop for Texture3D {
+    fn drop(&mut self) {
+        self.unbind();
+        self.delete();
+    }
+}
+
+#[derive(Debug, Clone)]
+/// a 2d texture with several layers, used for the cascades of directional light shadows
+pub struct TextureArray {
+    pub id: u32,
+}
+impl TextureArray {
+    pub fn new() -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+        }
+        Self { id }
+    }
+}
+impl Drop for TextureArray {
+    fn drop(&mut self) {
+        self.unbind();
+        self.delete();
+    }
+}
+impl TextureArray {
+    pub fn bind(&self) {
+        unsafe { gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id) }
+    }
+
+    pub fn unbind(&self) {
+        unsafe { gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0) }
+    }
+    pub fn delete(&self) {
+        unsafe {
+            gl::DeleteTextures(1, [self.id].as_ptr());
+        }
+    }
+    /// same as [`Texture::make_empty_depth_buffer`] but with a layer per cascade
+    pub fn make_empty_depth_buffer(&self, size: (u32, u32), layers: u32) -> Result<(), ImageError> {
+        self.bind();
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_COMPARE_MODE,
+                gl::COMPARE_REF_TO_TEXTURE as i32,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as i32);
+        }
+        unsafe {
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                gl::DEPTH_COMPONENT32F as i32,
+                size.0 as i32,
+                size.1 as i32,
+                layers as i32,
+                0,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+                null(),
+            );
+        }
+        Ok(())
+    }
+}
+impl ModelTexture for TextureArray {
+    fn bind_texture(&self) {
+        self.bind();
+    }
+}
+
 #[derive(Debug, Clone)]
-///this is not used, and not very tested, sorry
+/// a cube texture, right now only used as the depth target of point light shadows
 pub struct Cubemap {
     pub id: u32,
 }
@@ -426,11 +667,11 @@ impl Cubemap {
             }
         }
         unsafe {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
         }
 
         Ok(())
@@ -442,6 +683,50 @@ impl ModelTexture for Cubemap {
     }
 }
 
+/// sampler objects override the sampling parameters of whatever texture is bound to the same unit
+pub struct Sampler {
+    pub id: u32,
+}
+impl Sampler {
+    pub fn new() -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenSamplers(1, &mut id);
+        }
+        Self { id }
+    }
+}
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.delete();
+    }
+}
+impl Sampler {
+    pub fn bind(&self, unit: u32) {
+        unsafe { gl::BindSampler(unit, self.id) };
+    }
+
+    pub fn unbind(&self, unit: u32) {
+        unsafe { gl::BindSampler(unit, 0) };
+    }
+    pub fn delete(&self) {
+        unsafe {
+            gl::DeleteSamplers(1, [self.id].as_ptr());
+        }
+    }
+    /// reads the stored depth of a depth texture instead of comparing against it,
+    /// pcss needs this for its blocker search
+    pub fn make_raw_depth(&self) {
+        unsafe {
+            gl::SamplerParameteri(self.id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::SamplerParameteri(self.id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::SamplerParameteri(self.id, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::SamplerParameteri(self.id, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::SamplerParameteri(self.id, gl::TEXTURE_COMPARE_MODE, gl::NONE as i32);
+        }
+    }
+}
+
 pub struct FrameBuffer {
     pub id: u32,
 }
@@ -469,6 +754,26 @@ impl FrameBuffer {
     pub fn unbind(&self) {
         unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0) };
     }
+    /// reads the back buffer of the window, flipped so the first row is the top of the image
+    pub fn read_window_pixels(size: (u32, u32)) -> Rgba32FImage {
+        let mut pixels = vec![0.0f32; size.0 as usize * size.1 as usize * 4];
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::ReadBuffer(gl::BACK);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                size.0 as i32,
+                size.1 as i32,
+                gl::RGBA,
+                gl::FLOAT,
+                pixels.as_mut_ptr() as *mut c_void,
+            );
+        }
+        let image = Rgba32FImage::from_raw(size.0, size.1, pixels).unwrap();
+        return imageops::flip_vertical(&image);
+    }
     pub fn delete(&self) {
         unsafe {
             gl::DeleteFramebuffers(1, [self.id].as_ptr());
@@ -510,6 +815,28 @@ impl FrameBuffer {
 
         Ok(())
     }
+    /// attaches a single layer of a texture array as the depth target, call this before rendering into each layer
+    pub fn load_depth_texture_array_layer(
+        &self,
+        texture_array: &TextureArray,
+        layer: u32,
+    ) -> Result<(), EngineError> {
+        self.bind();
+        unsafe {
+            gl::FramebufferTextureLayer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                texture_array.id,
+                0,
+                layer as i32,
+            );
+        };
+        unsafe {
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+        }
+        check_framebuffer_status()
+    }
     pub fn load_depth_cubemap(&self, cubemap: &Cubemap) -> Result<(), String> {
         cubemap.bind();
         self.bind();
@@ -562,7 +889,7 @@ impl RenderBuffer {
             gl::DeleteRenderbuffers(1, [self.id].as_ptr());
         }
     }
-    pub fn load(&self, framebuffer: &FrameBuffer, size: (u32, u32)) -> Result<(), String> {
+    pub fn load(&self, framebuffer: &FrameBuffer, size: (u32, u32)) -> Result<(), EngineError> {
         framebuffer.bind();
         self.bind();
         unsafe {
@@ -573,6 +900,12 @@ impl RenderBuffer {
                 size.1 as i32,
             );
         }
+        self.attach(framebuffer)
+    }
+    /// attaches the same depth and stencil storage to another framebuffer, so passes drawing
+    /// into it can test against the depth of the scene
+    pub fn attach(&self, framebuffer: &FrameBuffer) -> Result<(), EngineError> {
+        framebuffer.bind();
         unsafe {
             gl::FramebufferRenderbuffer(
                 gl::FRAMEBUFFER,
@@ -581,14 +914,214 @@ impl RenderBuffer {
                 self.id,
             );
         }
-        if unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) } != gl::FRAMEBUFFER_COMPLETE {
-            return Err("ERROR::FRAMEBUFFER:: Framebuffer is not complete!".to_owned());
-        }
-        Ok(())
+        check_framebuffer_status()
+    }
+}
+/// checks the currently bound framebuffer
+pub fn check_framebuffer_status() -> Result<(), EngineError> {
+    let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+    if status != gl::FRAMEBUFFER_COMPLETE {
+        return Err(EngineError::FramebufferIncomplete { status });
     }
+    Ok(())
 }
 impl ModelTexture for RenderBuffer {
     fn bind_texture(&self) {
         self.bind();
     }
 }
+/// backs a std140 uniform block that is shared between programs through a binding point
+pub struct UniformBuffer {
+    pub id: u32,
+}
+impl UniformBuffer {
+    pub fn new() -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+        }
+        Self { id }
+    }
+}
+impl Drop for UniformBuffer {
+    fn drop(&mut self) {
+        self.unbind();
+        self.delete();
+    }
+}
+impl UniformBuffer {
+    pub fn bind(&self) {
+        unsafe { gl::BindBuffer(gl::UNIFORM_BUFFER, self.id) };
+    }
+
+    pub fn unbind(&self) {
+        unsafe { gl::BindBuffer(gl::UNIFORM_BUFFER, 0) };
+    }
+    pub fn delete(&self) {
+        unsafe {
+            gl::DeleteBuffers(1, [self.id].as_ptr());
+        }
+    }
+    /// makes room for size bytes, the contents are undefined until set is called
+    pub fn allocate(&self, size: usize) {
+        self.bind();
+        unsafe {
+            gl::BufferData(gl::UNIFORM_BUFFER, size as isize, null(), gl::DYNAMIC_DRAW);
+        }
+        self.unbind();
+    }
+    /// attaches the whole buffer to a binding point, programs read it through the block bound to the same point
+    pub fn bind_base(&self, binding: u32) {
+        unsafe {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, self.id);
+        }
+    }
+    /// uploads a #[repr(C)] struct laid out to match the block, it has to fit in the allocated size
+    pub fn set<T: Copy>(&self, data: &T) {
+        self.bind();
+        unsafe {
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                0,
+                std::mem::size_of::<T>() as isize,
+                data as *const T as *const c_void,
+            );
+        }
+        self.unbind();
+    }
+}
+/// a shader storage buffer, for data the shaders index freely or write to, like the light clusters
+pub struct ShaderStorageBuffer {
+    pub id: u32,
+}
+impl ShaderStorageBuffer {
+    pub fn new() -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+        }
+        Self { id }
+    }
+}
+impl Drop for ShaderStorageBuffer {
+    fn drop(&mut self) {
+        self.unbind();
+        self.delete();
+    }
+}
+impl ShaderStorageBuffer {
+    pub fn bind(&self) {
+        unsafe { gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id) };
+    }
+
+    pub fn unbind(&self) {
+        unsafe { gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0) };
+    }
+    pub fn delete(&self) {
+        unsafe {
+            gl::DeleteBuffers(1, [self.id].as_ptr());
+        }
+    }
+    /// makes room for size bytes, for buffers only the shaders write to
+    pub fn allocate(&self, size: usize) {
+        self.bind();
+        unsafe {
+            gl::BufferData(
+                gl::SHADER_STORAGE_BUFFER,
+                size as isize,
+                null(),
+                gl::DYNAMIC_COPY,
+            );
+        }
+        self.unbind();
+    }
+    /// replaces the contents with a slice of #[repr(C)] structs laid out for std430
+    pub fn set_slice<T: Copy>(&self, data: &[T]) {
+        self.bind();
+        // an empty buffer can't be bound, so always keep at least one element
+        let size = std::mem::size_of::<T>() * data.len().max(1);
+        unsafe {
+            gl::BufferData(
+                gl::SHADER_STORAGE_BUFFER,
+                size as isize,
+                null(),
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                0,
+                std::mem::size_of_val(data) as isize,
+                data.as_ptr() as *const c_void,
+            );
+        }
+        self.unbind();
+    }
+    pub fn bind_base(&self, binding: u32) {
+        unsafe {
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, self.id);
+        }
+    }
+}
+/// measures how long the gpu takes between begin and end, the result is only read once it's
+/// ready so it never stalls the frame, which makes it a frame or two behind
+pub struct TimerQuery {
+    pub id: u32,
+    waiting: bool,
+    /// the last result in milliseconds
+    pub elapsed_ms: f32,
+}
+impl TimerQuery {
+    pub fn new() -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenQueries(1, &mut id);
+        }
+        Self {
+            id,
+            waiting: false,
+            elapsed_ms: 0.0,
+        }
+    }
+}
+impl Drop for TimerQuery {
+    fn drop(&mut self) {
+        self.delete();
+    }
+}
+impl TimerQuery {
+    pub fn delete(&self) {
+        unsafe {
+            gl::DeleteQueries(1, [self.id].as_ptr());
+        }
+    }
+    pub fn begin(&mut self) {
+        if self.waiting {
+            let mut available: i32 = 0;
+            unsafe {
+                gl::GetQueryObjectiv(self.id, gl::QUERY_RESULT_AVAILABLE, &mut available);
+            }
+            if available == 0 {
+                return;
+            }
+            let mut elapsed_ns: u64 = 0;
+            unsafe {
+                gl::GetQueryObjectui64v(self.id, gl::QUERY_RESULT, &mut elapsed_ns);
+            }
+            self.elapsed_ms = elapsed_ns as f32 / 1_000_000.0;
+            self.waiting = false;
+        }
+        unsafe {
+            gl::BeginQuery(gl::TIME_ELAPSED, self.id);
+        }
+    }
+    /// does nothing if begin skipped this frame because the last result wasn't ready
+    pub fn end(&mut self) {
+        if self.waiting {
+            return;
+        }
+        unsafe {
+            gl::EndQuery(gl::TIME_ELAPSED);
+        }
+        self.waiting = true;
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use egui_sdl2_gl::egui::{self, Widget};
+use image::{DynamicImage, Rgba32FImage};
+
+use crate::errors::EngineError;
+
+/// what a screenshot or recording reads back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureSource {
+    /// the window after the final pass, without the debug ui
+    Screen,
+    Position,
+    Normal,
+    AlbedoSpec,
+    Emissive,
+    /// the light buffer the lighting passes add into
+    Light,
+    /// the scene before it is tone mapped
+    Hdr,
+}
+
+impl CaptureSource {
+    pub const ALL: [Self; 7] = [
+        Self::Screen,
+        Self::Position,
+        Self::Normal,
+        Self::AlbedoSpec,
+        Self::Emissive,
+        Self::Light,
+        Self::Hdr,
+    ];
+
+    /// used in the ui and at the start of file names
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Screen => "screen",
+            Self::Position => "g_position",
+            Self::Normal => "g_normal",
+            Self::AlbedoSpec => "g_albedo_spec",
+            Self::Emissive => "g_emissive",
+            Self::Light => "light_texture",
+            Self::Hdr => "hdr_texture",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// 8 bits a channel, clamped to 0-1 and opaque
+    Png,
+    /// 32 bit floats, every channel as it is on the gpu
+    Exr,
+}
+
+impl CaptureFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Exr => "exr",
+        }
+    }
+}
+
+/// a recording in progress
+struct Recording {
+    folder: PathBuf,
+    /// the number of the next frame
+    frame: u32,
+    /// the time of the first frame, taken when it is rendered
+    start_time: Option<f32>,
+}
+
+/// screenshot and recording settings, and the recording in progress
+pub struct Capture {
+    pub source: CaptureSource,
+    pub format: CaptureFormat,
+    /// screenshots and recordings go in here
+    pub folder: String,
+    /// every recorded frame moves time forward by exactly 1 / recording_fps, however long it took
+    pub recording_fps: u32,
+    /// set by the key binding or the button, taken after the next frame is rendered
+    pub screenshot_requested: bool,
+    recording: Option<Recording>,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Self {
+            source: CaptureSource::Screen,
+            format: CaptureFormat::Png,
+            folder: "./captures".to_owned(),
+            recording_fps: 60,
+            screenshot_requested: false,
+            recording: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// frames are written to a new numbered folder in the capture folder
+    pub fn start_recording(&mut self) -> Result<(), EngineError> {
+        let folder = first_free_path(Path::new(&self.folder), "recording", None);
+        fs::create_dir_all(&folder).map_err(|source| EngineError::Write {
+            path: folder.clone(),
+            source,
+        })?;
+        println!("recording to {}", folder.display());
+        self.recording = Some(Recording {
+            folder,
+            frame: 0,
+            start_time: None,
+        });
+        return Ok(());
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            println!(
+                "recorded {} frames to {}",
+                recording.frame,
+                recording.folder.display()
+            );
+        }
+    }
+
+    pub fn toggle_recording(&mut self) -> Result<(), EngineError> {
+        if self.is_recording() {
+            self.stop_recording();
+            return Ok(());
+        }
+        return self.start_recording();
+    }
+
+    /// the time to render the frame at, while recording it steps from the start of the recording
+    pub fn frame_time(&mut self, time: f32) -> f32 {
+        let fps = self.recording_fps.max(1) as f32;
+        match &mut self.recording {
+            Some(recording) => {
+                *recording.start_time.get_or_insert(time) + recording.frame as f32 / fps
+            }
+            None => time,
+        }
+    }
+
+    /// how long the last frame took, or one step while recording so movement matches the frame times
+    pub fn frame_deltatime(&self, deltatime: f32) -> f32 {
+        if self.is_recording() {
+            return 1.0 / self.recording_fps.max(1) as f32;
+        }
+        return deltatime;
+    }
+
+    /// where the next screenshot goes, if one was asked for
+    pub fn take_screenshot_path(&mut self) -> Option<PathBuf> {
+        if !self.screenshot_requested {
+            return None;
+        }
+        self.screenshot_requested = false;
+        return Some(first_free_path(
+            Path::new(&self.folder),
+            self.source.name(),
+            Some(self.format),
+        ));
+    }
+
+    /// where the next frame of the recording goes, moves the recording on a frame
+    pub fn next_recording_path(&mut self) -> Option<PathBuf> {
+        let format = self.format;
+        let recording = self.recording.as_mut()?;
+        let path = numbered_path(&recording.folder, "frame", recording.frame, Some(format));
+        recording.frame += 1;
+        return Some(path);
+    }
+}
+
+impl Widget for &mut Capture {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        ui.vertical(|ui| {
+            egui::ComboBox::from_label("Source")
+                .selected_text(self.source.name())
+                .show_ui(ui, |ui| {
+                    for source in CaptureSource::ALL {
+                        ui.selectable_value(&mut self.source, source, source.name());
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.format, CaptureFormat::Png, "PNG");
+                ui.selectable_value(&mut self.format, CaptureFormat::Exr, "EXR");
+            });
+            ui.text_edit_singleline(&mut self.folder);
+            ui.add_enabled(
+                !self.is_recording(),
+                egui::DragValue::new(&mut self.recording_fps)
+                    .range(1..=240)
+                    .suffix(" fps"),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("Screenshot (F12)").clicked() {
+                    self.screenshot_requested = true;
+                }
+                let record_text = match &self.recording {
+                    Some(recording) => format!("Stop Recording, {} frames (F9)", recording.frame),
+                    None => "Start Recording (F9)".to_owned(),
+                };
+                if ui.button(record_text).clicked() {
+                    if let Err(error) = self.toggle_recording() {
+                        println!("warning: failed to start recording: {}", error);
+                    }
+                }
+            });
+        })
+        .response
+    }
+}
+
+/// writes an image read back from the gpu, the format comes from the extension
+pub fn save_capture(image: Rgba32FImage, path: &Path) -> Result<(), EngineError> {
+    if let Some(folder) = path.parent() {
+        fs::create_dir_all(folder).map_err(|source| EngineError::Write {
+            path: folder.to_path_buf(),
+            source,
+        })?;
+    }
+    let saved = if path.extension().is_some_and(|extension| extension == "exr") {
+        image.save(path)
+    } else {
+        let mut image = DynamicImage::ImageRgba32F(image).to_rgba8();
+        // alpha is data in the g-buffer, and left over in the window, it would only hide the colors
+        for pixel in image.pixels_mut() {
+            pixel.0[3] = 255;
+        }
+        image.save(path)
+    };
+    return saved.map_err(|source| EngineError::ImageWrite {
+        path: path.to_path_buf(),
+        source,
+    });
+}
+
+/// prefix_00012.extension in folder, or a folder without an extension
+pub fn numbered_path(
+    folder: &Path,
+    prefix: &str,
+    number: u32,
+    format: Option<CaptureFormat>,
+) -> PathBuf {
+    let name = match format {
+        Some(format) => format!("{}_{:05}.{}", prefix, number, format.extension()),
+        None => format!("{}_{:05}", prefix, number),
+    };
+    return folder.join(name);
+}
+
+/// the numbered path with the lowest number that isn't taken yet
+pub fn first_free_path(folder: &Path, prefix: &str, format: Option<CaptureFormat>) -> PathBuf {
+    let mut number = 0;
+    loop {
+        let path = numbered_path(folder, prefix, number, format);
+        if !path.exists() {
+            return path;
+        }
+        number += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn recordings_step_time_by_the_frame_rate() {
+        let mut capture = Capture::new();
+        capture.recording_fps = 25;
+        assert_eq!(capture.frame_time(3.5), 3.5);
+        assert_eq!(capture.frame_deltatime(0.1), 0.1);
+
+        capture.recording = Some(Recording {
+            folder: PathBuf::from("recording_00000"),
+            frame: 0,
+            start_time: None,
+        });
+        let mut paths = Vec::new();
+        let mut times = Vec::new();
+        for real_time in [10.0, 10.5, 13.0] {
+            times.push(capture.frame_time(real_time));
+            paths.push(capture.next_recording_path().unwrap());
+        }
+        assert_eq!(times, [10.0, 10.04, 10.08]);
+        assert_eq!(capture.frame_deltatime(0.1), 0.04);
+        assert_eq!(
+            paths[2],
+            Path::new("recording_00000").join("frame_00002.png")
+        );
+
+        capture.stop_recording();
+        assert!(capture.next_recording_path().is_none());
+    }
+
+    #[test]
+    fn screenshots_take_the_first_free_number() {
+        let folder = env::temp_dir().join(format!("capture_test_{}", std::process::id()));
+        let mut capture = Capture::new();
+        capture.folder = folder.to_string_lossy().into_owned();
+        capture.format = CaptureFormat::Exr;
+        capture.source = CaptureSource::Normal;
+        assert!(capture.take_screenshot_path().is_none());
+
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("g_normal_00000.exr"), "").unwrap();
+        fs::write(folder.join("g_normal_00001.png"), "").unwrap();
+        capture.screenshot_requested = true;
+        assert_eq!(
+            capture.take_screenshot_path().unwrap(),
+            folder.join("g_normal_00001.exr")
+        );
+        assert!(!capture.screenshot_requested);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
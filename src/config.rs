@@ -0,0 +1,441 @@
+use std::{fs, io, path::PathBuf};
+
+use crate::{
+    errors::EngineError,
+    models::{MAX_SHADOW_RESOLUTION, MIN_SHADOW_RESOLUTION},
+};
+
+/// the gl versions tried when the config doesn't ask for one, newest first. clustered lighting needs 4.3
+pub const GL_VERSIONS: [(u8, u8); 5] = [(4, 6), (4, 5), (4, 3), (4, 1), (3, 3)];
+
+/// read when there is no --config flag, it's fine for it not to exist
+const DEFAULT_CONFIG_PATH: &str = "./config.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowMode {
+    Windowed,
+    /// changes the display mode to the window's resolution
+    Fullscreen,
+    /// a window without borders covering the whole display, at the display's resolution
+    Borderless,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Vsync {
+    Off,
+    On,
+    /// waits for vblank unless the frame is late, then swaps straight away. falls back to On
+    /// where the driver doesn't support it
+    Adaptive,
+}
+
+/// window and renderer settings. the defaults are overridden by config.toml, which is
+/// overridden by the command line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub width: u32,
+    pub height: u32,
+    pub window_mode: WindowMode,
+    pub vsync: Vsync,
+    /// samples of the window's framebuffer, 0 for none. the g-buffer passes aren't multisampled,
+    /// this only smooths what is drawn straight to the window
+    pub msaa_samples: u32,
+    /// horizontal field of view in degrees
+    pub fov: f32,
+    /// resolution of the shadow maps of the lights the engine starts with
+    pub shadow_resolution: u32,
+    /// a scene file to load at startup instead of the built in scene
+    pub scene: Option<String>,
+    /// only try this gl version instead of falling back through GL_VERSIONS
+    pub gl_version: Option<(u8, u8)>,
+    /// render into an offscreen framebuffer with the window hidden, write the last frame to
+    /// output and exit. enabled in the [headless] section of config.toml or --headless
+    pub headless: bool,
+    /// how many frames are rendered in headless mode
+    pub frames: u32,
+    /// the png the last headless frame is written to
+    pub output: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1052,
+            window_mode: WindowMode::Windowed,
+            vsync: Vsync::On,
+            msaa_samples: 0,
+            fov: 90.0,
+            shadow_resolution: 2048,
+            scene: None,
+            gl_version: None,
+            headless: false,
+            frames: 1,
+            output: "./headless.png".to_owned(),
+        }
+    }
+}
+
+/// every setting as it is written in config.toml, its command line flag and what it does. the
+/// flag is written with dashes, like --shadow-resolution
+const SETTINGS: [(&str, &str, &str); 12] = [
+    ("window.width", "width", "window width in pixels"),
+    ("window.height", "height", "window height in pixels"),
+    ("window.mode", "mode", "windowed, fullscreen or borderless"),
+    ("window.vsync", "vsync", "on, off or adaptive"),
+    ("window.msaa_samples", "msaa_samples", "0, 2, 4, 8 or 16"),
+    ("renderer.fov", "fov", "horizontal field of view in degrees"),
+    (
+        "renderer.shadow_resolution",
+        "shadow_resolution",
+        "shadow map size of the starting lights",
+    ),
+    (
+        "renderer.gl_version",
+        "gl_version",
+        "like 4.1, instead of trying 4.6 down to 3.3",
+    ),
+    ("renderer.scene", "scene", "scene file to open at startup"),
+    (
+        "headless.enabled",
+        "headless",
+        "render offscreen and exit, alone it means true",
+    ),
+    (
+        "headless.frames",
+        "frames",
+        "frames to render before writing the output",
+    ),
+    (
+        "headless.output",
+        "output",
+        "png the last headless frame is written to",
+    ),
+];
+
+impl Config {
+    /// the defaults, then the config file, then the flags. --config picks another config file
+    pub fn from_args(args: &[String]) -> Result<Config, EngineError> {
+        let flags = parse_flags(args).map_err(|message| EngineError::Config { message })?;
+        let mut config = Config::default();
+        let config_path = flags
+            .iter()
+            .find(|(key, _)| key == "config")
+            .map(|(_, path)| PathBuf::from(path));
+        let path = config_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+        match fs::read_to_string(&path) {
+            Ok(text) => config
+                .apply_toml(&text)
+                .map_err(|message| EngineError::Config {
+                    message: format!("{}: {}", path.display(), message),
+                })?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound && config_path.is_none() => {}
+            Err(source) => return Err(EngineError::Io { path, source }),
+        }
+        for (key, value) in flags.iter().filter(|(key, _)| key != "config") {
+            config
+                .set(key, value)
+                .map_err(|message| EngineError::Config {
+                    message: format!("--{}: {}", key.replace('_', "-"), message),
+                })?;
+        }
+        return Ok(config);
+    }
+
+    /// the text printed for --help
+    pub fn usage() -> String {
+        let mut usage = String::from("usage: amtf-engine [options]\n\n");
+        usage.push_str(
+            "  --config <path>             config file to read, ./config.toml by default\n",
+        );
+        for (_, flag, description) in SETTINGS {
+            let flag = format!("--{} <value>", flag.replace('_', "-"));
+            usage.push_str(&format!("  {:<28}{}\n", flag, description));
+        }
+        usage.push_str("  --windowed, --fullscreen, --borderless\n");
+        usage.push_str("                              short for --mode <mode>\n");
+        usage.push_str("  --help                      show this\n");
+        return usage;
+    }
+
+    /// sets the keys of a toml file, every setting sits in a [section] and is a string, number or
+    /// boolean
+    pub fn apply_toml(&mut self, text: &str) -> Result<(), String> {
+        let table: toml::Table = text
+            .parse()
+            .map_err(|error: toml::de::Error| error.to_string().trim_end().to_owned())?;
+        for (section, keys) in table {
+            let keys = match keys {
+                toml::Value::Table(keys) => keys,
+                _ => return Err(format!("unknown setting {}", section)),
+            };
+            for (key, value) in keys {
+                let key = format!("{}.{}", section, key);
+                let flag = SETTINGS
+                    .iter()
+                    .find(|(setting, _, _)| *setting == key)
+                    .map(|(_, flag, _)| *flag)
+                    .ok_or_else(|| format!("unknown setting {}", key))?;
+                let value = match value {
+                    toml::Value::String(string) => string,
+                    toml::Value::Integer(integer) => integer.to_string(),
+                    toml::Value::Float(float) => float.to_string(),
+                    toml::Value::Boolean(boolean) => boolean.to_string(),
+                    other => {
+                        return Err(format!(
+                            "{}: expected a string, number or boolean, found {}",
+                            key,
+                            other.type_str()
+                        ))
+                    }
+                };
+                self.set(flag, &value)
+                    .map_err(|message| format!("{}: {}", key, message))?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// sets one setting by its flag name, like shadow_resolution
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "width" => self.width = parse_in_range(value, 1, 16384)?,
+            "height" => self.height = parse_in_range(value, 1, 16384)?,
+            "mode" => {
+                self.window_mode = match value {
+                    "windowed" => WindowMode::Windowed,
+                    "fullscreen" => WindowMode::Fullscreen,
+                    "borderless" => WindowMode::Borderless,
+                    _ => return Err(expected("windowed, fullscreen or borderless", value)),
+                }
+            }
+            "vsync" => {
+                self.vsync = match value {
+                    "off" | "false" => Vsync::Off,
+                    "on" | "true" => Vsync::On,
+                    "adaptive" => Vsync::Adaptive,
+                    _ => return Err(expected("on, off or adaptive", value)),
+                }
+            }
+            "msaa_samples" => {
+                self.msaa_samples = match value.parse() {
+                    Ok(samples @ (0 | 2 | 4 | 8 | 16)) => samples,
+                    _ => return Err(expected("0, 2, 4, 8 or 16", value)),
+                }
+            }
+            "fov" => {
+                self.fov = match value.parse::<f32>() {
+                    Ok(fov) if (1.0..=179.0).contains(&fov) => fov,
+                    _ => return Err(expected("a number of degrees from 1 to 179", value)),
+                }
+            }
+            "shadow_resolution" => {
+                self.shadow_resolution =
+                    parse_in_range(value, MIN_SHADOW_RESOLUTION, MAX_SHADOW_RESOLUTION)?
+            }
+            "gl_version" => self.gl_version = Some(parse_gl_version(value)?),
+            "scene" => self.scene = Some(value.to_owned()),
+            "headless" => {
+                self.headless = match value {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(expected("true or false", value)),
+                }
+            }
+            "frames" => self.frames = parse_in_range(value, 1, 100_000)?,
+            "output" => self.output = value.to_owned(),
+            _ => return Err(format!("unknown setting {}", name)),
+        }
+        return Ok(());
+    }
+}
+
+fn expected(what: &str, value: &str) -> String {
+    format!("expected {}, found {}", what, value)
+}
+
+fn parse_in_range(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(number) if (min..=max).contains(&number) => Ok(number),
+        _ => Err(expected(
+            &format!("a whole number from {} to {}", min, max),
+            value,
+        )),
+    }
+}
+
+/// major.minor, the shaders need at least 3.3
+fn parse_gl_version(value: &str) -> Result<(u8, u8), String> {
+    let version = value
+        .split_once('.')
+        .and_then(|(major, minor)| Some((major.parse::<u8>().ok()?, minor.parse::<u8>().ok()?)));
+    match version {
+        Some(version) if version >= (3, 3) => Ok(version),
+        _ => Err(expected("a gl version of 3.3 or newer, like 4.1", value)),
+    }
+}
+
+/// the flags as (name, value) pairs with underscores, --name value and --name=value both work
+fn parse_flags(args: &[String]) -> Result<Vec<(String, String)>, String> {
+    let mut flags = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let flag = arg
+            .strip_prefix("--")
+            .ok_or_else(|| format!("unexpected argument {}, see --help", arg))?;
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => (name.replace('-', "_"), Some(value.to_owned())),
+            None => (flag.replace('-', "_"), None),
+        };
+        if matches!(name.as_str(), "windowed" | "fullscreen" | "borderless") {
+            flags.push(("mode".to_owned(), name));
+            continue;
+        }
+        if name == "headless" && value.is_none() {
+            flags.push((name, "true".to_owned()));
+            continue;
+        }
+        let known = name == "config" || SETTINGS.iter().any(|(_, flag, _)| *flag == name);
+        if !known {
+            return Err(format!("unknown flag {}, see --help", arg));
+        }
+        let value = match value {
+            Some(value) => value,
+            None => args
+                .next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", arg))?,
+        };
+        flags.push((name, value));
+    }
+    return Ok(flags);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn toml_sets_every_setting() {
+        let mut config = Config::default();
+        config
+            .apply_toml(
+                r#"
+                # a comment
+                [window]
+                width = 1280
+                height = 720 # after a value
+                mode = "borderless"
+                vsync = "adaptive"
+                msaa_samples = 4
+
+                [renderer]
+                fov = 75.5
+                shadow_resolution = 4096
+                gl_version = "4.1"
+                scene = "./scenes/#1.json"
+
+                [headless]
+                enabled = true
+                frames = 3
+                output = "./out.png"
+                "#,
+            )
+            .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                width: 1280,
+                height: 720,
+                window_mode: WindowMode::Borderless,
+                vsync: Vsync::Adaptive,
+                msaa_samples: 4,
+                fov: 75.5,
+                shadow_resolution: 4096,
+                scene: Some("./scenes/#1.json".to_owned()),
+                gl_version: Some((4, 1)),
+                headless: true,
+                frames: 3,
+                output: "./out.png".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn flags_override_the_config_file() {
+        let dir = std::env::temp_dir().join(format!("config_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(&path, "[window]\nwidth = 800\nheight = 600\n").unwrap();
+        let config = Config::from_args(&args(&[
+            "--config",
+            path.to_str().unwrap(),
+            "--width=1024",
+            "--fullscreen",
+            "--shadow-resolution",
+            "512",
+            "--headless",
+            "--frames=4",
+        ]))
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(config.width, 1024);
+        assert_eq!(config.height, 600);
+        assert_eq!(config.window_mode, WindowMode::Fullscreen);
+        assert_eq!(config.shadow_resolution, 512);
+        assert!(config.headless);
+        assert_eq!(config.frames, 4);
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        let error = |args_: &[&str]| match Config::from_args(&args(args_)) {
+            Err(EngineError::Config { message }) => message,
+            Err(other) => panic!("unexpected error {}", other),
+            Ok(_) => panic!("{:?} should fail", args_),
+        };
+        assert_eq!(
+            error(&["--msaa-samples", "3"]),
+            "--msaa-samples: expected 0, 2, 4, 8 or 16, found 3"
+        );
+        assert_eq!(
+            error(&["--msaa-samples", "1"]),
+            "--msaa-samples: expected 0, 2, 4, 8 or 16, found 1"
+        );
+        assert_eq!(
+            error(&["--gl-version", "2.1"]),
+            "--gl-version: expected a gl version of 3.3 or newer, like 4.1, found 2.1"
+        );
+        assert_eq!(error(&["--width"]), "--width needs a value");
+        assert_eq!(error(&["--depth", "3"]), "unknown flag --depth, see --help");
+
+        let mut config = Config::default();
+        assert_eq!(
+            config.apply_toml("[window]\nvsync = \"sometimes\"\n"),
+            Err("window.vsync: expected on, off or adaptive, found sometimes".to_owned())
+        );
+        assert_eq!(
+            config.apply_toml("fov = 90\n"),
+            Err("unknown setting fov".to_owned())
+        );
+        // headless is only turned on in its own section now
+        assert_eq!(
+            config.apply_toml("[window]\nheadless = true\n"),
+            Err("unknown setting window.headless".to_owned())
+        );
+        assert_eq!(
+            config.apply_toml("[headless]\nenabled = [true]\n"),
+            Err("headless.enabled: expected a string, number or boolean, found array".to_owned())
+        );
+        assert!(config
+            .apply_toml("[window\nwidth = 800\n")
+            .unwrap_err()
+            .contains("line 1"));
+    }
+}
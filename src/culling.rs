@@ -0,0 +1,439 @@
+use cgmath::{Array, InnerSpace, Matrix, Matrix3, Matrix4, Vector3, Vector4, Zero};
+use egui_sdl2_gl::egui::{self, Widget};
+
+/// an axis aligned box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    /// the smallest box around points, with no points min ends up above max and the box is empty
+    pub fn from_points(points: impl IntoIterator<Item = Vector3<f32>>) -> Self {
+        let mut aabb = Self {
+            min: Vector3::from_value(f32::INFINITY),
+            max: Vector3::from_value(f32::NEG_INFINITY),
+        };
+        for point in points {
+            aabb.min = Vector3::new(
+                aabb.min.x.min(point.x),
+                aabb.min.y.min(point.y),
+                aabb.min.z.min(point.z),
+            );
+            aabb.max = Vector3::new(
+                aabb.max.x.max(point.x),
+                aabb.max.y.max(point.y),
+                aabb.max.z.max(point.z),
+            );
+        }
+        return aabb;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min) / 2.0
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        return Self::from_points([self.min, self.max, other.min, other.max]);
+    }
+
+    /// the box around this box after it is transformed by matrix, which is a bit bigger than the box
+    /// itself when matrix rotates it
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        let center = (matrix * self.center().extend(1.0)).truncate();
+        let extents = self.half_extents();
+        // every axis of the box adds how far it reaches along each world axis
+        let reach = |column: Vector4<f32>, extent: f32| {
+            Vector3::new(column.x.abs(), column.y.abs(), column.z.abs()) * extent
+        };
+        let half_extents =
+            reach(matrix.x, extents.x) + reach(matrix.y, extents.y) + reach(matrix.z, extents.z);
+        return Self {
+            min: center - half_extents,
+            max: center + half_extents,
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// the sphere after it is transformed by matrix, scaled by at least how much matrix stretches
+    /// anything, so it still contains everything when the scale isn't uniform
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Self {
+        let linear = Matrix3::from_cols(
+            matrix.x.truncate(),
+            matrix.y.truncate(),
+            matrix.z.truncate(),
+        );
+        // the stretch is the square root of the biggest eigenvalue of linear^T * linear, no
+        // eigenvalue is bigger than the biggest row sum. exact for a rotation and a scale
+        let squared = linear.transpose() * linear;
+        let biggest_row_sum = (0..3)
+            .map(|column| {
+                let column = squared[column];
+                column.x.abs() + column.y.abs() + column.z.abs()
+            })
+            .fold(0.0f32, f32::max);
+        let scale = biggest_row_sum.sqrt();
+        Self {
+            center: (matrix * self.center.extend(1.0)).truncate(),
+            radius: self.radius * scale,
+        }
+    }
+
+    pub fn intersects_sphere(&self, other: &BoundingSphere) -> bool {
+        (self.center - other.center).magnitude2() <= (self.radius + other.radius).powi(2)
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        let closest = Vector3::new(
+            self.center.x.clamp(aabb.min.x, aabb.max.x),
+            self.center.y.clamp(aabb.min.y, aabb.max.y),
+            self.center.z.clamp(aabb.min.z, aabb.max.z),
+        );
+        (closest - self.center).magnitude2() <= self.radius * self.radius
+    }
+}
+
+/// a box and a sphere around the same thing, the sphere is the cheaper test and the box the tighter one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+impl Bounds {
+    /// the sphere is around the center of the box, as small as the points allow
+    pub fn from_points(points: &[Vector3<f32>]) -> Self {
+        let aabb = Aabb::from_points(points.iter().copied());
+        let center = if aabb.is_empty() {
+            Vector3::zero()
+        } else {
+            aabb.center()
+        };
+        let radius = points
+            .iter()
+            .map(|point| (point - center).magnitude())
+            .fold(0.0f32, f32::max);
+        Self {
+            aabb,
+            sphere: BoundingSphere { center, radius },
+        }
+    }
+
+    /// the sphere goes through the box's corners
+    pub fn from_aabb(aabb: Aabb) -> Self {
+        if aabb.is_empty() {
+            return Self::from_points(&[]);
+        }
+        Self {
+            aabb,
+            sphere: BoundingSphere {
+                center: aabb.center(),
+                radius: aabb.half_extents().magnitude(),
+            },
+        }
+    }
+
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Self {
+        Self {
+            aabb: self.aabb.transformed(matrix),
+            sphere: self.sphere.transformed(matrix),
+        }
+    }
+}
+
+/// the points with normal.dot(point) + distance >= 0 are in front of the plane
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    /// from the a, b, c, d of ax + by + cz + d = 0, normalized so distances come out in world units
+    fn from_coefficients(coefficients: Vector4<f32>) -> Self {
+        let length = coefficients.truncate().magnitude();
+        Self {
+            normal: coefficients.truncate() / length,
+            distance: coefficients.w / length,
+        }
+    }
+
+    pub fn signed_distance(&self, point: Vector3<f32>) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+/// the 6 planes around what a projection * view matrix can see, with the normals pointing inwards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// works for perspective and orthographic matrices, with gl's -1 to 1 depth range
+    pub fn from_matrix(view_projection: Matrix4<f32>) -> Self {
+        let (x, y, z, w) = (
+            view_projection.row(0),
+            view_projection.row(1),
+            view_projection.row(2),
+            view_projection.row(3),
+        );
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z].map(Plane::from_coefficients),
+        }
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(sphere.center) >= -sphere.radius)
+    }
+
+    /// can let boxes near the frustum's corners through, it never culls a box that is visible
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the normal
+            let corner = Vector3::new(
+                if plane.normal.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.normal.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.normal.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.signed_distance(corner) >= 0.0
+        })
+    }
+}
+
+/// what a pass can see, point light shadows render a whole cube map so they see a sphere
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CullingVolume {
+    Frustum(Frustum),
+    Sphere(BoundingSphere),
+}
+
+impl CullingVolume {
+    pub fn from_matrix(view_projection: Matrix4<f32>) -> Self {
+        CullingVolume::Frustum(Frustum::from_matrix(view_projection))
+    }
+
+    /// bounds in world space
+    pub fn is_visible(&self, bounds: &Bounds) -> bool {
+        if bounds.aabb.is_empty() {
+            return false;
+        }
+        match self {
+            CullingVolume::Frustum(frustum) => {
+                frustum.intersects_sphere(&bounds.sphere) && frustum.intersects_aabb(&bounds.aabb)
+            }
+            CullingVolume::Sphere(sphere) => {
+                sphere.intersects_sphere(&bounds.sphere) && sphere.intersects_aabb(&bounds.aabb)
+            }
+        }
+    }
+}
+
+/// how many models one kind of pass drew and culled this frame, summed over all of its lights
+pub struct PassCullingStats {
+    pub pass: String,
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+#[derive(Default)]
+pub struct CullingStats {
+    pub passes: Vec<PassCullingStats>,
+}
+
+impl CullingStats {
+    /// call at the start of every frame
+    pub fn clear(&mut self) {
+        self.passes.clear();
+    }
+
+    pub fn record(&mut self, pass: &str, drawn: bool) {
+        let index = match self.passes.iter().position(|stats| stats.pass == pass) {
+            Some(index) => index,
+            None => {
+                self.passes.push(PassCullingStats {
+                    pass: pass.to_owned(),
+                    drawn: 0,
+                    culled: 0,
+                });
+                self.passes.len() - 1
+            }
+        };
+        if drawn {
+            self.passes[index].drawn += 1;
+        } else {
+            self.passes[index].culled += 1;
+        }
+    }
+}
+
+impl Widget for &mut CullingStats {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        egui::Grid::new("culling_stats_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Pass");
+                ui.label("Drawn");
+                ui.label("Culled");
+                ui.end_row();
+                for stats in &self.passes {
+                    ui.label(&stats.pass);
+                    ui.label(stats.drawn.to_string());
+                    ui.label(stats.culled.to_string());
+                    ui.end_row();
+                }
+            })
+            .response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{perspective, Deg, Point3};
+
+    use super::*;
+
+    fn camera_frustum() -> Frustum {
+        // at the origin looking down -z, 90 degrees each way and 0.1 to 100
+        let projection = perspective(Deg(90.0), 1.0, 0.1, 100.0);
+        let view = Matrix4::look_at_rh(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vector3::unit_y(),
+        );
+        Frustum::from_matrix(projection * view)
+    }
+
+    fn unit_cube_at(center: Vector3<f32>) -> Bounds {
+        let corners: Vec<Vector3<f32>> = (0..8)
+            .map(|i| {
+                center
+                    + Vector3::new(
+                        if i & 1 == 0 { -0.5 } else { 0.5 },
+                        if i & 2 == 0 { -0.5 } else { 0.5 },
+                        if i & 4 == 0 { -0.5 } else { 0.5 },
+                    )
+            })
+            .collect();
+        Bounds::from_points(&corners)
+    }
+
+    #[test]
+    fn frustum_planes_face_inwards() {
+        let frustum = camera_frustum();
+        let inside = Vector3::new(0.0, 0.0, -10.0);
+        for plane in &frustum.planes {
+            assert!(plane.signed_distance(inside) > 0.0);
+            assert!((plane.normal.magnitude() - 1.0).abs() < 1e-5);
+        }
+        // the near plane is 0.1 in front of the camera
+        assert!((frustum.planes[4].signed_distance(Vector3::new(0.0, 0.0, -0.1))).abs() < 1e-4);
+    }
+
+    #[test]
+    fn perspective_frustum_culls_what_is_outside() {
+        let volume = CullingVolume::Frustum(camera_frustum());
+        assert!(volume.is_visible(&unit_cube_at(Vector3::new(0.0, 0.0, -5.0))));
+        // behind the camera
+        assert!(!volume.is_visible(&unit_cube_at(Vector3::new(0.0, 0.0, 5.0))));
+        // past the far plane
+        assert!(!volume.is_visible(&unit_cube_at(Vector3::new(0.0, 0.0, -110.0))));
+        // left of the 45 degree side plane, then straddling it
+        assert!(!volume.is_visible(&unit_cube_at(Vector3::new(-8.0, 0.0, -5.0))));
+        assert!(volume.is_visible(&unit_cube_at(Vector3::new(-5.2, 0.0, -5.0))));
+        // above the top plane
+        assert!(!volume.is_visible(&unit_cube_at(Vector3::new(0.0, 8.0, -5.0))));
+    }
+
+    #[test]
+    fn orthographic_frustum_is_a_box() {
+        let projection = cgmath::ortho(-2.0, 2.0, -2.0, 2.0, 0.0, 10.0);
+        let volume = CullingVolume::from_matrix(projection);
+        assert!(volume.is_visible(&unit_cube_at(Vector3::new(2.3, 0.0, -5.0))));
+        assert!(!volume.is_visible(&unit_cube_at(Vector3::new(2.6, 0.0, -5.0))));
+        assert!(!volume.is_visible(&unit_cube_at(Vector3::new(0.0, 0.0, -10.6))));
+    }
+
+    #[test]
+    fn sphere_volume_culls_by_distance() {
+        let volume = CullingVolume::Sphere(BoundingSphere {
+            center: Vector3::zero(),
+            radius: 3.0,
+        });
+        assert!(volume.is_visible(&unit_cube_at(Vector3::new(3.4, 0.0, 0.0))));
+        assert!(!volume.is_visible(&unit_cube_at(Vector3::new(3.6, 0.0, 0.0))));
+        // the corner of the cube's sphere reaches in, but the box doesn't
+        assert!(!volume.is_visible(&unit_cube_at(Vector3::new(2.7, 2.7, 0.0))));
+    }
+
+    #[test]
+    fn empty_bounds_are_never_visible() {
+        let volume = CullingVolume::Frustum(camera_frustum());
+        assert!(!volume.is_visible(&Bounds::from_points(&[])));
+    }
+
+    #[test]
+    fn union_ignores_empty_boxes() {
+        let aabb = unit_cube_at(Vector3::new(1.0, 0.0, 0.0)).aabb;
+        let empty = Aabb::from_points([]);
+        assert_eq!(empty.union(&aabb), aabb);
+        assert_eq!(aabb.union(&empty), aabb);
+        let both = aabb.union(&unit_cube_at(Vector3::new(-1.0, 0.0, 0.0)).aabb);
+        assert_eq!(both.min, Vector3::new(-1.5, -0.5, -0.5));
+        assert_eq!(both.max, Vector3::new(1.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn transformed_bounds_contain_the_transformed_box() {
+        let bounds = unit_cube_at(Vector3::zero());
+        let matrix = Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0))
+            * Matrix4::from_nonuniform_scale(2.0, 1.0, 1.0)
+            * Matrix4::from_angle_y(Deg(45.0));
+        let transformed = bounds.transformed(&matrix);
+        // rotated 45 degrees the box reaches sqrt(2) / 2 along x and z, then x is doubled
+        let reach = 2.0f32.sqrt() / 2.0;
+        let expected = Vector3::new(2.0 * reach, 0.5, reach);
+        assert!((transformed.aabb.half_extents() - expected).magnitude() < 1e-5);
+        assert!((transformed.aabb.center() - Vector3::new(1.0, 2.0, 3.0)).magnitude() < 1e-5);
+        assert!((transformed.sphere.radius - 2.0 * 3.0f32.sqrt() / 2.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,304 @@
+use egui_sdl2_gl::egui;
+
+/// a buffer the debug view shader can show instead of the lit scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugBuffer {
+    Position,
+    Normal,
+    Albedo,
+    Specular,
+    Emissive,
+    AmbientOcclusion,
+    /// what the lighting passes added up
+    Lighting,
+    /// the distance from the camera
+    Depth,
+    SpotShadow {
+        light: usize,
+    },
+    /// one cascade of a directional light's shadow map
+    DirectionalShadow {
+        light: usize,
+        cascade: usize,
+    },
+    /// one face of a point light's shadow cubemap, in the order of GL_TEXTURE_CUBE_MAP_POSITIVE_X + face
+    PointShadow {
+        light: usize,
+        face: usize,
+    },
+}
+
+/// the names of the faces of a cubemap, in the order of GL_TEXTURE_CUBE_MAP_POSITIVE_X + face
+pub const CUBEMAP_FACE_NAMES: [&str; 6] = ["+X", "-X", "+Y", "-Y", "+Z", "-Z"];
+
+impl DebugBuffer {
+    /// everything that comes from the g-buffer and light buffer, in the order the grid shows them
+    pub const SCREEN_BUFFERS: [Self; 8] = [
+        Self::Position,
+        Self::Normal,
+        Self::Albedo,
+        Self::Specular,
+        Self::Emissive,
+        Self::AmbientOcclusion,
+        Self::Lighting,
+        Self::Depth,
+    ];
+
+    pub fn name(self) -> String {
+        match self {
+            Self::Position => "Position".to_owned(),
+            Self::Normal => "Normal".to_owned(),
+            Self::Albedo => "Albedo".to_owned(),
+            Self::Specular => "Specular".to_owned(),
+            Self::Emissive => "Emissive".to_owned(),
+            Self::AmbientOcclusion => "Ambient Occlusion".to_owned(),
+            Self::Lighting => "Lighting".to_owned(),
+            Self::Depth => "Depth".to_owned(),
+            Self::SpotShadow { light } => format!("Spot Light {} Shadow", light),
+            Self::DirectionalShadow { light, cascade } => {
+                format!("Directional Light {} Cascade {}", light, cascade)
+            }
+            Self::PointShadow { light, face } => {
+                format!("Point Light {} Shadow {}", light, CUBEMAP_FACE_NAMES[face])
+            }
+        }
+    }
+
+    /// the mode uniform of the debug view shader
+    pub fn shader_mode(self) -> i32 {
+        match self {
+            Self::Position => 0,
+            Self::Normal => 1,
+            Self::Albedo => 2,
+            Self::Specular => 3,
+            Self::Emissive => 4,
+            Self::AmbientOcclusion => 5,
+            Self::Lighting => 6,
+            Self::Depth => 7,
+            Self::SpotShadow { .. } => 8,
+            Self::DirectionalShadow { .. } => 9,
+            Self::PointShadow { .. } => 10,
+        }
+    }
+}
+
+/// every shadow map there is, cascade_counts has the cascade count of each directional light and
+/// point_shadow_lights the point lights that have a shadow cubemap
+pub fn shadow_buffers(
+    cascade_counts: &[usize],
+    spot_light_count: usize,
+    point_shadow_lights: &[usize],
+) -> Vec<DebugBuffer> {
+    let mut buffers = Vec::new();
+    for (light, cascade_count) in cascade_counts.iter().enumerate() {
+        for cascade in 0..*cascade_count {
+            buffers.push(DebugBuffer::DirectionalShadow { light, cascade });
+        }
+    }
+    for light in 0..spot_light_count {
+        buffers.push(DebugBuffer::SpotShadow { light });
+    }
+    for light in point_shadow_lights {
+        for face in 0..CUBEMAP_FACE_NAMES.len() {
+            buffers.push(DebugBuffer::PointShadow {
+                light: *light,
+                face,
+            });
+        }
+    }
+    return buffers;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugViewMode {
+    /// the lit scene
+    Off,
+    Buffer(DebugBuffer),
+    /// every buffer at once, side by side
+    Grid,
+}
+
+/// a rectangle of the screen in pixels, from the bottom left like gl::Viewport
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// splits the screen into a grid with room for count tiles, filled from the top left
+pub fn grid_tiles(count: usize, size: (u32, u32)) -> Vec<Tile> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let columns = (count as f32).sqrt().ceil() as u32;
+    let rows = (count as u32).div_ceil(columns);
+    let (width, height) = (size.0 / columns, size.1 / rows);
+    return (0..count as u32)
+        .map(|i| Tile {
+            x: (i % columns) * width,
+            y: size.1 - (i / columns + 1) * height,
+            width,
+            height,
+        })
+        .collect();
+}
+
+/// what the debug view shows and how the buffers are mapped to colors
+pub struct DebugViews {
+    pub mode: DebugViewMode,
+    /// positions from -position_range to position_range meters go from black to full color
+    pub position_range: f32,
+    /// the distance from the camera that is white in the depth view
+    pub depth_range: f32,
+}
+
+impl DebugViews {
+    pub fn new() -> Self {
+        Self {
+            mode: DebugViewMode::Off,
+            position_range: 20.0,
+            depth_range: 50.0,
+        }
+    }
+
+    /// the buffers to draw this frame and where on the screen each one goes, a shadow map of a
+    /// light that was removed turns the view off
+    pub fn tiles(
+        &mut self,
+        size: (u32, u32),
+        shadow_buffers: &[DebugBuffer],
+    ) -> Vec<(DebugBuffer, Tile)> {
+        let full_screen = Tile {
+            x: 0,
+            y: 0,
+            width: size.0,
+            height: size.1,
+        };
+        match self.mode {
+            DebugViewMode::Off => Vec::new(),
+            DebugViewMode::Buffer(buffer) => {
+                if DebugBuffer::SCREEN_BUFFERS.contains(&buffer) || shadow_buffers.contains(&buffer)
+                {
+                    return vec![(buffer, full_screen)];
+                }
+                self.mode = DebugViewMode::Off;
+                return Vec::new();
+            }
+            DebugViewMode::Grid => {
+                let buffers: Vec<DebugBuffer> = DebugBuffer::SCREEN_BUFFERS
+                    .iter()
+                    .chain(shadow_buffers)
+                    .copied()
+                    .collect();
+                let tiles = grid_tiles(buffers.len(), size);
+                return buffers.into_iter().zip(tiles).collect();
+            }
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, shadow_buffers: &[DebugBuffer]) {
+        let selected_text = match self.mode {
+            DebugViewMode::Off => "Off".to_owned(),
+            DebugViewMode::Buffer(buffer) => buffer.name(),
+            DebugViewMode::Grid => "All Buffers".to_owned(),
+        };
+        egui::ComboBox::from_label("Buffer")
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.mode, DebugViewMode::Off, "Off");
+                ui.selectable_value(&mut self.mode, DebugViewMode::Grid, "All Buffers");
+                for buffer in DebugBuffer::SCREEN_BUFFERS.iter().chain(shadow_buffers) {
+                    ui.selectable_value(
+                        &mut self.mode,
+                        DebugViewMode::Buffer(*buffer),
+                        buffer.name(),
+                    );
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.position_range)
+                    .prefix("Position Range ")
+                    .suffix(" m")
+                    .range(0.1..=1000.0)
+                    .speed(0.1),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.depth_range)
+                    .prefix("Depth Range ")
+                    .suffix(" m")
+                    .range(0.1..=1000.0)
+                    .speed(0.1),
+            );
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_tiles_fill_rows_from_the_top() {
+        let tiles = grid_tiles(5, (300, 200));
+        assert_eq!(tiles.len(), 5);
+        // 3 columns and 2 rows
+        assert_eq!(
+            tiles[0],
+            Tile {
+                x: 0,
+                y: 100,
+                width: 100,
+                height: 100
+            }
+        );
+        assert_eq!((tiles[2].x, tiles[2].y), (200, 100));
+        assert_eq!((tiles[4].x, tiles[4].y), (100, 0));
+        assert!(grid_tiles(0, (300, 200)).is_empty());
+        assert_eq!(grid_tiles(1, (300, 200))[0].width, 300);
+    }
+
+    #[test]
+    fn views_of_removed_lights_turn_off() {
+        let shadows = shadow_buffers(&[2], 1, &[3]);
+        assert_eq!(
+            shadows[..3],
+            [
+                DebugBuffer::DirectionalShadow {
+                    light: 0,
+                    cascade: 0
+                },
+                DebugBuffer::DirectionalShadow {
+                    light: 0,
+                    cascade: 1
+                },
+                DebugBuffer::SpotShadow { light: 0 },
+            ]
+        );
+        // every face of the cubemap of point light 3
+        assert_eq!(shadows.len(), 9);
+        assert_eq!(shadows[8], DebugBuffer::PointShadow { light: 3, face: 5 });
+        assert_eq!(shadows[8].name(), "Point Light 3 Shadow -Z");
+
+        let mut views = DebugViews::new();
+        views.mode = DebugViewMode::Grid;
+        let tiles = views.tiles((400, 300), &shadows);
+        assert_eq!(tiles.len(), DebugBuffer::SCREEN_BUFFERS.len() + 9);
+        assert_eq!(tiles[8].0, shadows[0]);
+
+        views.mode = DebugViewMode::Buffer(DebugBuffer::SpotShadow { light: 0 });
+        assert_eq!(views.tiles((400, 300), &shadows).len(), 1);
+        assert!(views
+            .tiles((400, 300), &shadow_buffers(&[2], 0, &[3]))
+            .is_empty());
+        assert_eq!(views.mode, DebugViewMode::Off);
+
+        // a point light that stopped casting shadows has no cubemap to show
+        views.mode = DebugViewMode::Buffer(DebugBuffer::PointShadow { light: 3, face: 0 });
+        assert!(views
+            .tiles((400, 300), &shadow_buffers(&[2], 1, &[]))
+            .is_empty());
+    }
+}
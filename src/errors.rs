@@ -0,0 +1,156 @@
+use std::{fmt, io, path::PathBuf};
+
+use gl::types::GLenum;
+use image::ImageError;
+
+/// everything that can go wrong while loading assets or creating gl objects
+pub enum EngineError {
+    /// a file couldn't be read
+    Io { path: PathBuf, source: io::Error },
+    /// a file couldn't be written
+    Write { path: PathBuf, source: io::Error },
+    /// tobj couldn't parse an obj file
+    ObjLoad {
+        path: PathBuf,
+        source: tobj::LoadError,
+    },
+    /// an image couldn't be opened or decoded
+    Image { path: PathBuf, source: ImageError },
+    /// an image couldn't be encoded or written
+    ImageWrite { path: PathBuf, source: ImageError },
+    /// an empty texture, like a shadow map, couldn't be made
+    Texture { name: String, source: ImageError },
+    /// a gltf or glb file is broken or uses something the loader doesn't support
+    Gltf { path: PathBuf, message: String },
+    /// a shader failed to compile, log is the gl info log
+    ShaderCompile {
+        path: PathBuf,
+        stage: &'static str,
+        log: String,
+    },
+    /// the shaders compiled but the program failed to link
+    ProgramLink { paths: Vec<PathBuf>, log: String },
+    /// a scene file isn't json or is missing something a scene needs
+    Scene { path: PathBuf, message: String },
+    /// a color grading lookup table image isn't a strip of square slices
+    Lut { path: PathBuf, message: String },
+    /// a setting in the config file or on the command line is wrong
+    Config { message: String },
+    /// sdl couldn't make the window or a gl context
+    Window { message: String },
+    /// glCheckFramebufferStatus didn't return FRAMEBUFFER_COMPLETE
+    FramebufferIncomplete { status: GLenum },
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Io { path, source } => {
+                write!(f, "failed to read {}: {}", path.display(), source)
+            }
+            EngineError::Write { path, source } => {
+                write!(f, "failed to write {}: {}", path.display(), source)
+            }
+            EngineError::ObjLoad { path, source } => {
+                write!(f, "failed to load obj {}: {}", path.display(), source)
+            }
+            EngineError::Image { path, source } => {
+                write!(f, "failed to load image {}: {}", path.display(), source)
+            }
+            EngineError::ImageWrite { path, source } => {
+                write!(f, "failed to write image {}: {}", path.display(), source)
+            }
+            EngineError::Texture { name, source } => {
+                write!(f, "failed to create texture {}: {}", name, source)
+            }
+            EngineError::Gltf { path, message } => {
+                write!(f, "failed to load gltf {}: {}", path.display(), message)
+            }
+            EngineError::Scene { path, message } => {
+                write!(f, "failed to load scene {}: {}", path.display(), message)
+            }
+            EngineError::Lut { path, message } => {
+                write!(f, "failed to load lut {}: {}", path.display(), message)
+            }
+            EngineError::Config { message } => write!(f, "invalid config: {}", message),
+            EngineError::Window { message } => {
+                write!(f, "failed to create the window: {}", message)
+            }
+            EngineError::ShaderCompile { path, stage, log } => write!(
+                f,
+                "failed to compile {} shader {}:\n{}",
+                stage,
+                path.display(),
+                log.trim_end()
+            ),
+            EngineError::ProgramLink { paths, log } => {
+                let paths: Vec<String> = paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                write!(
+                    f,
+                    "failed to link program ({}):\n{}",
+                    paths.join(", "),
+                    log.trim_end()
+                )
+            }
+            EngineError::FramebufferIncomplete { status } => write!(
+                f,
+                "framebuffer is not complete: {}",
+                framebuffer_status_name(*status)
+            ),
+        }
+    }
+}
+
+/// forwards to Display so an error returned from main prints the readable message, with the shader log on its own lines
+impl fmt::Debug for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for EngineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EngineError::Io { source, .. } => Some(source),
+            EngineError::Write { source, .. } => Some(source),
+            EngineError::ObjLoad { source, .. } => Some(source),
+            EngineError::Image { source, .. } => Some(source),
+            EngineError::ImageWrite { source, .. } => Some(source),
+            EngineError::Texture { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+pub fn shader_stage_name(kind: GLenum) -> &'static str {
+    match kind {
+        gl::VERTEX_SHADER => "vertex",
+        gl::FRAGMENT_SHADER => "fragment",
+        gl::GEOMETRY_SHADER => "geometry",
+        gl::COMPUTE_SHADER => "compute",
+        gl::TESS_CONTROL_SHADER => "tessellation control",
+        gl::TESS_EVALUATION_SHADER => "tessellation evaluation",
+        _ => "unknown",
+    }
+}
+
+fn framebuffer_status_name(status: GLenum) -> String {
+    match status {
+        gl::FRAMEBUFFER_UNDEFINED => "FRAMEBUFFER_UNDEFINED".to_owned(),
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "FRAMEBUFFER_INCOMPLETE_ATTACHMENT".to_owned(),
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => {
+            "FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT".to_owned()
+        }
+        gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER".to_owned(),
+        gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "FRAMEBUFFER_INCOMPLETE_READ_BUFFER".to_owned(),
+        gl::FRAMEBUFFER_UNSUPPORTED => "FRAMEBUFFER_UNSUPPORTED".to_owned(),
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "FRAMEBUFFER_INCOMPLETE_MULTISAMPLE".to_owned(),
+        gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => {
+            "FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS".to_owned()
+        }
+        _ => format!("0x{:x}", status),
+    }
+}
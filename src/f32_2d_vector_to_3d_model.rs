@@ -1,6 +1,13 @@
-use cgmath::{Vector2, Vector3};
+use std::rc::Rc;
 
-use crate::{material_structs::MaterialInfo, models::Model};
+use cgmath::{Vector2, Vector3, Vector4, Zero};
+
+use crate::{
+    material_structs::MaterialInfo,
+    models::{Mesh, Model},
+    tangents::generate_tangents,
+    vertex_layout::Vertex,
+};
 
 pub struct F322DVectorTo3DModel {
     pub model_index: usize,
@@ -28,22 +35,22 @@ impl F322DVectorTo3DModel {
     }
     pub fn create_geometry(&self, models: &mut Vec<Model>) {
         let model = &mut models[self.model_index];
-        let mut vertices: Vec<(Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector2<f32>)> =
-            Vec::new();
+        let mut vertices: Vec<Vertex> = Vec::new();
         let mut indices: Vec<u32> = Vec::new();
 
         for i in 0..self.values.len() {
             for j in 0..self.values[i].len() {
-                let vertice: (Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector2<f32>) = (
-                    Vector3::new(
+                let vertice = Vertex {
+                    position: Vector3::new(
                         i as f32 * self.scale,
                         self.values[i][j] * self.scale,
                         j as f32 * self.scale,
                     ),
-                    Vector3::new(0.529, 0.808, 0.922),
-                    Vector3::unit_y(),
-                    Vector2::new(i as f32, j as f32),
-                );
+                    color: Vector3::new(0.529, 0.808, 0.922),
+                    normal: Vector3::unit_y(),
+                    uv: Vector2::new(i as f32, j as f32),
+                    tangent: Vector4::zero(),
+                };
                 vertices.push(vertice);
             }
         }
@@ -58,24 +65,26 @@ impl F322DVectorTo3DModel {
                 indices.push(((i + 1) + (j + 1) * self.values[i + 1].len()) as u32);
             }
         }
-        model.vertices = vertices;
-        model.indices = indices;
+        generate_tangents(&mut vertices, &mut indices);
+        model.mesh = Rc::new(Mesh::new(&vertices, &indices));
         model.material_info = MaterialInfo {
             ambient: Vector3::new(1., 1., 1.),
             diffuse: Vector3::new(1., 1., 1.),
             specular: 0.,
             shininess: 32.,
+            dissolve: 1.,
+            ..MaterialInfo::default()
         };
-        model.start();
     }
 }
 
 /*
 globals.models.push(Model::new(
-        &Vec::new(),
-        &Vec::new(),
-        "./shaders/2dVectorModel/shader.vert",
-        "./shaders/2dVectorModel/shader.frag",
+        Rc::new(Mesh::new(&Vec::new(), &Vec::new())),
+        globals.assets.program(
+            "./shaders/2dVectorModel/shader.vert",
+            "./shaders/2dVectorModel/shader.frag",
+        ).unwrap(),
         "2d vector model".to_owned(),
     ));
     let length = globals.models.len();
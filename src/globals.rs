@@ -1,15 +1,28 @@
-use cgmath::{Vector2, Vector3};
+use cgmath::{Vector2, Vector3, Vector4, Zero};
 use egui_sdl2_gl::{egui, painter::Painter, DpiScaling, EguiStateHandler, ShaderVersion};
 
 use crate::{
-    models::{Camera, Model},
-    shaders::create_program,
+    assets::AssetCache,
+    buffers::TimerQuery,
+    capture::Capture,
+    config::Config,
+    culling::CullingStats,
+    debug_views::DebugViews,
+    errors::EngineError,
+    instancing::InstancedModel,
+    light_clusters::LightClusters,
+    light_volumes::{cone_volume_mesh, sphere_volume_mesh, LIGHT_VOLUME_SEGMENTS},
+    models::{Camera, Mesh, Model},
+    post_processing::PostProcessStack,
+    scene_graph::SceneGraph,
+    shaders::ShaderRegistry,
+    tone_mapping::ToneMapping,
+    uniform_blocks::UniformBlocks,
+    vertex_layout::Vertex,
     winsdl::WinSdl,
 };
 /// A bunch of variables that most things need to some extent
 pub struct Globals {
-    /// basic sld2 things
-    pub win_sdl: WinSdl,
     /// egui context, gui context
     pub egui_ctx: egui::Context,
     /// egui painter, it basically puts the gui on screen
@@ -20,19 +33,61 @@ pub struct Globals {
     pub look_sensitivity: f32,
     pub mouse_look_sensitivity: f32,
     pub should_grab_mouse: bool,
+    /// light that reaches every surface, added in the final pass
+    pub ambient_light: Vector3<f32>,
     pub cam: Camera,
     pub models: Vec<Model>,
+    /// the meshes, textures and programs models share
+    pub assets: AssetCache,
+    /// drawn after models, every one of these is a single draw call however many instances it has
+    pub instanced_models: Vec<InstancedModel>,
     /// a model that is a quad that will cover the entire screen
     pub screen_model: Model,
     /// a model that will be rendered where lights are
     pub light_model: Model,
+    /// covers a point light, or a spot light too wide for the cone, in the lighting passes
+    pub sphere_volume_model: Model,
+    /// covers a spot light in the lighting passes
+    pub cone_volume_model: Model,
+    /// light point lights inside stenciled sphere volumes instead of over the whole screen
+    pub point_light_volumes: bool,
+    /// light spot lights inside stenciled cone volumes instead of over the whole screen
+    pub spot_light_volumes: bool,
+    /// reloads shader programs when their files change
+    pub shader_registry: ShaderRegistry,
+    /// camera and light data every program reads, updated once a frame
+    pub uniform_blocks: UniformBlocks,
+    /// point lights binned into clusters for the clustered lighting pass, None below gl 4.3
+    pub light_clusters: Option<LightClusters>,
+    /// light point lights with one clustered pass instead of one additive pass per light
+    pub clustered_lighting: bool,
+    /// tint the clustered pass by how many lights each cluster has
+    pub debug_clusters: bool,
+    /// gpu time of the point light passes, or the culling and clustered pass
+    pub point_lighting_timer: TimerQuery,
+    /// skip models outside of what the camera or a shadow casting light can see
+    pub frustum_culling: bool,
+    /// how many models each pass drew and culled, cleared every frame
+    pub culling_stats: CullingStats,
+    /// the parents of the models, lights and camera
+    pub scene_graph: SceneGraph,
+    /// the file the Save Scene and Load Scene buttons use
+    pub scene_path: String,
+    /// screenshots and frame recordings
+    pub capture: Capture,
+    /// shows the g-buffer, light buffer or shadow maps instead of the lit scene
+    pub debug_views: DebugViews,
+    /// exposure, tone mapping and gamma of the final image
+    pub tone_mapping: ToneMapping,
+    /// effects drawn over the scene before and after tone mapping
+    pub post_processing: PostProcessStack,
+    /// basic sld2 things. fields are dropped in order and everything above deletes its gl objects
+    /// when it is dropped, so the gl context has to go last
+    pub win_sdl: WinSdl,
 }
-///Window Width and height, should probably be variables to new, but I'm commenting rn
-const WIDTH: usize = 1920;
-const HEIGHT: usize = 1052;
 impl Globals {
-    pub fn new() -> Self {
-        let win_sdl: WinSdl = WinSdl::new(WIDTH, HEIGHT).unwrap();
+    pub fn new(config: &Config) -> Result<Self, EngineError> {
+        let win_sdl: WinSdl = WinSdl::new(config)?;
 
         let (egui_painter, egui_state) = egui_sdl2_gl::with_sdl2(
             &win_sdl.window,
@@ -45,59 +100,91 @@ impl Globals {
         let look_sensitivity: f32 = 1.0;
         let mouse_look_sensitivity: f32 = 1.0;
 
-        let horizontal_fov = 90.0f32.to_radians();
-        let fovy = 0.5 * HEIGHT as f32 / (0.5 * WIDTH as f32 / (0.5 * horizontal_fov).tan());
+        // borderless and fullscreen windows can end up a different size than asked for
+        let (width, height) = win_sdl.window.size();
+        let horizontal_fov = config.fov.to_radians();
+        let fovy = 0.5 * height as f32 / (0.5 * width as f32 / (0.5 * horizontal_fov).tan());
 
-        let cam = Camera::new(WIDTH as f32 / HEIGHT as f32, fovy, 0.1, 100.);
+        let cam = Camera::new(width as f32 / height as f32, fovy, 0.1, 100.);
 
         let models: Vec<Model> = Vec::new();
+        let mut assets = AssetCache::new();
 
-        let mut light_model = Model::from_obj_file("./models/Cube.obj".to_owned());
-        light_model.shader_program = create_program(
+        let mut light_model = Model::from_obj_file("./models/Cube.obj".to_owned(), &mut assets)?;
+        light_model.shader_program = assets.program(
             "./shaders/LightSource/shader.vert",
             "./shaders/LightSource/shader.frag",
-        )
-        .unwrap();
+        )?;
         light_model.position = Vector3::new(2.4, 2.0, 4.0);
         light_model.scale = Vector3::new(0.2, 0.2, 0.2);
 
-        let screen_model_vertices: Vec<(Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector2<f32>)> = vec![
-            (
-                Vector3::new(-1., -1., -1.),
-                Vector3::new(0., 0., 0.),
-                Vector3::new(0., 0., 0.),
-                Vector2::new(0., 0.),
-            ),
-            (
-                Vector3::new(-1., 1., -1.),
-                Vector3::new(0., 0., 0.),
-                Vector3::new(0., 0., 0.),
-                Vector2::new(0., 1.),
-            ),
-            (
-                Vector3::new(1., -1., -1.),
-                Vector3::new(0., 0., 0.),
-                Vector3::new(0., 0., 0.),
-                Vector2::new(1., 0.),
-            ),
-            (
-                Vector3::new(1., 1., -1.),
-                Vector3::new(0., 0., 0.),
-                Vector3::new(0., 0., 0.),
-                Vector2::new(1., 1.),
-            ),
+        let screen_model_vertices: Vec<Vertex> = vec![
+            Vertex {
+                position: Vector3::new(-1., -1., -1.),
+                color: Vector3::zero(),
+                normal: Vector3::zero(),
+                uv: Vector2::new(0., 0.),
+                tangent: Vector4::zero(),
+            },
+            Vertex {
+                position: Vector3::new(-1., 1., -1.),
+                color: Vector3::zero(),
+                normal: Vector3::zero(),
+                uv: Vector2::new(0., 1.),
+                tangent: Vector4::zero(),
+            },
+            Vertex {
+                position: Vector3::new(1., -1., -1.),
+                color: Vector3::zero(),
+                normal: Vector3::zero(),
+                uv: Vector2::new(1., 0.),
+                tangent: Vector4::zero(),
+            },
+            Vertex {
+                position: Vector3::new(1., 1., -1.),
+                color: Vector3::zero(),
+                normal: Vector3::zero(),
+                uv: Vector2::new(1., 1.),
+                tangent: Vector4::zero(),
+            },
         ];
         let screen_model_indices: Vec<u32> = vec![0, 1, 2, 1, 2, 3];
         let screen_model = Model::new(
-            &screen_model_vertices,
-            &screen_model_indices,
-            "./shaders/FinalPass/shader.vert",
-            "./shaders/FinalPass/shader.frag",
+            assets.add_mesh(
+                "screen quad".to_owned(),
+                Mesh::new(&screen_model_vertices, &screen_model_indices),
+            ),
+            assets.program(
+                "./shaders/FinalPass/shader.vert",
+                "./shaders/FinalPass/shader.frag",
+            )?,
             "Screen Model".to_owned(),
         );
-        screen_model.start();
 
-        return Self {
+        let light_volume_program = assets.program(
+            "./shaders/LightVolume/shader.vert",
+            "./shaders/LightVolume/shader.frag",
+        )?;
+        let (sphere_vertices, sphere_indices) = sphere_volume_mesh(LIGHT_VOLUME_SEGMENTS);
+        let sphere_volume_model = Model::new(
+            assets.add_mesh(
+                "sphere light volume".to_owned(),
+                Mesh::new(&sphere_vertices, &sphere_indices),
+            ),
+            light_volume_program.clone(),
+            "Sphere Light Volume".to_owned(),
+        );
+        let (cone_vertices, cone_indices) = cone_volume_mesh(LIGHT_VOLUME_SEGMENTS);
+        let cone_volume_model = Model::new(
+            assets.add_mesh(
+                "cone light volume".to_owned(),
+                Mesh::new(&cone_vertices, &cone_indices),
+            ),
+            light_volume_program,
+            "Cone Light Volume".to_owned(),
+        );
+
+        return Ok(Self {
             win_sdl,
             egui_ctx,
             egui_painter,
@@ -106,10 +193,34 @@ impl Globals {
             look_sensitivity,
             mouse_look_sensitivity,
             should_grab_mouse: true,
+            ambient_light: Vector3::new(0.1, 0.1, 0.1),
             cam,
             models,
+            assets,
+            instanced_models: Vec::new(),
             screen_model,
             light_model,
-        };
+            sphere_volume_model,
+            cone_volume_model,
+            point_light_volumes: true,
+            spot_light_volumes: true,
+            shader_registry: ShaderRegistry::new(),
+            uniform_blocks: UniformBlocks::new(),
+            light_clusters: None,
+            clustered_lighting: false,
+            debug_clusters: false,
+            point_lighting_timer: TimerQuery::new(),
+            frustum_culling: true,
+            culling_stats: CullingStats::default(),
+            scene_graph: SceneGraph::new(),
+            scene_path: config
+                .scene
+                .clone()
+                .unwrap_or_else(|| "./scenes/scene.json".to_owned()),
+            capture: Capture::new(),
+            debug_views: DebugViews::new(),
+            tone_mapping: ToneMapping::new(),
+            post_processing: PostProcessStack::with_default_passes(),
+        });
     }
 }
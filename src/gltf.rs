@@ -0,0 +1,505 @@
+use std::path::{Path, PathBuf};
+
+use cgmath::{
+    InnerSpace, Matrix3, Matrix4, Quaternion, SquareMatrix, Vector2, Vector3, Vector4, Zero,
+};
+use gltf::{buffer, mesh::Mode, Document, Semantic};
+
+use crate::{
+    errors::EngineError, models::generate_normals, tangents::generate_tangents,
+    vertex_layout::Vertex,
+};
+
+/// where a gltf image lives, either next to the .gltf file or inside one of its buffers
+#[derive(Debug, Clone, PartialEq)]
+pub enum GltfImage {
+    Path(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfMaterial {
+    pub base_color_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub emissive_factor: [f32; 3],
+    pub base_color_texture: Option<GltfImage>,
+    pub normal_texture: Option<GltfImage>,
+    pub metallic_roughness_texture: Option<GltfImage>,
+    pub occlusion_texture: Option<GltfImage>,
+    pub emissive_texture: Option<GltfImage>,
+}
+
+impl Default for GltfMaterial {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            emissive_factor: [0.0, 0.0, 0.0],
+            base_color_texture: None,
+            normal_texture: None,
+            metallic_roughness_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+        }
+    }
+}
+
+/// one primitive of one mesh, placed where its node ends up after walking the node hierarchy
+#[derive(Debug, Clone)]
+pub struct GltfPrimitive {
+    pub name: String,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub material: GltfMaterial,
+
+    pub position: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+/// loads every mesh primitive of the default scene of a .gltf or .glb file
+pub fn load_gltf(path: &Path) -> Result<Vec<GltfPrimitive>, EngineError> {
+    let error = |message: String| EngineError::Gltf {
+        path: path.to_path_buf(),
+        message,
+    };
+    let gltf::Gltf { document, blob } =
+        gltf::Gltf::open(path).map_err(|source| error(source.to_string()))?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let buffers = gltf::import_buffers(&document, Some(base_dir), blob)
+        .map_err(|source| error(source.to_string()))?;
+    let gltf = Gltf {
+        document: &document,
+        buffers,
+        base_dir,
+    };
+
+    let mut primitives: Vec<GltfPrimitive> = Vec::new();
+    for node in gltf.root_nodes() {
+        gltf.load_node(node, Matrix4::identity(), &mut primitives)
+            .map_err(error)?;
+    }
+    return Ok(primitives);
+}
+
+struct Gltf<'a> {
+    document: &'a Document,
+    buffers: Vec<buffer::Data>,
+    base_dir: &'a Path,
+}
+
+impl<'a> Gltf<'a> {
+    fn root_nodes(&self) -> Vec<gltf::Node<'a>> {
+        let scene = self
+            .document
+            .default_scene()
+            .or_else(|| self.document.scenes().next());
+        if let Some(scene) = scene {
+            return scene.nodes().collect();
+        }
+        // without scenes every node that isn't somebody's child is a root
+        let children: Vec<usize> = self
+            .document
+            .nodes()
+            .flat_map(|node| node.children())
+            .map(|child| child.index())
+            .collect();
+        return self
+            .document
+            .nodes()
+            .filter(|node| !children.contains(&node.index()))
+            .collect();
+    }
+
+    fn load_node(
+        &self,
+        node: gltf::Node,
+        parent_matrix: Matrix4<f32>,
+        primitives: &mut Vec<GltfPrimitive>,
+    ) -> Result<(), String> {
+        // gltf matrices are column major, same as cgmath
+        let world_matrix = parent_matrix * Matrix4::from(node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            let mesh_name = mesh
+                .name()
+                .or(node.name())
+                .map(|name| name.to_owned())
+                .unwrap_or(format!("mesh {}", mesh.index()));
+            let (position, rotation, scale) = decompose_matrix(world_matrix);
+            let primitive_count = mesh.primitives().len();
+            for (i, primitive) in mesh.primitives().enumerate() {
+                let (vertices, indices) = self
+                    .load_primitive_geometry(&primitive)
+                    .map_err(|message| format!("{} primitive {}: {}", mesh_name, i, message))?;
+                let material = match primitive.material().index() {
+                    Some(_) => self.load_material(&primitive.material())?,
+                    None => GltfMaterial::default(),
+                };
+                let name = if primitive_count > 1 {
+                    format!("{}.{}", mesh_name, i)
+                } else {
+                    mesh_name.clone()
+                };
+                primitives.push(GltfPrimitive {
+                    name,
+                    vertices,
+                    indices,
+                    material,
+                    position,
+                    rotation,
+                    scale,
+                });
+            }
+        }
+
+        for child in node.children() {
+            self.load_node(child, world_matrix, primitives)?;
+        }
+        Ok(())
+    }
+
+    fn load_primitive_geometry(
+        &self,
+        primitive: &gltf::Primitive,
+    ) -> Result<(Vec<Vertex>, Vec<u32>), String> {
+        if primitive.mode() != Mode::Triangles {
+            return Err(format!(
+                "primitive mode {:?} is not supported, only triangles",
+                primitive.mode()
+            ));
+        }
+        let vertex_count = primitive
+            .get(&Semantic::Positions)
+            .ok_or("primitive has no POSITION attribute")?
+            .count();
+        // the reader stops at the shortest attribute, so a short one would silently drop vertices
+        for semantic in [
+            Semantic::Normals,
+            Semantic::TexCoords(0),
+            Semantic::Colors(0),
+            Semantic::Tangents,
+        ] {
+            if let Some(accessor) = primitive.get(&semantic) {
+                if accessor.count() != vertex_count {
+                    return Err(format!(
+                        "{} has {} elements but POSITION has {}",
+                        semantic.to_string(),
+                        accessor.count(),
+                        vertex_count
+                    ));
+                }
+            }
+        }
+
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let positions: Vec<[f32; 3]> = reader
+            .read_positions()
+            .ok_or("POSITION has no data")?
+            .collect();
+        // colors and texcoords can also be normalized bytes or shorts, these read them as floats
+        let texcoords: Option<Vec<[f32; 2]>> =
+            reader.read_tex_coords(0).map(|t| t.into_f32().collect());
+        let colors: Option<Vec<[f32; 3]>> =
+            reader.read_colors(0).map(|c| c.into_rgb_f32().collect());
+        let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(|t| t.collect());
+
+        let mut indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if !indices.len().is_multiple_of(3) {
+            return Err("index count is not a multiple of 3".to_owned());
+        }
+        if let Some(index) = indices.iter().find(|index| **index as usize >= positions.len()) {
+            return Err(format!("index {} is out of range", index));
+        }
+        let normals: Vec<Vector3<f32>> = match reader.read_normals() {
+            Some(normals) => normals.map(Vector3::from).collect(),
+            None => {
+                let positions: Vec<Vector3<f32>> =
+                    positions.iter().map(|position| Vector3::from(*position)).collect();
+                generate_normals(&positions, &indices)
+            }
+        };
+
+        let mut vertices: Vec<Vertex> = Vec::with_capacity(positions.len());
+        for (vtx, position) in positions.iter().enumerate() {
+            let color = match &colors {
+                Some(colors) => Vector3::from(colors[vtx]),
+                None => Vector3::new(1., 1., 1.),
+            };
+            // gltf puts the uv origin in the top left, the rest of the engine uses the bottom left
+            let uv = match &texcoords {
+                Some(texcoords) => Vector2::new(texcoords[vtx][0], 1.0 - texcoords[vtx][1]),
+                None => Vector2::zero(),
+            };
+            // gltf tangents already follow MikkTSpace, so they're used as they are
+            let tangent = match &tangents {
+                Some(tangents) => Vector4::from(tangents[vtx]),
+                None => Vector4::zero(),
+            };
+            vertices.push(Vertex {
+                position: Vector3::from(*position),
+                color,
+                normal: normals[vtx],
+                uv,
+                tangent,
+            });
+        }
+        if tangents.is_none() {
+            generate_tangents(&mut vertices, &mut indices);
+        }
+        Ok((vertices, indices))
+    }
+
+    fn load_material(&self, material: &gltf::Material) -> Result<GltfMaterial, String> {
+        let pbr = material.pbr_metallic_roughness();
+        Ok(GltfMaterial {
+            base_color_factor: pbr.base_color_factor(),
+            metallic_factor: pbr.metallic_factor(),
+            roughness_factor: pbr.roughness_factor(),
+            emissive_factor: material.emissive_factor(),
+            base_color_texture: self
+                .load_texture(pbr.base_color_texture().map(|info| info.texture()))?,
+            normal_texture: self
+                .load_texture(material.normal_texture().map(|info| info.texture()))?,
+            metallic_roughness_texture: self
+                .load_texture(pbr.metallic_roughness_texture().map(|info| info.texture()))?,
+            occlusion_texture: self
+                .load_texture(material.occlusion_texture().map(|info| info.texture()))?,
+            emissive_texture: self
+                .load_texture(material.emissive_texture().map(|info| info.texture()))?,
+        })
+    }
+
+    fn load_texture(&self, texture: Option<gltf::Texture>) -> Result<Option<GltfImage>, String> {
+        let texture = match texture {
+            Some(texture) => texture,
+            None => return Ok(None),
+        };
+        match texture.source().source() {
+            gltf::image::Source::Uri { uri, .. } if uri.starts_with("data:") => {
+                let data = buffer::Data::from_source(buffer::Source::Uri(uri), Some(self.base_dir))
+                    .map_err(|error| format!("image {}: {}", texture.source().index(), error))?;
+                Ok(Some(GltfImage::Bytes(data.0)))
+            }
+            gltf::image::Source::Uri { uri, .. } => {
+                let uri = urlencoding::decode(uri)
+                    .map_err(|error| format!("image uri {}: {}", uri, error))?;
+                Ok(Some(GltfImage::Path(self.base_dir.join(&*uri))))
+            }
+            gltf::image::Source::View { view, .. } => {
+                let buffer = &self.buffers[view.buffer().index()];
+                let bytes = buffer
+                    .get(view.offset()..view.offset() + view.length())
+                    .ok_or(format!(
+                        "bufferView {} is out of range of its buffer",
+                        view.index()
+                    ))?;
+                Ok(Some(GltfImage::Bytes(bytes.to_vec())))
+            }
+        }
+    }
+}
+
+/// splits a translation * rotation * scale matrix back into its parts, shear is lost
+pub fn decompose_matrix(matrix: Matrix4<f32>) -> (Vector3<f32>, Quaternion<f32>, Vector3<f32>) {
+    let position = matrix.w.truncate();
+    let mut scale = Vector3::new(
+        matrix.x.truncate().magnitude(),
+        matrix.y.truncate().magnitude(),
+        matrix.z.truncate().magnitude(),
+    );
+    // a negative determinant means the matrix mirrors, put that in the x scale
+    if Matrix3::from_cols(
+        matrix.x.truncate(),
+        matrix.y.truncate(),
+        matrix.z.truncate(),
+    )
+    .determinant()
+        < 0.0
+    {
+        scale.x = -scale.x;
+    }
+    let rotation_matrix = Matrix3::from_cols(
+        matrix.x.truncate() / scale.x,
+        matrix.y.truncate() / scale.y,
+        matrix.z.truncate() / scale.z,
+    );
+    let rotation = Quaternion::from(rotation_matrix).normalize();
+    (position, rotation, scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{assert_relative_eq, Deg, Rotation3};
+    use std::fs;
+
+    /// q and -q are the same rotation, so compare how far apart they are instead of their components
+    fn assert_same_rotation(a: Quaternion<f32>, b: Quaternion<f32>) {
+        if (a.dot(b).abs() - 1.0).abs() > 1e-5 {
+            panic!("{:?} is not the same rotation as {:?}", a, b);
+        }
+    }
+
+    fn check_two_quads(primitives: &[GltfPrimitive]) {
+        assert_eq!(
+            primitives.len(),
+            3,
+            "the parent mesh has two primitives, the child one"
+        );
+        let names: Vec<&str> = primitives.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Quads.0", "Quads.1", "Child"]);
+
+        for primitive in primitives {
+            assert_eq!(primitive.vertices.len(), 4);
+            assert_eq!(primitive.indices, vec![0, 1, 2, 2, 1, 3]);
+        }
+        assert_eq!(primitives[0].vertices[3].position, Vector3::new(1., 1., 0.));
+        assert_eq!(primitives[0].vertices[0].normal, Vector3::new(0., 0., 1.));
+        // v is flipped to the bottom left origin
+        assert_eq!(primitives[0].vertices[0].uv, Vector2::new(0., 1.));
+        // there's no TANGENT attribute, so they're generated. the quad's v runs down the image
+        // as y goes up, so after the flip the bitangent points down and w is negative
+        assert_relative_eq!(primitives[0].vertices[0].tangent, Vector4::new(1., 0., 0., -1.));
+
+        assert_eq!(primitives[0].material.base_color_factor, [1.0, 0.5, 0.25, 1.0]);
+        assert_eq!(primitives[1].material.base_color_factor, [1.0, 1.0, 1.0, 1.0]);
+
+        assert_relative_eq!(primitives[0].position, Vector3::new(1., 2., 3.));
+        assert_relative_eq!(primitives[0].scale, Vector3::new(1., 1., 1.));
+
+        // the child is translated, rotated and scaled inside of its parent
+        let child_rotation = Quaternion::from_angle_y(Deg(90.0f32));
+        assert_relative_eq!(
+            primitives[2].position,
+            Vector3::new(1., 2., 3.) + Vector3::new(0., 0., -2.),
+            epsilon = 1e-5
+        );
+        assert_same_rotation(primitives[2].rotation, child_rotation);
+        assert_relative_eq!(primitives[2].scale, Vector3::new(2., 2., 2.), epsilon = 1e-5);
+    }
+
+    #[test]
+    fn test_load_gltf_with_external_buffer() {
+        let primitives = load_gltf(Path::new("./models/TwoQuads.gltf")).unwrap();
+        check_two_quads(&primitives);
+    }
+
+    #[test]
+    fn test_load_glb() {
+        let primitives = load_gltf(Path::new("./models/TwoQuads.glb")).unwrap();
+        check_two_quads(&primitives);
+    }
+
+    #[test]
+    fn test_decompose_matrix() {
+        let rotation = Quaternion::from_angle_z(Deg(30.0f32));
+        let matrix = Matrix4::from_translation(Vector3::new(4., 5., 6.))
+            * Matrix4::from(rotation)
+            * Matrix4::from_nonuniform_scale(1., 2., 3.);
+        let (position, decomposed_rotation, scale) = decompose_matrix(matrix);
+        assert_relative_eq!(position, Vector3::new(4., 5., 6.));
+        assert_same_rotation(decomposed_rotation, rotation);
+        assert_relative_eq!(scale, Vector3::new(1., 2., 3.), epsilon = 1e-5);
+    }
+
+    /// writes a triangle whose colors are normalized bytes and whose uvs are normalized shorts
+    /// to a temporary folder, colors_count is how many colors the COLOR_0 accessor says it has.
+    /// it has no NORMAL attribute
+    fn write_normalized_triangle(name: &str, colors_count: usize) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gltf_test_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut bytes: Vec<u8> = Vec::new();
+        for position in [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            for component in position {
+                bytes.extend(component.to_le_bytes());
+            }
+        }
+        bytes.extend([255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 51, 255]);
+        for uv in [[0u16, 0], [65535, 0], [0, 65535]] {
+            for component in uv {
+                bytes.extend(component.to_le_bytes());
+            }
+        }
+        // the uri is percent encoded, so this also checks that more than %20 is decoded
+        fs::write(dir.join("triangle data+1.bin"), &bytes).unwrap();
+        let gltf = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scenes": [{{ "nodes": [0] }}],
+                "nodes": [{{ "mesh": 0 }}],
+                "meshes": [{{ "name": "Triangle", "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "COLOR_0": 1, "TEXCOORD_0": 2 }}
+                }}] }}],
+                "buffers": [{{ "uri": "triangle%20data%2B1.bin", "byteLength": 60 }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 36, "byteLength": 12 }},
+                    {{ "buffer": 0, "byteOffset": 48, "byteLength": 12 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                       "min": [0, 0, 0], "max": [1, 1, 0] }},
+                    {{ "bufferView": 1, "componentType": 5121, "normalized": true,
+                       "count": {}, "type": "VEC4" }},
+                    {{ "bufferView": 2, "componentType": 5123, "normalized": true, "count": 3,
+                       "type": "VEC2" }}
+                ]
+            }}"#,
+            colors_count
+        );
+        let path = dir.join("triangle.gltf");
+        fs::write(&path, gltf).unwrap();
+        return path;
+    }
+
+    #[test]
+    fn test_normalized_colors_and_texcoords() {
+        let path = write_normalized_triangle("normalized", 3);
+        let primitives = load_gltf(&path);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        let primitives = primitives.unwrap();
+        assert_eq!(primitives.len(), 1);
+        let vertices = &primitives[0].vertices;
+        assert_eq!(vertices[0].color, Vector3::new(1., 0., 0.));
+        assert_eq!(vertices[1].color, Vector3::new(0., 1., 0.));
+        assert_relative_eq!(vertices[2].color, Vector3::new(0., 0., 0.2));
+        // v is flipped to the bottom left origin
+        assert_eq!(vertices[0].uv, Vector2::new(0., 1.));
+        assert_eq!(vertices[1].uv, Vector2::new(1., 1.));
+        assert_eq!(vertices[2].uv, Vector2::new(0., 0.));
+    }
+
+    #[test]
+    fn test_missing_normals_are_generated() {
+        let path = write_normalized_triangle("normals", 3);
+        let primitives = load_gltf(&path);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        // the triangle is counter clockwise in the xy plane, so it faces +z
+        for vertex in &primitives.unwrap()[0].vertices {
+            assert_relative_eq!(vertex.normal, Vector3::new(0., 0., 1.));
+        }
+    }
+
+    #[test]
+    fn test_attribute_count_mismatch_is_an_error() {
+        let path = write_normalized_triangle("mismatch", 2);
+        let result = load_gltf(&path);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        match result {
+            Err(EngineError::Gltf { message, .. }) => assert_eq!(
+                message,
+                "Triangle primitive 0: COLOR_0 has 2 elements but POSITION has 3"
+            ),
+            other => panic!(
+                "expected a count mismatch error, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+    }
+}
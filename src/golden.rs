@@ -0,0 +1,178 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use image::RgbaImage;
+
+/// set to write the golden images again instead of comparing against them
+pub const UPDATE_GOLDEN_IMAGES: &str = "UPDATE_GOLDEN_IMAGES";
+
+/// where the renders that didn't match are written, to look at next to the golden image
+const FAILED_IMAGES_FOLDER: &str = "./target/golden_failures";
+
+/// how far an image is from the one it should match
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageDifference {
+    /// pixels with any channel further off than the tolerance
+    pub mismatched_pixels: usize,
+    /// the largest difference of any channel of any pixel
+    pub max_difference: u8,
+}
+
+/// compares every channel of every pixel, differences up to tolerance are allowed
+pub fn compare_images(
+    actual: &RgbaImage,
+    expected: &RgbaImage,
+    tolerance: u8,
+) -> Result<ImageDifference, String> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(format!(
+            "the image is {}x{} but the golden image is {}x{}",
+            actual.width(),
+            actual.height(),
+            expected.width(),
+            expected.height()
+        ));
+    }
+    let mut difference = ImageDifference {
+        mismatched_pixels: 0,
+        max_difference: 0,
+    };
+    for (actual, expected) in actual.pixels().zip(expected.pixels()) {
+        let pixel_difference = actual
+            .0
+            .iter()
+            .zip(expected.0.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0);
+        if pixel_difference > tolerance {
+            difference.mismatched_pixels += 1;
+        }
+        difference.max_difference = difference.max_difference.max(pixel_difference);
+    }
+    return Ok(difference);
+}
+
+/// compares image with the png at golden_path. a fraction of the pixels, allowed_mismatches, can be
+/// further off than tolerance, so different drivers rasterizing edges differently don't fail the
+/// check. a render that doesn't match is written to target/golden_failures
+pub fn check_golden_image(
+    image: &RgbaImage,
+    golden_path: &Path,
+    tolerance: u8,
+    allowed_mismatches: f32,
+) -> Result<(), String> {
+    if env::var_os(UPDATE_GOLDEN_IMAGES).is_some() {
+        if let Some(folder) = golden_path.parent() {
+            fs::create_dir_all(folder).map_err(|error| error.to_string())?;
+        }
+        return image.save(golden_path).map_err(|error| error.to_string());
+    }
+    return compare_with_golden_image(image, golden_path, tolerance, allowed_mismatches);
+}
+
+fn compare_with_golden_image(
+    image: &RgbaImage,
+    golden_path: &Path,
+    tolerance: u8,
+    allowed_mismatches: f32,
+) -> Result<(), String> {
+    let golden = match image::open(golden_path) {
+        Ok(golden) => golden.to_rgba8(),
+        Err(error) => {
+            return Err(format!(
+                "couldn't open {} ({}), run with {}=1 to write it",
+                golden_path.display(),
+                error,
+                UPDATE_GOLDEN_IMAGES
+            ))
+        }
+    };
+    let difference = compare_images(image, &golden, tolerance)?;
+    let pixel_count = (image.width() * image.height()).max(1) as f32;
+    if difference.mismatched_pixels as f32 / pixel_count <= allowed_mismatches {
+        return Ok(());
+    }
+    let failed_path = save_failed_image(image, golden_path);
+    return Err(format!(
+        "{} pixels differ from {} by more than {}, up to {}. the render is in {}",
+        difference.mismatched_pixels,
+        golden_path.display(),
+        tolerance,
+        difference.max_difference,
+        failed_path
+    ));
+}
+
+fn save_failed_image(image: &RgbaImage, golden_path: &Path) -> String {
+    let path =
+        PathBuf::from(FAILED_IMAGES_FOLDER).join(golden_path.file_name().unwrap_or_default());
+    let saved = fs::create_dir_all(FAILED_IMAGES_FOLDER)
+        .map_err(|error| error.to_string())
+        .and_then(|_| image.save(&path).map_err(|error| error.to_string()));
+    match saved {
+        Ok(()) => path.display().to_string(),
+        Err(error) => format!("nowhere, it couldn't be written: {}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    fn gradient() -> RgbaImage {
+        RgbaImage::from_fn(16, 8, |x, y| Rgba([x as u8 * 16, y as u8 * 32, 128, 255]))
+    }
+
+    #[test]
+    fn small_differences_are_within_tolerance() {
+        let expected = gradient();
+        let mut actual = gradient();
+        actual.put_pixel(3, 3, Rgba([3 * 16 + 2, 3 * 32, 126, 255]));
+        let difference = compare_images(&actual, &expected, 2).unwrap();
+        assert_eq!(
+            difference,
+            ImageDifference {
+                mismatched_pixels: 0,
+                max_difference: 2
+            }
+        );
+
+        actual.put_pixel(5, 1, Rgba([0, 0, 0, 255]));
+        actual.put_pixel(6, 1, Rgba([6 * 16, 32, 128, 0]));
+        let difference = compare_images(&actual, &expected, 2).unwrap();
+        assert_eq!(difference.mismatched_pixels, 2);
+        assert_eq!(difference.max_difference, 255);
+    }
+
+    #[test]
+    fn different_sizes_dont_match() {
+        let small = RgbaImage::new(8, 8);
+        assert!(compare_images(&small, &gradient(), 255).is_err());
+    }
+
+    #[test]
+    fn golden_images_allow_a_few_mismatched_pixels() {
+        let folder = env::temp_dir().join(format!("golden_test_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let golden_path = folder.join("gradient.png");
+        gradient().save(&golden_path).unwrap();
+
+        let mut image = gradient();
+        image.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+        // 1 of 128 pixels
+        assert!(compare_with_golden_image(&image, &golden_path, 4, 0.01).is_ok());
+        image.put_pixel(1, 0, Rgba([255, 255, 255, 255]));
+        image.put_pixel(2, 0, Rgba([255, 255, 255, 255]));
+        let error = compare_with_golden_image(&image, &golden_path, 4, 0.01).unwrap_err();
+        assert!(error.starts_with("3 pixels differ"), "{}", error);
+
+        let missing = compare_with_golden_image(&image, &folder.join("missing.png"), 4, 0.01);
+        assert!(missing.unwrap_err().contains(UPDATE_GOLDEN_IMAGES));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
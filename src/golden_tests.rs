@@ -0,0 +1,252 @@
+//! renders scenes offscreen and compares them with the pngs in tests/golden. these need a gl context,
+//! so they are ignored by default. without a gpu mesa's llvmpipe works:
+//! `LIBGL_ALWAYS_SOFTWARE=1 cargo test -- --ignored`
+//! the pngs were rendered with llvmpipe from mesa 22.3, other drivers can be a few pixels off
+//! after a change that is meant to change the output, run them with UPDATE_GOLDEN_IMAGES=1 to write
+//! the pngs again
+
+use std::{f32::consts::PI, path::PathBuf};
+
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector2, Vector3, Zero};
+use image::RgbaImage;
+
+use crate::{
+    add_default_scene, add_test_point_lights,
+    config::Config,
+    globals::Globals,
+    golden::{check_golden_image, compare_images},
+    models::{DirectionalLight, PointLight, SpotLight},
+    render_offscreen,
+    scene_graph::SceneGraph,
+    Renderer, DEFAULT_POINT_LIGHT_POSITION,
+};
+
+const SIZE: (u32, u32) = (320, 180);
+/// how far a channel can be off before the pixel counts as different
+const TOLERANCE: u8 = 8;
+/// the fraction of pixels that can be different, for edges drivers rasterize differently
+const ALLOWED_MISMATCHES: f32 = 0.002;
+/// enough frames for the shadow maps and light clusters to be filled in
+const FRAMES: u32 = 3;
+
+struct Lights {
+    point_lights: Vec<PointLight>,
+    spot_lights: Vec<SpotLight>,
+    directional_lights: Vec<DirectionalLight>,
+}
+
+/// puts the default scene in globals with the camera looking at the cube from the same place every time
+fn reset_scene(globals: &mut Globals) -> Lights {
+    let mut lights = Lights {
+        point_lights: Vec::new(),
+        spot_lights: Vec::new(),
+        directional_lights: Vec::new(),
+    };
+    globals.models.clear();
+    globals.instanced_models.clear();
+    globals.scene_graph = SceneGraph::new();
+    add_default_scene(
+        globals,
+        512,
+        &mut lights.point_lights,
+        &mut lights.spot_lights,
+        &mut lights.directional_lights,
+    )
+    .unwrap();
+    globals.cam.position = Vector3::new(3.0, 2.0, 5.0);
+    globals.cam.camera_rotation = Vector2::new(0.54, -0.33);
+    globals.point_light_volumes = true;
+    globals.spot_light_volumes = true;
+    globals.clustered_lighting = false;
+    return lights;
+}
+
+/// spot lights in a ring around the cube looking at it, from narrow to wide cones
+fn add_test_spot_lights(spot_lights: &mut Vec<SpotLight>, count: usize) {
+    for i in 0..count {
+        let fraction = i as f32 / count as f32;
+        let angle = fraction * 2.0 * PI;
+        let horizontal_fov = (30.0 + fraction * 110.0).to_radians();
+        let mut spot_light = SpotLight::new((256, 256), horizontal_fov);
+        spot_light.position = Vector3::new(angle.cos() * 3.0, 1.5, angle.sin() * 3.0);
+        spot_light.direction = (Vector3::zero() - spot_light.position).normalize();
+        spot_light.info.color = Vector3::new(fraction, 1.0 - fraction, 0.5);
+        spot_light.info.radius = 6.0;
+        spot_lights.push(spot_light);
+    }
+}
+
+fn render(
+    name: &str,
+    globals: &mut Globals,
+    renderer: &mut Renderer,
+    lights: &mut Lights,
+) -> Result<RgbaImage, String> {
+    return render_offscreen(
+        globals,
+        renderer,
+        &mut lights.point_lights,
+        &mut lights.spot_lights,
+        &mut lights.directional_lights,
+        FRAMES,
+    )
+    .map_err(|error| format!("{}: {}", name, error));
+}
+
+/// compares a render with tests/golden/{name}.png
+fn check_golden(name: &str, image: &RgbaImage) -> Result<(), String> {
+    let path = PathBuf::from("./tests/golden").join(format!("{}.png", name));
+    return check_golden_image(image, &path, TOLERANCE, ALLOWED_MISMATCHES)
+        .map_err(|error| format!("{}: {}", name, error));
+}
+
+/// the pixel a world position lands on in a render from the camera
+fn pixel_at(globals: &mut Globals, position: Vector3<f32>) -> (u32, u32) {
+    let projection: Matrix4<f32> = globals.cam.projection_matrix.into();
+    let view = globals.cam.view_transform().invert().unwrap();
+    let clip = projection * view * position.extend(1.0);
+    let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+    // the renders are flipped so the first row is the top of the screen
+    return (
+        ((x * 0.5 + 0.5) * SIZE.0 as f32) as u32,
+        ((0.5 - y * 0.5) * SIZE.1 as f32) as u32,
+    );
+}
+
+fn brightness(image: &RgbaImage, pixel: (u32, u32)) -> f32 {
+    let [r, g, b, _] = image.get_pixel(pixel.0, pixel.1).0;
+    return (r as f32 + g as f32 + b as f32) / (3.0 * 255.0);
+}
+
+/// the floor straight through the cube from the default scene's point light should be darker with the
+/// light's shadows on, and the floor the light reaches past the cube shouldn't change
+fn check_point_light_shadow(globals: &mut Globals, renderer: &mut Renderer) -> Result<(), String> {
+    let floor_through = |target: Vector3<f32>| {
+        let direction = target - DEFAULT_POINT_LIGHT_POSITION;
+        let distance = (-1.0 - DEFAULT_POINT_LIGHT_POSITION.y) / direction.y;
+        DEFAULT_POINT_LIGHT_POSITION + direction * distance
+    };
+    let mut lights = reset_scene(globals);
+    let shadowed = pixel_at(globals, floor_through(Vector3::zero()));
+    // in front of the cube, nothing is between it and the light
+    let lit = pixel_at(globals, Vector3::new(-1.5, -1.0, 2.0));
+    let with_shadows = render("point light shadow", globals, renderer, &mut lights)?;
+    lights.point_lights[0].cast_shadows = false;
+    let without_shadows = render("point light shadow", globals, renderer, &mut lights)?;
+
+    let darkening = brightness(&without_shadows, shadowed) - brightness(&with_shadows, shadowed);
+    if darkening < 0.05 {
+        return Err(format!(
+            "point light shadow: the floor at {:?} is only {} darker with shadows",
+            shadowed, darkening
+        ));
+    }
+    let change = (brightness(&without_shadows, lit) - brightness(&with_shadows, lit)).abs();
+    if change > 0.01 {
+        return Err(format!(
+            "point light shadow: the lit floor at {:?} changed by {} with shadows",
+            lit, change
+        ));
+    }
+    return Ok(());
+}
+
+/// compares two renders of the same scene drawn different ways
+fn check_same(name: &str, image: &RgbaImage, reference: &RgbaImage) -> Result<(), String> {
+    let difference = compare_images(image, reference, TOLERANCE)?;
+    let pixel_count = (image.width() * image.height()) as f32;
+    if difference.mismatched_pixels as f32 / pixel_count <= ALLOWED_MISMATCHES {
+        return Ok(());
+    }
+    return Err(format!(
+        "{}: {} pixels differ by more than {}, up to {}",
+        name, difference.mismatched_pixels, TOLERANCE, difference.max_difference
+    ));
+}
+
+#[test]
+#[ignore = "needs a gl context"]
+fn golden_images() {
+    // sdl can only be started once a process, so every scene is rendered by this one test
+    let config = Config {
+        width: SIZE.0,
+        height: SIZE.1,
+        headless: true,
+        ..Default::default()
+    };
+    let mut globals = Globals::new(&config).unwrap();
+    let mut renderer = Renderer::new(&mut globals, SIZE).unwrap();
+    let mut failures: Vec<String> = Vec::new();
+
+    // draw_scene, the directional and spot lighting passes with their shadows and draw_final_pass
+    let mut lights = reset_scene(&mut globals);
+    failures.extend(
+        render("default_scene", &mut globals, &mut renderer, &mut lights)
+            .and_then(|image| check_golden("default_scene", &image))
+            .err(),
+    );
+
+    // the default scene's point light shadow lands on the other side of the cube
+    failures.extend(check_point_light_shadow(&mut globals, &mut renderer).err());
+
+    // the point lighting pass over the whole screen, with more lights than fit in one uniform block.
+    // light volumes and the clustered pass should light the scene the same way
+    let mut lights = reset_scene(&mut globals);
+    add_test_point_lights(&mut lights.point_lights, 100);
+    globals.point_light_volumes = false;
+    match render("point_lights", &mut globals, &mut renderer, &mut lights) {
+        Ok(full_screen) => {
+            failures.extend(check_golden("point_lights", &full_screen).err());
+            globals.point_light_volumes = true;
+            failures.extend(
+                render(
+                    "point light volumes",
+                    &mut globals,
+                    &mut renderer,
+                    &mut lights,
+                )
+                .and_then(|image| check_same("point light volumes", &image, &full_screen))
+                .err(),
+            );
+            if globals.win_sdl.gl_version >= (4, 3) {
+                globals.clustered_lighting = true;
+                failures.extend(
+                    render(
+                        "clustered lighting",
+                        &mut globals,
+                        &mut renderer,
+                        &mut lights,
+                    )
+                    .and_then(|image| check_same("clustered lighting", &image, &full_screen))
+                    .err(),
+                );
+            }
+        }
+        Err(error) => failures.push(error),
+    }
+
+    // the spot lighting pass over the whole screen and inside cone volumes, with more lights than
+    // fit in one uniform block
+    let mut lights = reset_scene(&mut globals);
+    add_test_spot_lights(&mut lights.spot_lights, 20);
+    globals.spot_light_volumes = false;
+    match render("spot_lights", &mut globals, &mut renderer, &mut lights) {
+        Ok(full_screen) => {
+            failures.extend(check_golden("spot_lights", &full_screen).err());
+            globals.spot_light_volumes = true;
+            failures.extend(
+                render(
+                    "spot light volumes",
+                    &mut globals,
+                    &mut renderer,
+                    &mut lights,
+                )
+                .and_then(|image| check_same("spot light volumes", &image, &full_screen))
+                .err(),
+            );
+        }
+        Err(error) => failures.push(error),
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
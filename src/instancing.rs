@@ -0,0 +1,115 @@
+use cgmath::Matrix4;
+use egui_sdl2_gl::egui::{self, Widget};
+
+use crate::{
+    buffers::{VertexArrayBuffer, VertexBuffer},
+    culling::{Aabb, Bounds},
+    models::Model,
+    scene_graph::SceneObject,
+    shaders::Program,
+    vertex_layout::{InstanceData, Vertex, VertexFormat},
+};
+
+/// one mesh drawn many times in a single draw call. every instance has its own transform that goes
+/// on top of the model's, so moving the model moves all of them
+pub struct InstancedModel {
+    pub model: Model,
+    /// call update_instances after changing these
+    pub instances: Vec<InstanceData>,
+    instance_buffer: VertexBuffer<InstanceData>,
+    /// the mesh is shared with other models, so the instance attributes go in a vertex array of its own
+    vao: VertexArrayBuffer,
+    /// around every instance, before the model's transform
+    bounds: Bounds,
+}
+
+impl InstancedModel {
+    pub fn new(model: Model, instances: Vec<InstanceData>) -> Self {
+        let mut instanced_model = Self {
+            model,
+            instances,
+            instance_buffer: VertexBuffer::new(),
+            vao: VertexArrayBuffer::new(),
+            bounds: Bounds::from_points(&[]),
+        };
+        let mesh = &instanced_model.model.mesh;
+        instanced_model.vao.bind();
+        mesh.vbo.bind();
+        Vertex::layout().apply();
+        mesh.ibo.bind();
+        instanced_model
+            .instance_buffer
+            .set(&instanced_model.instances);
+        InstanceData::layout().apply();
+        instanced_model.vao.unbind();
+        instanced_model.update_bounds();
+        return instanced_model;
+    }
+
+    pub fn update_instances(&mut self) {
+        self.instance_buffer.set(&self.instances);
+        self.update_bounds();
+    }
+
+    fn update_bounds(&mut self) {
+        let mesh_bounds = self.model.mesh.bounds;
+        let aabb = self
+            .instances
+            .iter()
+            .map(|instance| mesh_bounds.aabb.transformed(&instance.model))
+            .fold(Aabb::from_points([]), |bounds, aabb| bounds.union(&aabb));
+        self.bounds = Bounds::from_aabb(aabb);
+    }
+
+    /// the bounds of all of the instances moved to where the model is
+    pub fn world_bounds(&self) -> Bounds {
+        self.bounds.transformed(&self.model.model_matrix())
+    }
+
+    /// the geometry pass, the camera comes from the Camera uniform block
+    pub fn render(&mut self) {
+        self.model.start_render();
+        self.vao.bind();
+        let shader_program = &self.model.shader_program;
+        shader_program.set_matrix4_float("model", self.model.model_matrix());
+        self.model
+            .draw_instanced(shader_program, true, Some(self.instances.len()));
+    }
+
+    /// for shadow maps, the shader program has to declare the instanced uniform and the instance matrix
+    pub fn render_custom_shader_program(
+        &mut self,
+        view_matrix: Matrix4<f32>,
+        projection_matrix: Matrix4<f32>,
+        shader_program: &Program,
+    ) {
+        self.model
+            .start_render_custom_shader_program(shader_program);
+        self.vao.bind();
+        shader_program.set_matrix4_float("model", self.model.model_matrix());
+        shader_program.set_matrix4_float("view", view_matrix);
+        shader_program.set_matrix4_float("projection", projection_matrix);
+        self.model
+            .draw_instanced(shader_program, false, Some(self.instances.len()));
+    }
+}
+
+impl SceneObject for InstancedModel {
+    fn local_matrix(&self) -> Matrix4<f32> {
+        self.model.local_matrix()
+    }
+
+    fn set_parent_matrix(&mut self, parent_matrix: Matrix4<f32>) {
+        self.model.set_parent_matrix(parent_matrix);
+    }
+}
+
+impl Widget for &mut InstancedModel {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        ui.group(|ui| {
+            ui.label(format!("{} instances", self.instances.len()));
+            ui.add(&mut self.model);
+        })
+        .response
+    }
+}
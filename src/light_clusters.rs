@@ -0,0 +1,88 @@
+use std::mem::size_of;
+
+use cgmath::{Matrix4, SquareMatrix, Vector3};
+
+use crate::{buffers::ShaderStorageBuffer, shaders::Program, uniform_blocks::PointLightData};
+
+//// storage buffer binding points, these have to match the bindings in the culling and clustered lighting shaders
+pub const CLUSTER_LIGHTS_BINDING: u32 = 0;
+pub const CLUSTER_LIGHT_COUNTS_BINDING: u32 = 1;
+pub const CLUSTER_LIGHT_INDICES_BINDING: u32 = 2;
+
+/// how many tiles the screen is split into on x and y, and how many depth slices each tile has
+pub const CLUSTER_GRID: (u32, u32, u32) = (16, 9, 24);
+pub const CLUSTER_COUNT: u32 = CLUSTER_GRID.0 * CLUSTER_GRID.1 * CLUSTER_GRID.2;
+/// lights past this in one cluster are dropped, every cluster has this many slots in the index buffer
+pub const MAX_LIGHTS_PER_CLUSTER: u32 = 128;
+/// has to match local_size_x in the culling shader
+const CULLING_WORKGROUP_SIZE: u32 = 64;
+
+/// point lights binned into screen space tiles and exponential depth slices, so the clustered lighting
+/// pass only loops over the lights that can reach each fragment
+pub struct LightClusters {
+    /// every point light, laid out like the PointLights block since std430 matches std140 for it
+    pub lights: ShaderStorageBuffer,
+    /// how many lights ended up in each cluster
+    pub light_counts: ShaderStorageBuffer,
+    /// MAX_LIGHTS_PER_CLUSTER indices into lights for every cluster
+    pub light_indices: ShaderStorageBuffer,
+    light_count: u32,
+}
+
+impl LightClusters {
+    pub fn new() -> Self {
+        let lights = ShaderStorageBuffer::new();
+        lights.set_slice::<PointLightData>(&[]);
+        let light_counts = ShaderStorageBuffer::new();
+        light_counts.allocate(size_of::<u32>() * CLUSTER_COUNT as usize);
+        let light_indices = ShaderStorageBuffer::new();
+        light_indices
+            .allocate(size_of::<u32>() * CLUSTER_COUNT as usize * MAX_LIGHTS_PER_CLUSTER as usize);
+        Self {
+            lights,
+            light_counts,
+            light_indices,
+            light_count: 0,
+        }
+    }
+
+    /// uploads every point light, unlike the PointLights block this has no limit
+    pub fn update_lights(&mut self, point_lights: &[PointLightData]) {
+        self.lights.set_slice(point_lights);
+        self.light_count = point_lights.len() as u32;
+    }
+
+    /// binds the buffers to the binding points the shaders read them from
+    pub fn bind(&self) {
+        self.lights.bind_base(CLUSTER_LIGHTS_BINDING);
+        self.light_counts.bind_base(CLUSTER_LIGHT_COUNTS_BINDING);
+        self.light_indices.bind_base(CLUSTER_LIGHT_INDICES_BINDING);
+    }
+
+    /// sets the uniforms that describe the grid, both the culling and the lighting shader need them
+    pub fn set_grid_uniforms(&self, shader_program: &Program, near: f32, far: f32) {
+        shader_program.set_uvec3(
+            "clusterGrid",
+            Vector3::new(CLUSTER_GRID.0, CLUSTER_GRID.1, CLUSTER_GRID.2),
+        );
+        shader_program.set_uint("maxLightsPerCluster", MAX_LIGHTS_PER_CLUSTER);
+        shader_program.set_float("zNear", near);
+        shader_program.set_float("zFar", far);
+    }
+
+    /// runs the culling compute shader, one invocation per cluster, the camera comes from the Camera block
+    pub fn cull(&self, culling_program: &Program, projection: Matrix4<f32>, near: f32, far: f32) {
+        culling_program.set();
+        self.bind();
+        self.set_grid_uniforms(culling_program, near, far);
+        culling_program.set_uint("lightCount", self.light_count);
+        culling_program.set_matrix4_float(
+            "inverseProjection",
+            projection.invert().unwrap_or(Matrix4::identity()),
+        );
+        unsafe {
+            gl::DispatchCompute(CLUSTER_COUNT.div_ceil(CULLING_WORKGROUP_SIZE), 1, 1);
+            gl::MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT);
+        }
+    }
+}
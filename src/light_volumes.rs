@@ -0,0 +1,163 @@
+use std::f32::consts::PI;
+
+use cgmath::{Matrix4, Quaternion, Vector2, Vector3, Vector4, Zero};
+
+use crate::vertex_layout::Vertex;
+
+/// how many sides the volumes have around their axis, more fits tighter but costs more triangles
+pub const LIGHT_VOLUME_SEGMENTS: u32 = 16;
+/// a cone wide enough for a spot light past this half angle covers more than the sphere, so it uses the sphere
+pub const MAX_CONE_VOLUME_HALF_ANGLE: f32 = 80.0 * PI / 180.0;
+
+/// only the position is used
+fn volume_vertex(position: Vector3<f32>) -> Vertex {
+    Vertex {
+        position,
+        color: Vector3::zero(),
+        normal: Vector3::zero(),
+        uv: Vector2::zero(),
+        tangent: Vector4::zero(),
+    }
+}
+
+/// a uv sphere around the origin that fully contains the unit sphere, the flat faces would cut
+/// into it if the vertices were on it, so they are pushed out. counter clockwise seen from outside
+pub fn sphere_volume_mesh(segments: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let rings = segments / 2;
+    let scale = 1.0 / (2.0 * PI / segments as f32).cos();
+    let mut vertices: Vec<Vertex> = Vec::new();
+    for ring in 0..=rings {
+        let phi = PI * ring as f32 / rings as f32;
+        for segment in 0..segments {
+            let theta = 2.0 * PI * segment as f32 / segments as f32;
+            vertices.push(volume_vertex(
+                Vector3::new(phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin()) * scale,
+            ));
+        }
+    }
+    let mut indices: Vec<u32> = Vec::new();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let a = ring * segments + segment;
+            let b = ring * segments + (segment + 1) % segments;
+            let c = a + segments;
+            let d = b + segments;
+            indices.extend([a, b, c, b, d, c]);
+        }
+    }
+    return (vertices, indices);
+}
+
+/// a cone with its tip at the origin pointing down -z, 1 long with a base that contains a circle of
+/// radius 1, so it contains every point within 1 of the tip and 45 degrees of the axis.
+/// counter clockwise seen from outside
+pub fn cone_volume_mesh(segments: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let radius = 1.0 / (PI / segments as f32).cos();
+    let mut vertices: Vec<Vertex> = vec![
+        volume_vertex(Vector3::zero()),
+        volume_vertex(Vector3::new(0.0, 0.0, -1.0)),
+    ];
+    for segment in 0..segments {
+        let theta = 2.0 * PI * segment as f32 / segments as f32;
+        vertices.push(volume_vertex(Vector3::new(
+            radius * theta.cos(),
+            radius * theta.sin(),
+            -1.0,
+        )));
+    }
+    let mut indices: Vec<u32> = Vec::new();
+    for segment in 0..segments {
+        let current = 2 + segment;
+        let next = 2 + (segment + 1) % segments;
+        // side then base
+        indices.extend([0, current, next, 1, next, current]);
+    }
+    return (vertices, indices);
+}
+
+/// moves and scales the sphere volume over a point light
+pub fn sphere_volume_matrix(position: Vector3<f32>, radius: f32) -> Matrix4<f32> {
+    Matrix4::from_translation(position) * Matrix4::from_scale(radius)
+}
+
+/// turns the cone volume into a cone of half angle half_angle and length radius pointing along direction
+pub fn cone_volume_matrix(
+    position: Vector3<f32>,
+    direction: Vector3<f32>,
+    radius: f32,
+    half_angle: f32,
+) -> Matrix4<f32> {
+    let width = radius * half_angle.tan();
+    let rotation: Matrix4<f32> =
+        Quaternion::from_arc(-Vector3::unit_z(), direction, Some(Vector3::unit_y())).into();
+    Matrix4::from_translation(position)
+        * rotation
+        * Matrix4::from_nonuniform_scale(width, width, radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Transform};
+
+    use super::*;
+
+    /// true if point is behind every face, which also means the faces wind outwards
+    fn contains(mesh: &(Vec<Vertex>, Vec<u32>), point: Vector3<f32>) -> bool {
+        let (vertices, indices) = mesh;
+        indices.chunks(3).all(|triangle| {
+            let a = vertices[triangle[0] as usize].position;
+            let b = vertices[triangle[1] as usize].position;
+            let c = vertices[triangle[2] as usize].position;
+            let normal = (b - a).cross(c - a);
+            // the sphere's poles make zero area triangles
+            normal.magnitude2() < 1e-12 || normal.normalize().dot(point - a) <= 1e-5
+        })
+    }
+
+    fn direction(phi: f32, theta: f32) -> Vector3<f32> {
+        Vector3::new(phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin())
+    }
+
+    #[test]
+    fn sphere_volume_contains_the_unit_sphere() {
+        let mesh = sphere_volume_mesh(LIGHT_VOLUME_SEGMENTS);
+        assert!(contains(&mesh, Vector3::zero()));
+        for i in 0..=40 {
+            for j in 0..80 {
+                let point = direction(PI * i as f32 / 40.0, 2.0 * PI * j as f32 / 80.0);
+                assert!(contains(&mesh, point), "{:?} is outside", point);
+            }
+        }
+        assert!(!contains(&mesh, Vector3::new(0.0, 1.5, 0.0)));
+    }
+
+    #[test]
+    fn cone_volume_contains_the_unit_sector() {
+        let mesh = cone_volume_mesh(LIGHT_VOLUME_SEGMENTS);
+        assert!(contains(&mesh, Vector3::new(0.0, 0.0, -0.5)));
+        for i in 0..=20 {
+            // angle away from -z, out to 45 degrees
+            let angle = PI / 4.0 * i as f32 / 20.0;
+            for j in 0..80 {
+                let theta = 2.0 * PI * j as f32 / 80.0;
+                let point = Vector3::new(
+                    angle.sin() * theta.cos(),
+                    angle.sin() * theta.sin(),
+                    -angle.cos(),
+                );
+                assert!(contains(&mesh, point), "{:?} is outside", point);
+            }
+        }
+        assert!(!contains(&mesh, Vector3::new(0.0, 0.0, 0.5)));
+    }
+
+    #[test]
+    fn cone_volume_matrix_points_along_direction() {
+        let direction = Vector3::new(1.0, -1.0, 0.0).normalize();
+        let matrix = cone_volume_matrix(Vector3::new(1.0, 2.0, 3.0), direction, 4.0, PI / 6.0);
+        let tip = matrix.transform_point(cgmath::Point3::new(0.0, 0.0, 0.0));
+        let base = matrix.transform_point(cgmath::Point3::new(0.0, 0.0, -1.0));
+        assert!((tip - cgmath::Point3::new(1.0, 2.0, 3.0)).magnitude() < 1e-5);
+        assert!(((base - tip) - direction * 4.0).magnitude() < 1e-5);
+    }
+}
@@ -1,94 +1,101 @@
+mod assets;
 mod buffers;
+mod capture;
+mod config;
+mod culling;
+mod debug_views;
+mod errors;
 mod globals;
+#[cfg(test)]
+mod golden;
+#[cfg(test)]
+mod golden_tests;
+mod gltf;
+mod instancing;
+mod light_clusters;
+mod light_volumes;
 mod material_structs;
 mod models;
+mod post_process_passes;
+mod post_processing;
+mod scene;
+mod scene_graph;
+mod serde_vectors;
 mod shaders;
+mod tone_mapping;
+mod tangents;
+mod uniform_blocks;
+mod vertex_layout;
 mod winsdl;
 
-use buffers::{Cubemap, FrameBuffer, ModelTexture, RenderBuffer, Texture};
+use assets::AssetCache;
+use capture::{save_capture, Capture, CaptureSource};
+use config::Config;
+use buffers::{Cubemap, FrameBuffer, ModelTexture, RenderBuffer, Sampler, Texture, TextureArray};
+use culling::CullingVolume;
+use debug_views::{shadow_buffers, DebugBuffer, DebugViewMode, Tile};
 use cgmath::{
     InnerSpace, Matrix4, Quaternion, Rad, Rotation, Rotation3, SquareMatrix, Vector2, Vector3, Zero,
 };
 use core::f32;
 use egui_sdl2_gl::egui;
+use errors::EngineError;
 use egui_sdl2_gl::egui::FullOutput;
+use image::{Rgba32FImage, RgbaImage};
 use globals::Globals;
-use models::{DirectionalLight, Model, PointLight, SpotLight};
+use instancing::InstancedModel;
+use light_clusters::LightClusters;
+use models::{DirectionalLight, Model, PointLight, SpotLight, SPOT_LIGHT_NEAR_PLANE};
+use post_processing::{bind_output, PostProcessContext, PostProcessStage};
+use scene::{Scene, SceneSettings};
+use scene_graph::SceneObjects;
 use sdl2::event::Event;
 use sdl2::keyboard::{Keycode, Scancode};
-use shaders::{create_program, Program};
+use shaders::{
+    create_compute_program, create_program, create_program_with_geometry_shader, Program,
+};
+use std::path::{Path, PathBuf};
 use std::ptr::null;
+use tone_mapping::auto_exposure_mip_level;
+use uniform_blocks::{MAX_DIRECTIONAL_LIGHTS, MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS};
+use vertex_layout::InstanceData;
 use std::time::Instant;
 
-pub fn main() {
-    let mut globals = Globals::new();
+pub fn main() -> Result<(), EngineError> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", Config::usage());
+        return Ok(());
+    }
+    let config = Config::from_args(&args)?;
+    let mut globals = Globals::new(&config)?;
     let mut window_start_size = globals.win_sdl.window.size();
 
     unsafe {
         gl::Viewport(0, 0, window_start_size.0 as i32, window_start_size.1 as i32);
     }
-    //// depth only shader
-    let depth_only_shader = create_program(
-        "./shaders/BasicModelDepthOnly/shader.vert",
-        "./shaders/BasicModelDepthOnly/shader.frag",
-    )
-    .unwrap();
-
-    ////deferred passes shader programs
-    let point_lighting_pass = create_program(
-        "./shaders/PointLightingPass/shader.vert",
-        "./shaders/PointLightingPass/shader.frag",
-    )
-    .unwrap();
-    let directional_lighting_pass = create_program(
-        "./shaders/DirectionalLightingPass/shader.vert",
-        "./shaders/DirectionalLightingPass/shader.frag",
-    )
-    .unwrap();
-    let spot_lighting_pass = create_program(
-        "./shaders/SpotLightingPass/shader.vert",
-        "./shaders/SpotLightingPass/shader.frag",
-    )
-    .unwrap();
-    let final_pass = globals.screen_model.shader_program.clone();
-
-    ////lighting pass gbuffer
-    let (g_position, g_normal, g_albedo_spec, gbuffer, g_render_buffer) =
-        create_gbuffer(window_start_size);
-    let (light_texture, light_buffer) = create_framebuffer(window_start_size);
+    let mut renderer = Renderer::new(&mut globals, window_start_size)?;
 
     let mut directional_lights: Vec<DirectionalLight> = Vec::new();
-    directional_lights.push(DirectionalLight::new((4096, 4096)));
-    directional_lights[0].position = Vector3::new(-2.0, 4.0, -1.0);
-    directional_lights[0].direction =
-        (Vector3::zero() - directional_lights[0].position).normalize();
-
     let mut spot_lights: Vec<SpotLight> = Vec::new();
-    spot_lights.push(SpotLight::new((4096, 4096), (90.0f32).to_radians()));
-    spot_lights[0].position = Vector3::new(-2.0, 0.0, 0.0);
-    spot_lights[0].direction = (Vector3::zero() - spot_lights[0].position).normalize();
-
     let mut point_lights: Vec<PointLight> = Vec::new();
-
-    let cube_model = Model::from_obj_file("./models/Cube.obj".to_owned());
-    globals.models.push(cube_model);
-    let mut plane_model = Model::from_obj_file("./models/Plane.obj".to_owned());
-    plane_model.position = Vector3::new(0., -1.0, 0.);
-    globals.models.push(plane_model);
-    let mut plane_model = Model::from_obj_file("./models/Plane.obj".to_owned());
-    plane_model.position = Vector3::new(10., 9.0, 0.);
-    plane_model.rotation = Quaternion::from(cgmath::Euler::new(
-        Rad(90.0f32.to_radians()),
-        Rad(0.0f32.to_radians()),
-        Rad(90.0f32.to_radians()),
-    ));
-    globals.models.push(plane_model);
-
-    ////UNUSED CODE
-    /*
-    //point_lights.push(PointLight::new());
-    //point_lights[0].position = Vector3::new(0., 2., 0.);
-     */
+    add_default_scene(
+        &mut globals,
+        config.shadow_resolution,
+        &mut point_lights,
+        &mut spot_lights,
+        &mut directional_lights,
+    )?;
+
+    //// a scene from the config replaces the one above
+    if config.scene.is_some() {
+        load_scene(
+            &mut globals,
+            &mut point_lights,
+            &mut spot_lights,
+            &mut directional_lights,
+        )?;
+    }
 
     ////UNUSED CODE
     /*
@@ -96,13 +103,13 @@ pub fn main() {
     let mut portals: Vec<Portal> = Vec::new();
 
     let portal_shader_program: Program = create_program("./shaders/Portal/shader.vert", "./shaders/Portal/shader.frag").unwrap();
-    let mut portal_1_model = Model::from_obj_file("./models/Portal.obj".to_owned());
+    let mut portal_1_model = Model::from_obj_file("./models/Portal.obj".to_owned(), &mut globals.assets);
     portal_1_model.position = Vector3::new(0., 0., 2.);
     portal_1_model.shader_program = portal_shader_program;
     portal_1_model.render_shadows = false;
 
     let portal_shader_program: Program = create_program("./shaders/Portal/shader.vert", "./shaders/Portal/shader.frag").unwrap();
-    let mut portal_2_model = Model::from_obj_file("./models/Portal.obj".to_owned());
+    let mut portal_2_model = Model::from_obj_file("./models/Portal.obj".to_owned(), &mut globals.assets);
 
     portal_2_model.position = Vector3::new(0., 0., -2.);
     portal_2_model.shader_program = portal_shader_program;
@@ -114,6 +121,17 @@ pub fn main() {
     let portal_2 = Portal::new(globals.models.len()-1, portals.len()-1, &mut globals);
     portals.push(portal_2); */
 
+    if config.headless {
+        return render_headless(
+            &config,
+            &mut globals,
+            &mut renderer,
+            &mut point_lights,
+            &mut spot_lights,
+            &mut directional_lights,
+        );
+    }
+
     let start_time = Instant::now();
     let mut deltatime: f32 = 0.0;
 
@@ -122,6 +140,7 @@ pub fn main() {
 
     'running: loop {
         let frame_start = Instant::now();
+        globals.shader_registry.poll();
         let size = globals.win_sdl.window.size();
         globals.cam.projection_matrix.aspect = size.0 as f32 / size.1 as f32;
         globals.egui_state.input.time = Some(start_time.elapsed().as_secs_f64());
@@ -130,16 +149,7 @@ pub fn main() {
             .begin_frame(globals.egui_state.input.take());
 
         if window_start_size != size {
-            remake_gbuffer(
-                &gbuffer,
-                &g_position,
-                &g_normal,
-                &g_albedo_spec,
-                &g_render_buffer,
-                size,
-            );
-
-            remake_framebuffer(&light_buffer, &light_texture, size);
+            renderer.resize(size);
             window_start_size = size;
         }
 
@@ -157,6 +167,20 @@ pub fn main() {
                     globals.should_grab_mouse = !globals.should_grab_mouse;
                     reset_mouse = true;
                 }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    ..
+                } => {
+                    globals.capture.screenshot_requested = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    ..
+                } => {
+                    if let Err(error) = globals.capture.toggle_recording() {
+                        println!("warning: failed to start recording: {}", error);
+                    }
+                }
                 _ => {
                     globals.egui_state.process_input(
                         &globals.win_sdl.window,
@@ -212,11 +236,241 @@ pub fn main() {
 
         globals.cam.camera_rotation += mouse_look_delta.into();
 
-        for directional_light in &mut directional_lights {
-            directional_light.render(&mut globals, &depth_only_shader);
+        // recordings step time by a fixed amount each frame
+        let time = globals
+            .capture
+            .frame_time(start_time.elapsed().as_secs_f32());
+        renderer.render(
+            &mut globals,
+            &mut point_lights,
+            &mut spot_lights,
+            &mut directional_lights,
+            time,
+            None,
+        );
+        // before the ui is drawn over it
+        if let Err(error) = capture_frame(&mut globals.capture, &renderer) {
+            println!("warning: failed to capture the frame: {}", error);
+            globals.capture.stop_recording();
+        }
+
+        draw_ui(
+            &mut globals,
+            deltatime,
+            &mut point_lights,
+            &mut directional_lights,
+            &mut spot_lights,
+        );
+
+        globals.win_sdl.window.gl_swap_window();
+
+        deltatime = globals
+            .capture
+            .frame_deltatime(frame_start.elapsed().as_secs_f32());
+    }
+    return Ok(());
+}
+
+/// the programs and framebuffers of the deferred pipeline, everything a frame is drawn with
+pub struct Renderer {
+    depth_only_shader: Program,
+    point_shadow_shader: Program,
+    point_lighting_pass: Program,
+    directional_lighting_pass: Program,
+    spot_lighting_pass: Program,
+    /// the light culling compute program and the clustered lighting pass, None below gl 4.3
+    clustered_lighting_programs: Option<(Program, Program)>,
+    light_volume_shader: Program,
+    final_pass: Program,
+    /// exposes, tone maps and gamma corrects the hdr buffer into the target
+    tone_mapping_shader: Program,
+    /// shows a single buffer or shadow map in place of the final pass
+    debug_view_shader: Program,
+    raw_depth_sampler: Sampler,
+    g_position: Texture,
+    g_normal: Texture,
+    g_albedo_spec: Texture,
+    g_emissive: Texture,
+    gbuffer: FrameBuffer,
+    g_render_buffer: RenderBuffer,
+    light_texture: Texture,
+    light_buffer: FrameBuffer,
+    /// the final pass draws the scene in here before it is tone mapped
+    hdr_texture: Texture,
+    hdr_buffer: FrameBuffer,
+    size: (u32, u32),
+    /// the time of the last frame, auto exposure adapts by the time in between
+    last_frame_time: Option<f32>,
+}
+
+impl Renderer {
+    pub fn new(globals: &mut Globals, size: (u32, u32)) -> Result<Self, EngineError> {
+        //// depth only shader
+        let depth_only_shader = create_program(
+            "./shaders/BasicModelDepthOnly/shader.vert",
+            "./shaders/BasicModelDepthOnly/shader.frag",
+        )?;
+        //// point light shadow shader, renders all six cubemap faces in one pass
+        let point_shadow_shader = create_program_with_geometry_shader(
+            "./shaders/PointLightDepth/shader.vert",
+            "./shaders/PointLightDepth/shader.frag",
+            "./shaders/PointLightDepth/shader.geom",
+        )?;
+
+        ////deferred passes shader programs
+        let point_lighting_pass = create_program(
+            "./shaders/PointLightingPass/shader.vert",
+            "./shaders/PointLightingPass/shader.frag",
+        )?;
+        let directional_lighting_pass = create_program(
+            "./shaders/DirectionalLightingPass/shader.vert",
+            "./shaders/DirectionalLightingPass/shader.frag",
+        )?;
+        let spot_lighting_pass = create_program(
+            "./shaders/SpotLightingPass/shader.vert",
+            "./shaders/SpotLightingPass/shader.frag",
+        )?;
+        //// clustered point lights, culled by a compute pass then lit in one pass. compute shaders need gl 4.3
+        let clustered_lighting_programs = if globals.win_sdl.gl_version >= (4, 3) {
+            globals.light_clusters = Some(LightClusters::new());
+            Some((
+                create_compute_program("./shaders/LightCulling/shader.comp")?,
+                create_program(
+                    "./shaders/ClusteredLightingPass/shader.vert",
+                    "./shaders/ClusteredLightingPass/shader.frag",
+                )?,
+            ))
+        } else {
+            println!("warning: clustered lighting needs gl 4.3, it is turned off");
+            None
+        };
+        //// marks the pixels inside a light volume in the stencil buffer
+        let light_volume_shader = create_program(
+            "./shaders/LightVolume/shader.vert",
+            "./shaders/LightVolume/shader.frag",
+        )?;
+        let final_pass = globals.screen_model.shader_program.clone();
+        let tone_mapping_shader = create_program(
+            "./shaders/ToneMapping/shader.vert",
+            "./shaders/ToneMapping/shader.frag",
+        )?;
+        let debug_view_shader = create_program(
+            "./shaders/DebugWidget/shader.vert",
+            "./shaders/DebugWidget/shader.frag",
+        )?;
+        //// lets the lighting passes read shadow map depth directly next to the comparison sampler
+        let raw_depth_sampler = Sampler::new();
+        raw_depth_sampler.make_raw_depth();
+
+        ////lighting pass gbuffer
+        let (g_position, g_normal, g_albedo_spec, g_emissive, gbuffer, g_render_buffer) =
+            create_gbuffer(size);
+        let (light_texture, light_buffer) = create_hdr_framebuffer(size);
+        let (hdr_texture, hdr_buffer) = create_hdr_framebuffer(size);
+        // light volumes are stenciled against the depth of the scene
+        g_render_buffer.attach(&light_buffer)?;
+
+        return Ok(Self {
+            depth_only_shader,
+            point_shadow_shader,
+            point_lighting_pass,
+            directional_lighting_pass,
+            spot_lighting_pass,
+            clustered_lighting_programs,
+            light_volume_shader,
+            final_pass,
+            tone_mapping_shader,
+            debug_view_shader,
+            raw_depth_sampler,
+            g_position,
+            g_normal,
+            g_albedo_spec,
+            g_emissive,
+            gbuffer,
+            g_render_buffer,
+            light_texture,
+            light_buffer,
+            hdr_texture,
+            hdr_buffer,
+            size,
+            last_frame_time: None,
+        });
+    }
+
+    /// the g-buffer textures the lighting passes read
+    fn g_textures(&self) -> GBufferTextures<'_> {
+        return GBufferTextures {
+            position: &self.g_position,
+            normal: &self.g_normal,
+            albedo_spec: &self.g_albedo_spec,
+        };
+    }
+
+    /// remakes the g-buffer and light buffer at a new size
+    pub fn resize(&mut self, size: (u32, u32)) {
+        remake_gbuffer(
+            &self.gbuffer,
+            &self.g_position,
+            &self.g_normal,
+            &self.g_albedo_spec,
+            &self.g_emissive,
+            &self.g_render_buffer,
+            size,
+        );
+        remake_hdr_framebuffer(&self.light_buffer, &self.light_texture, size);
+        remake_hdr_framebuffer(&self.hdr_buffer, &self.hdr_texture, size);
+        self.size = size;
+    }
+
+    /// reads back the window or one of the buffers the frame was drawn with
+    pub fn read_capture(&self, source: CaptureSource) -> Rgba32FImage {
+        match source {
+            CaptureSource::Screen => FrameBuffer::read_window_pixels(self.size),
+            CaptureSource::Position => self.g_position.read_pixels_f32(),
+            CaptureSource::Normal => self.g_normal.read_pixels_f32(),
+            CaptureSource::AlbedoSpec => self.g_albedo_spec.read_pixels_f32(),
+            CaptureSource::Emissive => self.g_emissive.read_pixels_f32(),
+            CaptureSource::Light => self.light_texture.read_pixels_f32(),
+            CaptureSource::Hdr => self.hdr_texture.read_pixels_f32(),
         }
-        for spot_light in &mut spot_lights {
-            spot_light.render(&mut globals, &depth_only_shader);
+    }
+
+    /// updates the scene graph, renders the shadow maps, then the g-buffer, lighting, final and tone
+    /// mapping passes. the tone mapped image goes into target, or the window with None. time is the
+    /// time in the Camera block
+    pub fn render(
+        &mut self,
+        globals: &mut Globals,
+        point_lights: &mut Vec<PointLight>,
+        spot_lights: &mut Vec<SpotLight>,
+        directional_lights: &mut Vec<DirectionalLight>,
+        time: f32,
+        target: Option<&FrameBuffer>,
+    ) {
+        // a loaded scene can turn it on
+        if self.clustered_lighting_programs.is_none() {
+            globals.clustered_lighting = false;
+        }
+        globals.culling_stats.clear();
+        globals.scene_graph.update(&mut SceneObjects {
+            models: &mut globals.models,
+            instanced_models: &mut globals.instanced_models,
+            point_lights,
+            spot_lights,
+            directional_lights,
+            camera: &mut globals.cam,
+        });
+
+        for directional_light in directional_lights.iter_mut() {
+            directional_light.render(globals, &self.depth_only_shader);
+        }
+        for spot_light in spot_lights.iter_mut() {
+            spot_light.render(globals, &self.depth_only_shader);
+        }
+        for point_light in point_lights.iter_mut() {
+            if point_light.cast_shadows {
+                point_light.render(globals, &self.point_shadow_shader);
+            }
         }
 
         ////UNUSED CODE, am still figuring out how to implement portals
@@ -226,24 +480,39 @@ pub fn main() {
         //}
 
         unsafe {
-            gl::Viewport(
-                0,
-                0,
-                globals.win_sdl.window.size().0 as i32,
-                globals.win_sdl.window.size().1 as i32,
-            );
+            gl::Viewport(0, 0, self.size.0 as i32, self.size.1 as i32);
         }
 
-        gbuffer.bind();
         let projection_matrix: Matrix4<f32> = globals.cam.projection_matrix.into();
         let view_matrix: Matrix4<f32> = globals.cam.view_transform().invert().unwrap();
-        draw_scene(&mut globals, projection_matrix, view_matrix);
-        gbuffer.unbind();
+        globals.uniform_blocks.update_camera(
+            view_matrix,
+            projection_matrix,
+            globals.cam.world_position(),
+            Vector2::new(self.size.0 as f32, self.size.1 as f32),
+            time,
+        );
+        let point_light_data: Vec<_> = point_lights.iter().map(PointLight::uniform_data).collect();
+        let directional_light_data: Vec<_> = directional_lights
+            .iter()
+            .map(DirectionalLight::uniform_data)
+            .collect();
+        let spot_light_data: Vec<_> = spot_lights.iter().map(SpotLight::uniform_data).collect();
+        // after the shadow passes, so the cascade and spot light matrices are from this frame
+        globals.uniform_blocks.update_lights(
+            &point_light_data,
+            &directional_light_data,
+            &spot_light_data,
+        );
 
-        light_buffer.bind();
+        self.gbuffer.bind();
+        draw_scene(globals);
+        self.gbuffer.unbind();
+
+        self.light_buffer.bind();
 
         unsafe {
-            gl::ClearColor(0.1, 0.1, 0.1, 1.0);
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
         }
         unsafe {
@@ -253,76 +522,338 @@ pub fn main() {
             gl::BlendFunc(gl::ONE, gl::ONE);
         }
 
-        for i in 0..point_lights.len() {
-            draw_point_lighting_pass(
-                &mut globals,
-                &point_lighting_pass,
-                &g_position,
-                &g_normal,
-                &g_albedo_spec,
-                &point_lights[i],
+        let g_textures = self.g_textures();
+        globals.point_lighting_timer.begin();
+        //// the clustered pass doesn't sample shadows, so shadow casting point lights are left to
+        //// their own passes and both paths light the scene the same way
+        let clustered = globals.clustered_lighting;
+        let clustered_lighting = (
+            clustered,
+            &self.clustered_lighting_programs,
+            &mut globals.light_clusters,
+        );
+        if let (true, Some((light_culling_shader, clustered_lighting_pass)), Some(light_clusters)) =
+            clustered_lighting
+        {
+            let clustered_light_data: Vec<_> = point_lights
+                .iter()
+                .filter(|point_light| !point_light.cast_shadows)
+                .map(PointLight::uniform_data)
+                .collect();
+            light_clusters.update_lights(&clustered_light_data);
+            light_clusters.cull(
+                light_culling_shader,
+                projection_matrix,
+                globals.cam.projection_matrix.near,
+                globals.cam.projection_matrix.far,
             );
+            draw_clustered_lighting_pass(globals, clustered_lighting_pass, &g_textures);
+        }
+        //// lights past the size of their uniform block are drawn a block at a time,
+        //// each block is uploaded over the one before it
+        for (block, lights) in point_lights.chunks(MAX_POINT_LIGHTS).enumerate() {
+            if block > 0 {
+                globals
+                    .uniform_blocks
+                    .update_point_lights(&point_light_data[block * MAX_POINT_LIGHTS..]);
+            }
+            for (i, point_light) in lights.iter().enumerate() {
+                if clustered && !point_light.cast_shadows {
+                    continue;
+                }
+                let light_volume = globals.point_light_volumes.then_some(&self.light_volume_shader);
+                draw_point_lighting_pass(
+                    globals,
+                    &self.point_lighting_pass,
+                    &g_textures,
+                    point_light,
+                    i,
+                    light_volume,
+                );
+            }
         }
+        globals.point_lighting_timer.end();
 
-        for i in 0..directional_lights.len() {
-            draw_directional_lighting_pass(
-                &mut globals,
-                &directional_lighting_pass,
-                &g_position,
-                &g_normal,
-                &g_albedo_spec,
-                &directional_lights[i],
-            );
+        for (block, lights) in directional_lights.chunks(MAX_DIRECTIONAL_LIGHTS).enumerate() {
+            if block > 0 {
+                globals
+                    .uniform_blocks
+                    .update_directional_lights(&directional_light_data[block * MAX_DIRECTIONAL_LIGHTS..]);
+            }
+            for (i, directional_light) in lights.iter().enumerate() {
+                draw_directional_lighting_pass(
+                    globals,
+                    &self.directional_lighting_pass,
+                    &g_textures,
+                    directional_light,
+                    i,
+                    &self.raw_depth_sampler,
+                );
+            }
         }
 
-        for i in 0..spot_lights.len() {
-            draw_spot_lighting_pass(
-                &mut globals,
-                &spot_lighting_pass,
-                &g_position,
-                &g_normal,
-                &g_albedo_spec,
-                &spot_lights[i],
-            );
+        for (block, lights) in spot_lights.chunks(MAX_SPOT_LIGHTS).enumerate() {
+            if block > 0 {
+                globals
+                    .uniform_blocks
+                    .update_spot_lights(&spot_light_data[block * MAX_SPOT_LIGHTS..]);
+            }
+            for (i, spot_light) in lights.iter().enumerate() {
+                let light_volume = globals.spot_light_volumes.then_some(&self.light_volume_shader);
+                draw_spot_lighting_pass(
+                    globals,
+                    &self.spot_lighting_pass,
+                    &g_textures,
+                    spot_light,
+                    i,
+                    &self.raw_depth_sampler,
+                    light_volume,
+                );
+            }
         }
 
-        light_buffer.unbind();
+        self.light_buffer.unbind();
 
-        ////draw final pass
+        ////draw final pass into the hdr buffer
         unsafe {
             gl::Disable(gl::BLEND);
         }
-
+        self.hdr_buffer.bind();
         draw_final_pass(
-            &mut globals,
-            &final_pass,
-            &g_position,
-            &g_normal,
-            &g_albedo_spec,
-            &light_texture,
+            globals,
+            &self.final_pass,
+            &self.g_position,
+            &self.g_normal,
+            &self.g_albedo_spec,
+            &self.g_emissive,
+            &self.light_texture,
         );
+        self.hdr_buffer.unbind();
 
-        draw_scene_light_points(&mut globals, &point_lights, &spot_lights);
+        let deltatime = time - self.last_frame_time.unwrap_or(time);
+        self.last_frame_time = Some(time);
+        if globals.tone_mapping.auto_exposure {
+            self.adapt_exposure(globals, deltatime);
+        }
 
-        draw_ui(
-            &mut globals,
-            deltatime,
-            &mut point_lights,
-            &mut directional_lights,
-            &mut spot_lights,
-        );
+        ////post processing, the passes before tone mapping draw over the hdr buffer
+        globals.post_processing.prepare(&mut globals.assets, self.size);
+        let post_process_context = PostProcessContext {
+            screen_model: &globals.screen_model,
+            size: self.size,
+            time,
+            scene: &self.hdr_texture,
+            g_position: &self.g_position,
+            g_normal: &self.g_normal,
+        };
+        let hdr_target = globals.post_processing.run_hdr(&post_process_context);
 
-        globals.win_sdl.window.gl_swap_window();
+        ////tone map into the target, or into the passes after tone mapping when there are any
+        let tone_mapping_input = match hdr_target {
+            Some(index) => globals.post_processing.hdr_texture(index),
+            None => &self.hdr_texture,
+        };
+        bind_output(globals.post_processing.ldr_input().or(target));
+        draw_tone_mapping_pass(globals, &self.tone_mapping_shader, tone_mapping_input);
+        if globals.post_processing.has_enabled(PostProcessStage::Ldr) {
+            globals.post_processing.run_ldr(&post_process_context, target);
+            // tone mapping cleared the depth of its own target, the light points test against this one
+            unsafe {
+                gl::Clear(gl::DEPTH_BUFFER_BIT);
+            }
+        }
+
+        draw_scene_light_points(globals, point_lights, spot_lights);
+
+        ////debug views, drawn over the final pass
+        let shadow_buffers = debug_shadow_buffers(point_lights, spot_lights, directional_lights);
+        let debug_tiles = globals.debug_views.tiles(self.size, &shadow_buffers);
+        for (buffer, tile) in &debug_tiles {
+            self.draw_debug_view(
+                globals,
+                point_lights,
+                spot_lights,
+                directional_lights,
+                *buffer,
+                *tile,
+            );
+        }
+        if !debug_tiles.is_empty() {
+            unsafe {
+                gl::Viewport(0, 0, self.size.0 as i32, self.size.1 as i32);
+            }
+        }
+    }
 
-        deltatime = frame_start.elapsed().as_secs_f32();
+    /// averages the luminance of a small mip level of the hdr buffer for auto exposure
+    fn adapt_exposure(&self, globals: &mut Globals, deltatime: f32) {
+        self.hdr_texture.bind();
+        unsafe {
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+        }
+        let colors = self
+            .hdr_texture
+            .read_mip_level_f32(auto_exposure_mip_level(self.size));
+        globals
+            .tone_mapping
+            .adapt_exposure(colors.as_raw(), deltatime);
+    }
+
+    /// draws one buffer or shadow map into tile through the debug view shader
+    fn draw_debug_view(
+        &self,
+        globals: &mut Globals,
+        point_lights: &[PointLight],
+        spot_lights: &[SpotLight],
+        directional_lights: &[DirectionalLight],
+        buffer: DebugBuffer,
+        tile: Tile,
+    ) {
+        let shader_program = &self.debug_view_shader;
+        shader_program.set();
+        unsafe {
+            gl::Viewport(
+                tile.x as i32,
+                tile.y as i32,
+                tile.width as i32,
+                tile.height as i32,
+            );
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::CULL_FACE);
+            gl::Disable(gl::BLEND);
+        }
+        globals.screen_model.mesh.vbo.bind();
+        let textures = [
+            &self.g_position,
+            &self.g_normal,
+            &self.g_albedo_spec,
+            &self.g_emissive,
+            &self.light_texture,
+        ];
+        for (unit, texture) in textures.iter().enumerate() {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+            }
+            texture.bind_texture();
+        }
+        shader_program.set_int("gPosition", 0);
+        shader_program.set_int("gNormal", 1);
+        shader_program.set_int("gAlbedoSpec", 2);
+        shader_program.set_int("gEmissive", 3);
+        shader_program.set_int("gLighting", 4);
+        shader_program.set_int("shadowMap", 5);
+        shader_program.set_int("shadowMapArray", 6);
+        shader_program.set_int("shadowCubemap", 7);
+        match buffer {
+            DebugBuffer::SpotShadow { light } => {
+                unsafe {
+                    gl::ActiveTexture(gl::TEXTURE5);
+                }
+                spot_lights[light].shadow_texture.bind_texture();
+                shader_program.set_float("nearPlane", SPOT_LIGHT_NEAR_PLANE);
+                shader_program.set_float("farPlane", spot_lights[light].info.radius);
+            }
+            DebugBuffer::DirectionalShadow { light, cascade } => {
+                unsafe {
+                    gl::ActiveTexture(gl::TEXTURE6);
+                }
+                directional_lights[light].shadow_texture.bind_texture();
+                shader_program.set_int("shadowLayer", cascade as i32);
+            }
+            DebugBuffer::PointShadow { light, face } => {
+                unsafe {
+                    gl::ActiveTexture(gl::TEXTURE7);
+                }
+                if let Some(shadow_cubemap) = point_lights[light].shadow_cubemap() {
+                    shadow_cubemap.bind_texture();
+                }
+                shader_program.set_int("cubemapFace", face as i32);
+            }
+            _ => {}
+        }
+        self.raw_depth_sampler.bind(5);
+        self.raw_depth_sampler.bind(6);
+        shader_program.set_int("mode", buffer.shader_mode());
+        shader_program.set_float("positionRange", globals.debug_views.position_range);
+        shader_program.set_float("depthRange", globals.debug_views.depth_range);
+
+        globals.screen_model.mesh.vao.bind();
+        globals.screen_model.mesh.ibo.bind();
+        globals.screen_model.render_fullbright();
+        self.raw_depth_sampler.unbind(5);
+        self.raw_depth_sampler.unbind(6);
     }
 }
 
-pub fn draw_scene(
+/// every shadow map a debug view can show
+pub fn debug_shadow_buffers(
+    point_lights: &[PointLight],
+    spot_lights: &[SpotLight],
+    directional_lights: &[DirectionalLight],
+) -> Vec<DebugBuffer> {
+    let cascade_counts: Vec<usize> = directional_lights
+        .iter()
+        .map(|directional_light| directional_light.cascade_count)
+        .collect();
+    let point_shadow_lights: Vec<usize> = point_lights
+        .iter()
+        .enumerate()
+        .filter(|(_, point_light)| point_light.shadow_cubemap().is_some())
+        .map(|(i, _)| i)
+        .collect();
+    return shadow_buffers(&cascade_counts, spot_lights.len(), &point_shadow_lights);
+}
+
+/// where add_default_scene puts its shadow casting point light
+pub const DEFAULT_POINT_LIGHT_POSITION: Vector3<f32> = Vector3::new(-3.0, 2.0, 0.5);
+
+/// the cube, the two planes and the lights the engine starts with when there is no scene file
+pub fn add_default_scene(
     globals: &mut Globals,
-    projection_matrix: Matrix4<f32>,
-    view_matrix: Matrix4<f32>,
-) {
+    shadow_resolution: u32,
+    point_lights: &mut Vec<PointLight>,
+    spot_lights: &mut Vec<SpotLight>,
+    directional_lights: &mut Vec<DirectionalLight>,
+) -> Result<(), EngineError> {
+    // left of the cube, so its shadow falls on the floor to the right of it.
+    // a cubemap face covers less than a spot light's map, so half the resolution looks about the same
+    let mut point_light = PointLight::new((shadow_resolution / 2).max(16));
+    point_light.position = DEFAULT_POINT_LIGHT_POSITION;
+    point_light.info.radius = 8.0;
+    point_lights.push(point_light);
+
+    let shadow_resolution = (shadow_resolution, shadow_resolution);
+    let mut directional_light = DirectionalLight::new(shadow_resolution);
+    directional_light.position = Vector3::new(-2.0, 4.0, -1.0);
+    directional_light.direction = (Vector3::zero() - directional_light.position).normalize();
+    directional_lights.push(directional_light);
+
+    let mut spot_light = SpotLight::new(shadow_resolution, (90.0f32).to_radians());
+    spot_light.position = Vector3::new(-2.0, 0.0, 0.0);
+    spot_light.direction = (Vector3::zero() - spot_light.position).normalize();
+    spot_lights.push(spot_light);
+
+    let cube_model = Model::from_obj_file("./models/Cube.obj".to_owned(), &mut globals.assets)?;
+    globals.models.push(cube_model);
+    let mut plane_model =
+        Model::from_obj_file("./models/Plane.obj".to_owned(), &mut globals.assets)?;
+    plane_model.position = Vector3::new(0., -1.0, 0.);
+    globals.models.push(plane_model);
+    let mut plane_model =
+        Model::from_obj_file("./models/Plane.obj".to_owned(), &mut globals.assets)?;
+    plane_model.position = Vector3::new(10., 9.0, 0.);
+    plane_model.rotation = Quaternion::from(cgmath::Euler::new(
+        Rad(90.0f32.to_radians()),
+        Rad(0.0f32.to_radians()),
+        Rad(90.0f32.to_radians()),
+    ));
+    globals.models.push(plane_model);
+
+    return Ok(());
+}
+
+/// draws every model into the currently bound framebuffer with the camera in the Camera block
+pub fn draw_scene(globals: &mut Globals) {
     unsafe {
         gl::ClearColor(0. / 255., 0. / 255., 0. / 255., 1.0);
         gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
@@ -332,33 +863,45 @@ pub fn draw_scene(
         gl::Enable(gl::CULL_FACE);
     }
 
+    let projection_matrix: Matrix4<f32> = globals.cam.projection_matrix.into();
+    let view_matrix: Matrix4<f32> = globals.cam.view_transform().invert().unwrap();
+    let culling_volume = CullingVolume::from_matrix(projection_matrix * view_matrix);
+
     for model in &mut globals.models {
-        let screen_size: Vector2<f32> = Vector2::new(
-            globals.win_sdl.window.size().0 as f32,
-            globals.win_sdl.window.size().1 as f32,
-        );
+        let visible = !globals.frustum_culling || culling_volume.is_visible(&model.world_bounds());
+        globals.culling_stats.record("Camera", visible);
+        if !visible {
+            continue;
+        }
         if model.render_shadows {
             unsafe {
                 gl::CullFace(gl::BACK);
             }
             model.start_render();
 
-            model.render(screen_size, view_matrix, projection_matrix);
+            model.render();
         } else {
             unsafe {
                 gl::CullFace(gl::BACK);
             }
             model.start_render();
-            model.render_fullbright(screen_size, view_matrix, projection_matrix);
+            model.render_fullbright();
+        }
+    }
+    unsafe {
+        gl::CullFace(gl::BACK);
+    }
+    for instanced_model in &mut globals.instanced_models {
+        let visible = !globals.frustum_culling
+            || culling_volume.is_visible(&instanced_model.world_bounds());
+        globals.culling_stats.record("Camera", visible);
+        if visible {
+            instanced_model.render();
         }
     }
 }
 
-pub fn draw_scene_shadows(
-    globals: &mut Globals,
-    projection_matrix: Matrix4<f32>,
-    view_matrix: Matrix4<f32>,
-) {
+pub fn draw_scene_shadows(globals: &mut Globals) {
     unsafe {
         gl::Enable(gl::DEPTH_TEST);
         gl::Enable(gl::CULL_FACE);
@@ -373,7 +916,7 @@ pub fn draw_scene_shadows(
             gl::CullFace(gl::FRONT);
         }
         model.start_render();
-        model.render_fullbright(Vector2::zero(), view_matrix, projection_matrix);
+        model.render_fullbright();
         unsafe {
             gl::CullFace(gl::CULL_FACE);
         }
@@ -386,6 +929,8 @@ pub fn draw_scene_custom_shader_program(
     view_matrix: Matrix4<f32>,
     shader_program: &Program,
     is_render_shadows: bool,
+    culling_volume: CullingVolume,
+    pass: &str,
 ) {
     unsafe {
         gl::Enable(gl::DEPTH_TEST);
@@ -398,6 +943,12 @@ pub fn draw_scene_custom_shader_program(
 
     for model in &mut globals.models {
         if (is_render_shadows && model.render_shadows) || !is_render_shadows {
+            let visible =
+                !globals.frustum_culling || culling_volume.is_visible(&model.world_bounds());
+            globals.culling_stats.record(pass, visible);
+            if !visible {
+                continue;
+            }
             unsafe {
                 gl::CullFace(gl::FRONT);
             }
@@ -414,146 +965,244 @@ pub fn draw_scene_custom_shader_program(
             }
         }
     }
+    for instanced_model in &mut globals.instanced_models {
+        if !is_render_shadows || instanced_model.model.render_shadows {
+            let visible = !globals.frustum_culling
+                || culling_volume.is_visible(&instanced_model.world_bounds());
+            globals.culling_stats.record(pass, visible);
+            if !visible {
+                continue;
+            }
+            unsafe {
+                gl::CullFace(gl::FRONT);
+            }
+            instanced_model.render_custom_shader_program(
+                view_matrix,
+                projection_matrix,
+                shader_program,
+            );
+        }
+    }
+}
+
+/// the g-buffer textures every lighting pass reads
+pub struct GBufferTextures<'a> {
+    pub position: &'a Texture,
+    pub normal: &'a Texture,
+    pub albedo_spec: &'a Texture,
+}
+
+impl GBufferTextures<'_> {
+    /// binds the textures to units 0 to 2 as gPosition, gNormal and gAlbedoSpec, with the
+    /// lighting program already set
+    pub fn bind(&self, shader_program: &Program) {
+        let textures = [self.position, self.normal, self.albedo_spec];
+        for (unit, texture) in textures.iter().enumerate() {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+            }
+            texture.bind_texture();
+        }
+        shader_program.set_int("gPosition", 0);
+        shader_program.set_int("gNormal", 1);
+        shader_program.set_int("gAlbedoSpec", 2);
+    }
 }
 
 pub fn draw_point_lighting_pass(
     globals: &mut Globals,
     shader_program: &Program,
-    g_position: &Texture,
-    g_normal: &Texture,
-    g_albedo_spec: &Texture,
+    g_textures: &GBufferTextures,
     point_light: &PointLight,
+    light_index: usize,
+    light_volume_shader: Option<&Program>,
 ) {
+    let volume_matrix = point_light.volume_matrix();
+    if let Some(light_volume_shader) = light_volume_shader {
+        mark_light_volume(
+            &globals.sphere_volume_model,
+            light_volume_shader,
+            volume_matrix,
+        );
+    }
     shader_program.set();
-    globals.screen_model.vbo.bind();
+    globals.screen_model.mesh.vbo.bind();
+    g_textures.bind(shader_program);
     unsafe {
-        gl::ActiveTexture(gl::TEXTURE0);
+        gl::ActiveTexture(gl::TEXTURE3);
     }
-    g_position.bind_texture();
-    unsafe {
-        gl::ActiveTexture(gl::TEXTURE1);
+    let shadow_cubemap = point_light.shadow_cubemap();
+    if let Some(shadow_cubemap) = shadow_cubemap {
+        shadow_cubemap.bind_texture();
     }
-    g_normal.bind_texture();
-    unsafe {
-        gl::ActiveTexture(gl::TEXTURE2);
+    shader_program.set_int("shadowMap", 3);
+    shader_program.set_int("lightIndex", light_index as i32);
+    shader_program.set_bool("castShadows", shadow_cubemap.is_some());
+    shader_program.set_bool("fullScreen", light_volume_shader.is_none());
+
+    if light_volume_shader.is_some() {
+        draw_light_volume(&globals.sphere_volume_model, shader_program, volume_matrix);
+        return;
     }
-    g_albedo_spec.bind_texture();
-    shader_program.set_int("gPosition", 0);
-    shader_program.set_int("gNormal", 1);
-    shader_program.set_int("gAlbedoSpec", 2);
-    shader_program.set_vector3("viewPos", globals.cam.position);
+    globals.screen_model.mesh.vao.bind();
+    globals.screen_model.mesh.ibo.bind();
 
-    globals.screen_model.vao.bind();
-    globals.screen_model.ibo.bind();
-    shader_program.set_point_light_info("light", point_light.info, point_light.position);
+    globals.screen_model.render_fullbright();
+}
 
-    globals.screen_model.render_fullbright(
-        Vector2::zero(),
-        globals.cam.view_transform().invert().unwrap(),
-        globals.cam.projection_matrix.into(),
-    );
+/// lights every point light in one pass, each fragment only loops over the lights in its cluster,
+/// LightClusters::cull has to have run this frame
+pub fn draw_clustered_lighting_pass(
+    globals: &mut Globals,
+    shader_program: &Program,
+    g_textures: &GBufferTextures,
+) {
+    shader_program.set();
+    globals.screen_model.mesh.vbo.bind();
+    g_textures.bind(shader_program);
+    if let Some(light_clusters) = &globals.light_clusters {
+        light_clusters.bind();
+        light_clusters.set_grid_uniforms(
+            shader_program,
+            globals.cam.projection_matrix.near,
+            globals.cam.projection_matrix.far,
+        );
+    }
+    shader_program.set_bool("debugClusters", globals.debug_clusters);
+
+    globals.screen_model.mesh.vao.bind();
+    globals.screen_model.mesh.ibo.bind();
+
+    globals.screen_model.render_fullbright();
 }
 
 pub fn draw_directional_lighting_pass(
     globals: &mut Globals,
     shader_program: &Program,
-    g_position: &Texture,
-    g_normal: &Texture,
-    g_albedo_spec: &Texture,
+    g_textures: &GBufferTextures,
     directional_light: &DirectionalLight,
+    light_index: usize,
+    raw_depth_sampler: &Sampler,
 ) {
     shader_program.set();
-    globals.screen_model.vbo.bind();
-    unsafe {
-        gl::ActiveTexture(gl::TEXTURE0);
-    }
-    g_position.bind_texture();
+    globals.screen_model.mesh.vbo.bind();
+    g_textures.bind(shader_program);
     unsafe {
-        gl::ActiveTexture(gl::TEXTURE1);
-    }
-    g_normal.bind_texture();
-    unsafe {
-        gl::ActiveTexture(gl::TEXTURE2);
+        gl::ActiveTexture(gl::TEXTURE3);
     }
-    g_albedo_spec.bind_texture();
+    directional_light.shadow_texture.bind_texture();
     unsafe {
-        gl::ActiveTexture(gl::TEXTURE3);
+        gl::ActiveTexture(gl::TEXTURE4);
     }
     directional_light.shadow_texture.bind_texture();
+    raw_depth_sampler.bind(4);
 
-    shader_program.set_int("gPosition", 0);
-    shader_program.set_int("gNormal", 1);
-    shader_program.set_int("gAlbedoSpec", 2);
     shader_program.set_int("shadowMap", 3);
-    shader_program.set_vector3("viewPos", globals.cam.position);
-
-    globals.screen_model.vao.bind();
-    globals.screen_model.ibo.bind();
-    shader_program.set_vector3("light.Position", directional_light.position);
-    shader_program.set_vector3("light.Direction", directional_light.direction);
-    shader_program.set_matrix4_float(
-        "lightSpaceMatrix",
-        directional_light.light_projection * directional_light.light_view,
-    );
-    shader_program.set_vector3("light.Color", directional_light.info.color);
+    shader_program.set_int("shadowMapRaw", 4);
+    shader_program.set_shadow_settings("shadowSettings", directional_light.shadow_settings);
+    shader_program.set_int("lightIndex", light_index as i32);
 
-    globals.screen_model.render_fullbright(
-        Vector2::zero(),
-        globals.cam.view_transform().invert().unwrap(),
-        globals.cam.projection_matrix.into(),
-    );
+    globals.screen_model.mesh.vao.bind();
+    globals.screen_model.mesh.ibo.bind();
+
+    globals.screen_model.render_fullbright();
+    raw_depth_sampler.unbind(4);
 }
 
 pub fn draw_spot_lighting_pass(
     globals: &mut Globals,
     shader_program: &Program,
-    g_position: &Texture,
-    g_normal: &Texture,
-    g_albedo_spec: &Texture,
+    g_textures: &GBufferTextures,
     spot_light: &SpotLight,
+    light_index: usize,
+    raw_depth_sampler: &Sampler,
+    light_volume_shader: Option<&Program>,
 ) {
+    let volume_matrix = spot_light.volume_matrix();
+    let volume_model = if spot_light.uses_cone_volume() {
+        &globals.cone_volume_model
+    } else {
+        &globals.sphere_volume_model
+    };
+    if let Some(light_volume_shader) = light_volume_shader {
+        mark_light_volume(volume_model, light_volume_shader, volume_matrix);
+    }
     shader_program.set();
-    globals.screen_model.vbo.bind();
+    globals.screen_model.mesh.vbo.bind();
+    g_textures.bind(shader_program);
     unsafe {
-        gl::ActiveTexture(gl::TEXTURE0);
+        gl::ActiveTexture(gl::TEXTURE3);
     }
-    g_position.bind_texture();
+    spot_light.shadow_texture.bind_texture();
     unsafe {
-        gl::ActiveTexture(gl::TEXTURE1);
+        gl::ActiveTexture(gl::TEXTURE4);
     }
-    g_normal.bind_texture();
+    spot_light.shadow_texture.bind_texture();
+    raw_depth_sampler.bind(4);
+
+    shader_program.set_int("shadowMap", 3);
+    shader_program.set_int("shadowMapRaw", 4);
+    shader_program.set_shadow_settings("shadowSettings", spot_light.shadow_settings);
+    shader_program.set_int("lightIndex", light_index as i32);
+    shader_program.set_bool("fullScreen", light_volume_shader.is_none());
+
+    if light_volume_shader.is_some() {
+        draw_light_volume(volume_model, shader_program, volume_matrix);
+    } else {
+        globals.screen_model.mesh.vao.bind();
+        globals.screen_model.mesh.ibo.bind();
+
+        globals.screen_model.render_fullbright();
+    }
+    raw_depth_sampler.unbind(4);
+}
+
+/// fills the stencil buffer with the pixels whose scene depth is inside the volume. back faces behind
+/// the scene increment and front faces behind it decrement, so only pixels with geometry between
+/// the two are left non zero. the light buffer has to have the gbuffer's depth attached
+pub fn mark_light_volume(volume_model: &Model, stencil_shader: &Program, model_matrix: Matrix4<f32>) {
     unsafe {
-        gl::ActiveTexture(gl::TEXTURE2);
+        gl::Enable(gl::STENCIL_TEST);
+        gl::Clear(gl::STENCIL_BUFFER_BIT);
+        gl::Enable(gl::DEPTH_TEST);
+        gl::DepthMask(gl::FALSE);
+        // keeps the near and far planes from cutting holes in the volume when the camera is inside it
+        gl::Enable(gl::DEPTH_CLAMP);
+        gl::Disable(gl::CULL_FACE);
+        gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+        gl::StencilFunc(gl::ALWAYS, 0, 0);
+        gl::StencilOpSeparate(gl::BACK, gl::KEEP, gl::INCR_WRAP, gl::KEEP);
+        gl::StencilOpSeparate(gl::FRONT, gl::KEEP, gl::DECR_WRAP, gl::KEEP);
     }
-    g_albedo_spec.bind_texture();
+    stencil_shader.set();
+    volume_model.mesh.vao.bind();
+    volume_model.mesh.ibo.bind();
+    volume_model.render_with_model_matrix(stencil_shader, model_matrix);
     unsafe {
-        gl::ActiveTexture(gl::TEXTURE3);
+        gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+        gl::Disable(gl::DEPTH_TEST);
     }
-    spot_light.shadow_texture.bind_texture();
+}
 
-    shader_program.set_int("gPosition", 0);
-    shader_program.set_int("gNormal", 1);
-    shader_program.set_int("gAlbedoSpec", 2);
-    shader_program.set_int("shadowMap", 3);
-    shader_program.set_vector3("viewPos", globals.cam.position);
-
-    globals.screen_model.vao.bind();
-    globals.screen_model.ibo.bind();
-    shader_program.set_vector3("light.Position", spot_light.position);
-    shader_program.set_vector3("light.Direction", spot_light.direction);
-    shader_program.set_matrix4_float(
-        "lightSpaceMatrix",
-        spot_light.light_projection * spot_light.light_view,
-    );
-    shader_program.set_vector3("light.Color", spot_light.info.color);
-    shader_program.set_float("light.Radius", spot_light.info.radius);
-    shader_program.set_float("light.Fov", spot_light.horizontal_fov);
-
-    globals.screen_model.render_fullbright(
-        Vector2::zero(),
-        globals.cam.view_transform().invert().unwrap(),
-        globals.cam.projection_matrix.into(),
-    );
+/// lights the pixels mark_light_volume marked, with the lighting program already set up
+pub fn draw_light_volume(volume_model: &Model, shader_program: &Program, model_matrix: Matrix4<f32>) {
+    unsafe {
+        gl::StencilFunc(gl::NOTEQUAL, 0, 0xFF);
+        gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+        // back faces, so the volume still draws with the camera inside it
+        gl::Enable(gl::CULL_FACE);
+        gl::CullFace(gl::FRONT);
+    }
+    volume_model.mesh.vao.bind();
+    volume_model.mesh.ibo.bind();
+    volume_model.render_with_model_matrix(shader_program, model_matrix);
+    unsafe {
+        gl::CullFace(gl::BACK);
+        gl::Disable(gl::CULL_FACE);
+        gl::Disable(gl::DEPTH_CLAMP);
+        gl::DepthMask(gl::TRUE);
+        gl::Disable(gl::STENCIL_TEST);
+    }
 }
 
 pub fn draw_final_pass(
@@ -562,6 +1211,7 @@ pub fn draw_final_pass(
     g_position: &Texture,
     g_normal: &Texture,
     g_albedo_spec: &Texture,
+    g_emissive: &Texture,
     g_lighting: &Texture,
 ) {
     shader_program.set();
@@ -573,7 +1223,7 @@ pub fn draw_final_pass(
         gl::Disable(gl::DEPTH_TEST);
         gl::Disable(gl::CULL_FACE);
     }
-    globals.screen_model.vbo.bind();
+    globals.screen_model.mesh.vbo.bind();
     unsafe {
         gl::ActiveTexture(gl::TEXTURE0);
     }
@@ -590,19 +1240,47 @@ pub fn draw_final_pass(
         gl::ActiveTexture(gl::TEXTURE3);
     }
     g_lighting.bind_texture();
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE4);
+    }
+    g_emissive.bind_texture();
     shader_program.set_int("gPosition", 0);
     shader_program.set_int("gNormal", 1);
     shader_program.set_int("gAlbedoSpec", 2);
     globals.screen_model.shader_program.set_int("gLighting", 3);
-    shader_program.set_vector3("viewPos", globals.cam.position);
-
-    globals.screen_model.vao.bind();
-    globals.screen_model.ibo.bind();
-    globals.screen_model.render_fullbright(
-        Vector2::zero(),
-        globals.cam.view_transform().invert().unwrap(),
-        globals.cam.projection_matrix.into(),
+    shader_program.set_int("gEmissive", 4);
+    shader_program.set_vector3("ambientLight", globals.ambient_light);
+
+    globals.screen_model.mesh.vao.bind();
+    globals.screen_model.mesh.ibo.bind();
+    globals.screen_model.render_fullbright();
+}
+
+/// exposes and tone maps the hdr scene into the bound framebuffer
+pub fn draw_tone_mapping_pass(globals: &Globals, shader_program: &Program, hdr_texture: &Texture) {
+    shader_program.set();
+    unsafe {
+        gl::ClearColor(1.0, 1.0, 1.0, 1.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::CULL_FACE);
+    }
+    globals.screen_model.mesh.vbo.bind();
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+    hdr_texture.bind_texture();
+    shader_program.set_int("hdrColor", 0);
+    shader_program.set_float("exposure", globals.tone_mapping.exposure_scale());
+    shader_program.set_float("gamma", globals.tone_mapping.gamma);
+    shader_program.set_int(
+        "toneMapper",
+        globals.tone_mapping.tone_mapper.shader_value(),
     );
+
+    globals.screen_model.mesh.vao.bind();
+    globals.screen_model.mesh.ibo.bind();
+    globals.screen_model.render_fullbright();
 }
 
 pub fn draw_scene_light_points(
@@ -616,23 +1294,16 @@ pub fn draw_scene_light_points(
     }
 
     for point_light in point_lights {
-        globals.light_model.position = point_light.position;
+        globals.light_model.position = point_light.world_position();
         globals.light_model.start_render();
-        globals.light_model.render_fullbright(
-            Vector2::zero(),
-            globals.cam.view_transform().invert().unwrap(),
-            globals.cam.projection_matrix.into(),
-        );
+        globals.light_model.render_fullbright();
     }
     for spot_light in spot_lights {
-        globals.light_model.position = spot_light.position;
-        globals.light_model.rotation = Quaternion::look_at(spot_light.direction, Vector3::unit_y());
+        globals.light_model.position = spot_light.world_position();
+        globals.light_model.rotation =
+            Quaternion::look_at(spot_light.world_direction(), Vector3::unit_y());
         globals.light_model.start_render();
-        globals.light_model.render_fullbright(
-            Vector2::zero(),
-            globals.cam.view_transform().invert().unwrap(),
-            globals.cam.projection_matrix.into(),
-        );
+        globals.light_model.render_fullbright();
     }
 }
 
@@ -650,6 +1321,9 @@ pub fn draw_ui(
 
     let egui_debug_ui = egui::Window::new("Debug Values");
     let fps = 1.0 / deltatime;
+    // the scene is saved or loaded once the window is drawn, it needs all of globals
+    let mut save_scene_clicked = false;
+    let mut load_scene_clicked = false;
     egui_debug_ui.show(&globals.egui_ctx, |ui| {
         ui.label("FPS: ".to_owned() + &fps.to_string());
         ui.separator();
@@ -664,46 +1338,137 @@ pub fn draw_ui(
         ui.label("Mouse Look Sensitivity");
         ui.add(egui::DragValue::new(&mut globals.mouse_look_sensitivity).speed(0.01));
         ui.separator();
+        ui.label("Ambient Light");
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut globals.ambient_light.x)
+                    .prefix("R ")
+                    .speed(0.005),
+            );
+            ui.add(
+                egui::DragValue::new(&mut globals.ambient_light.y)
+                    .prefix("G ")
+                    .speed(0.005),
+            );
+            ui.add(
+                egui::DragValue::new(&mut globals.ambient_light.z)
+                    .prefix("B ")
+                    .speed(0.005),
+            );
+        });
+        ui.separator();
+        ui.label("Shaders");
+        ui.add(&mut globals.shader_registry);
+        ui.separator();
+        ui.label("Assets");
+        ui.add(&mut globals.assets);
+        ui.separator();
+        ui.label("Culling");
+        ui.checkbox(&mut globals.frustum_culling, "Frustum Culling");
+        ui.add(&mut globals.culling_stats);
+        ui.separator();
+        ui.label("Scene File");
+        ui.text_edit_singleline(&mut globals.scene_path);
+        ui.horizontal(|ui| {
+            save_scene_clicked = ui.button("Save Scene").clicked();
+            load_scene_clicked = ui.button("Load Scene").clicked();
+        });
+        ui.separator();
+        ui.label("Tone Mapping");
+        ui.add(&mut globals.tone_mapping);
+        ui.separator();
+        ui.label("Post Processing");
+        globals.post_processing.ui(ui);
+        ui.separator();
+        ui.label("Debug View");
+        globals.debug_views.ui(
+            ui,
+            &debug_shadow_buffers(point_lights, spot_lights, directional_lights),
+        );
+        ui.separator();
+        ui.label("Capture");
+        ui.add(&mut globals.capture);
+        ui.separator();
         egui::ScrollArea::vertical()
             .id_source("explorer_scroll_area")
             .show(ui, |ui| {
                 ui.label("Models");
-                egui::ScrollArea::vertical()
-                    .id_source("model_scroll_area")
-                    .show(ui, |ui| {
-                        for model in &mut globals.models {
-                            ui.add(model);
-                        }
-                    });
+                if ui.button("Add 10k Instanced Cubes").clicked() {
+                    if let Err(error) = add_instanced_cube_stress_test(
+                        &mut globals.instanced_models,
+                        &mut globals.assets,
+                        10_000,
+                    ) {
+                        println!("warning: failed to add the instanced cubes: {}", error);
+                    }
+                }
                 ui.separator();
                 ui.label("Point Lights");
-
-                egui::ScrollArea::vertical()
-                    .id_source("point_light_scroll_area")
-                    .show(ui, |ui| {
-                        for point_light in point_lights.iter_mut() {
-                            ui.add(point_light);
-                        }
-                    });
+                ui.checkbox(&mut globals.point_light_volumes, "Sphere Light Volumes");
+                ui.add_enabled(
+                    globals.win_sdl.gl_version >= (4, 3),
+                    egui::Checkbox::new(&mut globals.clustered_lighting, "Clustered Lighting"),
+                );
+                ui.checkbox(&mut globals.debug_clusters, "Show Cluster Light Counts");
+                ui.label(format!(
+                    "Point Lighting GPU Time: {:.3} ms",
+                    globals.point_lighting_timer.elapsed_ms
+                ));
+                if ui.button("Add 100 Point Lights").clicked() {
+                    add_test_point_lights(point_lights, 100);
+                }
                 ui.separator();
-                ui.label("Directional Lights");
-
-                egui::ScrollArea::vertical()
-                    .id_source("directional_light_scroll_area")
-                    .show(ui, |ui| {
-                        for directional_light in directional_lights.iter_mut() {
-                            ui.add(directional_light);
-                        }
-                    });
+                ui.label("Spot Lights");
+                ui.checkbox(&mut globals.spot_light_volumes, "Cone Light Volumes");
+                ui.separator();
+                ui.label("Scene");
                 egui::ScrollArea::vertical()
-                    .id_source("spot_light_scroll_area")
+                    .id_source("scene_scroll_area")
                     .show(ui, |ui| {
-                        for spot_light in spot_lights.iter_mut() {
-                            ui.add(spot_light);
-                        }
+                        globals.scene_graph.explorer_ui(
+                            ui,
+                            &mut SceneObjects {
+                                models: &mut globals.models,
+                                instanced_models: &mut globals.instanced_models,
+                                point_lights,
+                                spot_lights,
+                                directional_lights,
+                                camera: &mut globals.cam,
+                            },
+                        );
                     });
             });
     });
+    //// the names of the buffers in the debug view grid
+    if globals.debug_views.mode == DebugViewMode::Grid {
+        let size = globals.win_sdl.window.size();
+        let pixels_per_point = globals.egui_ctx.pixels_per_point();
+        let painter = globals.egui_ctx.layer_painter(egui::LayerId::background());
+        let shadow_buffers = debug_shadow_buffers(point_lights, spot_lights, directional_lights);
+        for (buffer, tile) in globals.debug_views.tiles(size, &shadow_buffers) {
+            let top = size.1 - tile.y - tile.height;
+            painter.text(
+                egui::pos2(
+                    (tile.x as f32 + 4.0) / pixels_per_point,
+                    (top as f32 + 4.0) / pixels_per_point,
+                ),
+                egui::Align2::LEFT_TOP,
+                buffer.name(),
+                egui::FontId::monospace(12.0),
+                egui::Color32::WHITE,
+            );
+        }
+    }
+    if save_scene_clicked {
+        if let Err(error) = save_scene(globals, point_lights, spot_lights, directional_lights) {
+            println!("warning: failed to save the scene: {}", error);
+        }
+    }
+    if load_scene_clicked {
+        if let Err(error) = load_scene(globals, point_lights, spot_lights, directional_lights) {
+            println!("warning: failed to load the scene: {}", error);
+        }
+    }
 
     unsafe {
         gl::Disable(gl::DEPTH_TEST);
@@ -728,6 +1493,171 @@ pub fn draw_ui(
         .paint_jobs(None, textures_delta, paint_jobs);
 }
 
+/// renders config.frames frames into an offscreen framebuffer, a 60th of a second apart, and
+/// writes the last one to config.output
+pub fn render_headless(
+    config: &Config,
+    globals: &mut Globals,
+    renderer: &mut Renderer,
+    point_lights: &mut Vec<PointLight>,
+    spot_lights: &mut Vec<SpotLight>,
+    directional_lights: &mut Vec<DirectionalLight>,
+) -> Result<(), EngineError> {
+    let image = render_offscreen(
+        globals,
+        renderer,
+        point_lights,
+        spot_lights,
+        directional_lights,
+        config.frames,
+    )?;
+    let output = PathBuf::from(&config.output);
+    image
+        .save(&output)
+        .map_err(|source| EngineError::ImageWrite {
+            path: output.clone(),
+            source,
+        })?;
+    println!("wrote {}", output.display());
+    return Ok(());
+}
+
+/// renders frames into a framebuffer the size of the renderer and reads the last one back
+pub fn render_offscreen(
+    globals: &mut Globals,
+    renderer: &mut Renderer,
+    point_lights: &mut Vec<PointLight>,
+    spot_lights: &mut Vec<SpotLight>,
+    directional_lights: &mut Vec<DirectionalLight>,
+    frames: u32,
+) -> Result<RgbaImage, EngineError> {
+    let (target_texture, target, _target_depth) = create_render_target(renderer.size)?;
+    for frame in 0..frames {
+        renderer.render(
+            globals,
+            point_lights,
+            spot_lights,
+            directional_lights,
+            frame as f32 / 60.0,
+            Some(&target),
+        );
+    }
+    target.unbind();
+    return Ok(target_texture.read_pixels());
+}
+
+/// writes a screenshot if one was asked for, and the frame of the recording if there is one
+pub fn capture_frame(capture: &mut Capture, renderer: &Renderer) -> Result<(), EngineError> {
+    if let Some(path) = capture.take_screenshot_path() {
+        save_capture(renderer.read_capture(capture.source), &path)?;
+        println!("saved {}", path.display());
+    }
+    if let Some(path) = capture.next_recording_path() {
+        save_capture(renderer.read_capture(capture.source), &path)?;
+    }
+    return Ok(());
+}
+
+/// writes the models, lights, camera, scene graph and settings to globals.scene_path
+pub fn save_scene(
+    globals: &mut Globals,
+    point_lights: &mut Vec<PointLight>,
+    spot_lights: &mut Vec<SpotLight>,
+    directional_lights: &mut Vec<DirectionalLight>,
+) -> Result<(), EngineError> {
+    let settings = SceneSettings::from_globals(globals);
+    let scene = Scene::capture(
+        &globals.scene_graph,
+        &SceneObjects {
+            models: &mut globals.models,
+            instanced_models: &mut globals.instanced_models,
+            point_lights,
+            spot_lights,
+            directional_lights,
+            camera: &mut globals.cam,
+        },
+        settings,
+    );
+    return scene.save(Path::new(&globals.scene_path));
+}
+
+/// replaces the scene with the one in globals.scene_path, the current one is kept if it fails to load
+pub fn load_scene(
+    globals: &mut Globals,
+    point_lights: &mut Vec<PointLight>,
+    spot_lights: &mut Vec<SpotLight>,
+    directional_lights: &mut Vec<DirectionalLight>,
+) -> Result<(), EngineError> {
+    let scene = Scene::load(Path::new(&globals.scene_path))?;
+    scene.instantiate(
+        &mut globals.scene_graph,
+        &mut SceneObjects {
+            models: &mut globals.models,
+            instanced_models: &mut globals.instanced_models,
+            point_lights,
+            spot_lights,
+            directional_lights,
+            camera: &mut globals.cam,
+        },
+        &mut globals.assets,
+    )?;
+    scene.settings.apply(globals);
+    return Ok(());
+}
+
+/// scatters point lights without shadows over the scene, to compare the per light and clustered passes
+pub fn add_test_point_lights(point_lights: &mut Vec<PointLight>, count: usize) {
+    // a small lcg so every run places the same lights
+    let mut seed: u32 = 0x9e3779b9 ^ point_lights.len() as u32;
+    let mut random = || {
+        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        (seed >> 8) as f32 / (1 << 24) as f32
+    };
+    for _ in 0..count {
+        // without shadows the light never makes a shadow cubemap
+        let mut point_light = PointLight::new(512);
+        point_light.cast_shadows = false;
+        point_light.position = Vector3::new(
+            random() * 20.0 - 10.0,
+            random() * 3.0 - 0.5,
+            random() * 20.0 - 10.0,
+        );
+        point_light.info.color = Vector3::new(random(), random(), random());
+        point_light.info.radius = 1.0 + random() * 3.0;
+        point_lights.push(point_light);
+    }
+}
+
+/// a grid of cubes drawn as one instanced model, to stress the geometry and shadow passes
+pub fn add_instanced_cube_stress_test(
+    instanced_models: &mut Vec<InstancedModel>,
+    assets: &mut AssetCache,
+    count: usize,
+) -> Result<(), EngineError> {
+    let mut model = Model::from_obj_file("./models/Cube.obj".to_owned(), assets)?;
+    model.name = format!("{} Instanced Cubes", count);
+    let side = (count as f32).sqrt().ceil() as usize;
+    let spacing = 1.5;
+    let instances: Vec<InstanceData> = (0..count)
+        .map(|i| {
+            let (x, z) = ((i % side) as f32, (i / side) as f32);
+            let offset = side as f32 * spacing / 2.0;
+            // a gentle wave so the cubes shadow each other
+            let height = 3.0 + (x * 0.3).sin() * (z * 0.3).cos();
+            InstanceData {
+                model: Matrix4::from_translation(Vector3::new(
+                    x * spacing - offset,
+                    height,
+                    z * spacing - offset,
+                )) * Matrix4::from_angle_y(Rad(i as f32 * 0.37))
+                    * Matrix4::from_scale(0.4),
+            }
+        })
+        .collect();
+    instanced_models.push(InstancedModel::new(model, instances));
+    return Ok(());
+}
+
 pub fn remake_framebuffer(framebuffer: &FrameBuffer, texture: &Texture, size: (u32, u32)) {
     framebuffer.bind();
     texture.bind();
@@ -746,6 +1676,21 @@ pub fn remake_framebuffer(framebuffer: &FrameBuffer, texture: &Texture, size: (u
     }
 }
 
+pub fn remake_hdr_framebuffer(framebuffer: &FrameBuffer, texture: &Texture, size: (u32, u32)) {
+    framebuffer.bind();
+    texture.make_empty_hdr(size);
+}
+
+/// a half float color texture, for light that adds up past 1.0
+pub fn create_hdr_framebuffer(size: (u32, u32)) -> (Texture, FrameBuffer) {
+    let framebuffer = FrameBuffer::new();
+    framebuffer.bind();
+    let texture = Texture::new();
+    texture.make_empty_hdr(size);
+    framebuffer.load(&texture).unwrap();
+    return (texture, framebuffer);
+}
+
 pub fn create_framebuffer(size: (u32, u32)) -> (Texture, FrameBuffer) {
     let framebuffer = FrameBuffer::new();
     framebuffer.bind();
@@ -755,11 +1700,23 @@ pub fn create_framebuffer(size: (u32, u32)) -> (Texture, FrameBuffer) {
     return (texture, framebuffer);
 }
 
+/// a color texture with its own depth and stencil, to draw a frame into instead of the window
+pub fn create_render_target(
+    size: (u32, u32),
+) -> Result<(Texture, FrameBuffer, RenderBuffer), EngineError> {
+    let (texture, framebuffer) = create_framebuffer(size);
+    let depth = RenderBuffer::new();
+    depth.load(&framebuffer, size)?;
+    framebuffer.unbind();
+    return Ok((texture, framebuffer, depth));
+}
+
 pub fn remake_gbuffer(
     gbuffer: &FrameBuffer,
     g_position: &Texture,
     g_normal: &Texture,
     g_albedo_spec: &Texture,
+    g_emissive: &Texture,
     g_render_buffer: &RenderBuffer,
     size: (u32, u32),
 ) {
@@ -806,6 +1763,20 @@ pub fn remake_gbuffer(
             null(),
         );
     };
+    g_emissive.bind();
+    unsafe {
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA16F as i32,
+            size.0 as i32,
+            size.1 as i32,
+            0,
+            gl::RGBA,
+            gl::FLOAT,
+            null(),
+        );
+    };
     g_render_buffer.bind();
     unsafe {
         gl::RenderbufferStorage(
@@ -817,7 +1788,9 @@ pub fn remake_gbuffer(
     }
 }
 
-pub fn create_gbuffer(size: (u32, u32)) -> (Texture, Texture, Texture, FrameBuffer, RenderBuffer) {
+pub fn create_gbuffer(
+    size: (u32, u32),
+) -> (Texture, Texture, Texture, Texture, FrameBuffer, RenderBuffer) {
     let gbuffer = FrameBuffer::new();
     gbuffer.bind();
     let g_position = Texture::new();
@@ -904,14 +1877,43 @@ pub fn create_gbuffer(size: (u32, u32)) -> (Texture, Texture, Texture, FrameBuff
             0,
         );
     };
+    let g_emissive = Texture::new();
+    g_emissive.bind();
+    unsafe {
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA16F as i32,
+            size.0 as i32,
+            size.1 as i32,
+            0,
+            gl::RGBA,
+            gl::FLOAT,
+            null(),
+        );
+    };
+    unsafe {
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+    };
+    unsafe {
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT3,
+            gl::TEXTURE_2D,
+            g_emissive.id,
+            0,
+        );
+    };
 
     unsafe {
         gl::DrawBuffers(
-            3,
+            4,
             [
                 gl::COLOR_ATTACHMENT0,
                 gl::COLOR_ATTACHMENT1,
                 gl::COLOR_ATTACHMENT2,
+                gl::COLOR_ATTACHMENT3,
             ]
             .as_ptr() as *const u32,
         );
@@ -924,6 +1926,7 @@ pub fn create_gbuffer(size: (u32, u32)) -> (Texture, Texture, Texture, FrameBuff
         g_position,
         g_normal,
         g_albedo_spec,
+        g_emissive,
         gbuffer,
         g_render_buffer,
     );
@@ -938,6 +1941,21 @@ pub fn create_framebuffer_depthbuffer(size: (u32, u32)) -> (Texture, FrameBuffer
     return (texture, framebuffer);
 }
 
+pub fn create_framebuffer_depth_texture_array(
+    size: (u32, u32),
+    layers: u32,
+) -> (TextureArray, FrameBuffer) {
+    let framebuffer = FrameBuffer::new();
+    let texture_array = TextureArray::new();
+    texture_array.make_empty_depth_buffer(size, layers).unwrap();
+    framebuffer
+        .load_depth_texture_array_layer(&texture_array, 0)
+        .unwrap();
+    framebuffer.unbind();
+
+    return (texture_array, framebuffer);
+}
+
 pub fn create_framebuffer_depth_cubemap(size: (u32, u32)) -> (Cubemap, FrameBuffer) {
     let framebuffer = FrameBuffer::new();
     let cubemap = Cubemap::new();
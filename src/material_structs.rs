@@ -1,26 +1,114 @@
 use cgmath::Vector3;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+/// which brdf a material is lit with, also written into the g-buffer
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaterialModel {
+    /// blinn-phong with ambient, diffuse, specular and shininess
+    Phong = 0,
+    /// cook-torrance ggx with base color, metallic and roughness
+    MetallicRoughness = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct MaterialInfo {
+    pub model: MaterialModel,
+
+    #[serde(with = "crate::serde_vectors::vector3")]
     pub ambient: Vector3<f32>,
+    #[serde(with = "crate::serde_vectors::vector3")]
     pub diffuse: Vector3<f32>,
     pub specular: f32,
 
     pub shininess: f32,
+    /// opacity, the d value of an mtl file
+    pub dissolve: f32,
+
+    /// multiplies the diffuse texture
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub base_color: Vector3<f32>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// how much ambient light reaches the surface, multiplies the occlusion map
+    pub ambient_occlusion: f32,
+    /// light given off by the surface itself, multiplies the emissive map
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub emissive: Vector3<f32>,
+}
+impl Default for MaterialInfo {
+    fn default() -> Self {
+        Self {
+            model: MaterialModel::Phong,
+            ambient: Vector3::new(1.0, 1.0, 1.0),
+            diffuse: Vector3::new(1.0, 1.0, 1.0),
+            specular: 0.5,
+            shininess: 32.0,
+            dissolve: 1.0,
+            base_color: Vector3::new(1.0, 1.0, 1.0),
+            metallic: 0.0,
+            roughness: 0.5,
+            ambient_occlusion: 1.0,
+            emissive: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct DirectionalLightInfo {
+    #[serde(with = "crate::serde_vectors::vector3")]
     pub color: Vector3<f32>,
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct PointLightInfo {
+    #[serde(with = "crate::serde_vectors::vector3")]
     pub color: Vector3<f32>,
 
     pub radius: f32,
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct SpotLightInfo {
+    #[serde(with = "crate::serde_vectors::vector3")]
     pub color: Vector3<f32>,
 
     pub radius: f32,
 }
+
+/// how a light's shadow map is sampled in the lighting pass
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadowFilter {
+    /// a single depth comparison
+    Hard = 0,
+    /// an N×N grid of hardware comparisons
+    Pcf = 1,
+    /// 16 comparisons spread over a poisson disk
+    PoissonPcf = 2,
+    /// percentage-closer soft shadows, penumbras grow with the distance to the blocker
+    Pcss = 3,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ShadowSettings {
+    pub filter: ShadowFilter,
+    /// N of the N×N pcf kernel, also scales the poisson disk
+    pub pcf_kernel_size: i32,
+    /// size of the light used by pcss, in shadow map uv units
+    pub light_size: f32,
+    /// bias added to every depth comparison
+    pub constant_bias: f32,
+    /// bias scaled by the tangent of the angle between the surface and the light
+    pub slope_bias: f32,
+    /// how far in meters the fragment is pushed along its normal before looking it up in the shadow map
+    pub normal_offset: f32,
+}
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            filter: ShadowFilter::Pcf,
+            pcf_kernel_size: 3,
+            light_size: 0.05,
+            constant_bias: 0.0005,
+            slope_bias: 0.001,
+            normal_offset: 0.02,
+        }
+    }
+}
@@ -1,34 +1,121 @@
 use core::f32;
-use std::{ffi::c_void, path::Path};
+use std::{
+    ffi::c_void,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use cgmath::{
     num_traits::zero, perspective, Euler, InnerSpace, Matrix4, PerspectiveFov, Point3, Quaternion,
-    Rad, Rotation3, Vector2, Vector3, Zero,
+    Rad, Rotation3, SquareMatrix, Vector2, Vector3, Vector4, Zero,
 };
 use egui_sdl2_gl::egui::{self, DragValue, Widget};
 
 use crate::{
-    buffers::{FrameBuffer, IndexBuffer, ModelTexture, Texture, VertexArrayBuffer, VertexBuffer}, create_framebuffer_depthbuffer,
+    assets::AssetCache,
+    culling::{BoundingSphere, Bounds, CullingVolume},
+    gltf::{load_gltf, GltfImage},
+    buffers::{
+        Cubemap, FrameBuffer, IndexBuffer, ModelTexture, Texture, TextureArray, VertexArrayBuffer,
+        VertexBuffer,
+    },
+    create_framebuffer_depth_cubemap, create_framebuffer_depth_texture_array,
+    create_framebuffer_depthbuffer,
     draw_scene_custom_shader_program,
+    errors::EngineError,
     globals::Globals,
-    material_structs::{DirectionalLightInfo, MaterialInfo, PointLightInfo, SpotLightInfo},
-    shaders::{create_program, Program},
+    scene_graph::SceneObject,
+    light_volumes::{cone_volume_matrix, sphere_volume_matrix, MAX_CONE_VOLUME_HALF_ANGLE},
+    material_structs::{
+        DirectionalLightInfo, MaterialInfo, MaterialModel, PointLightInfo, ShadowFilter,
+        ShadowSettings, SpotLightInfo,
+    },
+    shaders::Program,
+    tangents::generate_tangents,
+    uniform_blocks::{DirectionalLightData, PointLightData, SpotLightData},
+    vertex_layout::{Vertex, VertexFormat},
 };
 
-pub struct Model {
+/// vertices and indices on the gpu, every model drawn with the same mesh shares one
+pub struct Mesh {
     pub vbo: VertexBuffer,
     pub vao: VertexArrayBuffer,
     pub ibo: IndexBuffer,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// around the vertices, before the model's transform
+    pub bounds: Bounds,
+}
+impl Mesh {
+    /// uploads the vertices and indices straight away
+    pub fn new(vertices: &[Vertex], indices: &[u32]) -> Self {
+        let mesh = Self {
+            vbo: VertexBuffer::new(),
+            vao: VertexArrayBuffer::new(),
+            ibo: IndexBuffer::new(),
+            vertices: vertices.to_vec(),
+            indices: indices.to_vec(),
+            bounds: Bounds::from_points(
+                &vertices
+                    .iter()
+                    .map(|vertex| vertex.position)
+                    .collect::<Vec<_>>(),
+            ),
+        };
+        mesh.vbo.set(&mesh.vertices);
+        mesh.vao.set(&Vertex::layout());
+        mesh.ibo.set(&mesh.indices);
+        return mesh;
+    }
+
+    pub fn bind(&self) {
+        self.vbo.bind();
+        self.vao.bind();
+        self.ibo.bind();
+    }
+
+    /// what the vertex and index buffers take up on the gpu
+    pub fn memory_size(&self) -> usize {
+        self.vertices.len() * std::mem::size_of::<Vertex>()
+            + self.indices.len() * std::mem::size_of::<u32>()
+    }
+}
+
+/// a range of a model's indices that is drawn with its own material
+#[derive(Clone)]
+pub struct SubMesh {
+    pub name: String,
+    /// first index of this sub mesh in the model's indices
+    pub index_offset: usize,
+    pub index_count: usize,
+    pub material_info: MaterialInfo,
+    /// shared between every sub mesh that uses the same material
+    pub diffuse_texture: Rc<Texture>,
+    /// from map_Bump, the model's normal_texture is used when there isn't one
+    pub normal_texture: Option<Rc<Texture>>,
+}
+
+/// the mesh, program and textures are handles, clones of a model share them
+#[derive(Clone)]
+pub struct Model {
+    pub mesh: Rc<Mesh>,
 
     pub shader_program: Program,
-    pub diffuse_texture: Texture,
+    pub diffuse_texture: Rc<Texture>,
+    pub normal_texture: Option<Rc<Texture>>,
+    pub metallic_roughness_texture: Option<Rc<Texture>>,
+    pub occlusion_texture: Option<Rc<Texture>>,
+    pub emissive_texture: Option<Rc<Texture>>,
 
-    pub vertices: Vec<(Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector2<f32>)>,
-    pub indices: Vec<u32>,
+    /// when empty the whole model is drawn at once with material_info and diffuse_texture
+    pub sub_meshes: Vec<SubMesh>,
 
+    /// position, rotation and scale are relative to the model's parent in the scene graph
     pub position: Vector3<f32>,
     pub rotation: Quaternion<f32>,
     pub scale: Vector3<f32>,
+    /// the world matrix of the parent, set by the scene graph
+    pub parent_matrix: Matrix4<f32>,
 
     pub euler_angles: Euler<Rad<f32>>,
     pub using_euler_angles: bool,
@@ -36,124 +123,110 @@ pub struct Model {
     pub material_info: MaterialInfo,
     pub name: String,
     pub render_shadows: bool,
+    /// the file the model was loaded from and which of its models this is, None for models made
+    /// in code. scene files only keep models that have one
+    pub source: Option<(String, usize)>,
 }
 impl Model {
-    pub fn new(
-        vertices: &Vec<(Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector2<f32>)>,
-        indices: &Vec<u32>,
-        vert_shader_path: &str,
-        frag_shader_path: &str,
-        name: String,
-        
-    ) -> Self {
-        let vbo = VertexBuffer::new();
-        let vao = VertexArrayBuffer::new();
-        let ibo = IndexBuffer::new();
-
+    pub fn new(mesh: Rc<Mesh>, shader_program: Program, name: String) -> Self {
         return Self {
-            vbo: vbo,
-            vao: vao,
-            ibo: ibo,
-            shader_program: create_program(vert_shader_path, frag_shader_path).unwrap(),
-            diffuse_texture: Texture::new(),
-            vertices: vertices.clone(),
-            indices: indices.clone(),
+            mesh,
+            shader_program,
+            diffuse_texture: Rc::new(Texture::new()),
+            normal_texture: None,
+            metallic_roughness_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+            sub_meshes: Vec::new(),
             position: Vector3::zero(),
             rotation: Quaternion::zero(),
             scale: Vector3::new(1., 1., 1.),
+            parent_matrix: Matrix4::identity(),
             euler_angles: Euler {
                 x: Rad(0.),
                 y: Rad(0.),
                 z: Rad(0.),
             },
             using_euler_angles: false,
-            material_info: MaterialInfo {
-                ambient: Vector3::new(1.0, 1.0, 1.0),
-                diffuse: Vector3::new(1.0, 1.0, 1.0),
-                specular: 0.5,
-                shininess: 32.0,
-            },
+            material_info: MaterialInfo::default(),
             name,
             render_shadows: true,
+            source: None,
         };
     }
-    pub fn start(&self) {
-        self.vbo.set(&self.vertices);
-        self.vao.set();
-        self.ibo.set(&self.indices);
+
+    /// every texture the model and its sub meshes hold a handle to
+    pub fn textures(&self) -> Vec<&Rc<Texture>> {
+        let mut textures: Vec<&Rc<Texture>> = vec![&self.diffuse_texture];
+        textures.extend(
+            [
+                &self.normal_texture,
+                &self.metallic_roughness_texture,
+                &self.occlusion_texture,
+                &self.emissive_texture,
+            ]
+            .into_iter()
+            .flatten(),
+        );
+        for sub_mesh in &self.sub_meshes {
+            textures.push(&sub_mesh.diffuse_texture);
+            textures.extend(&sub_mesh.normal_texture);
+        }
+        return textures;
     }
+
     pub fn start_render(&mut self) {
         self.shader_program.set();
-        self.vbo.bind();
         unsafe {
             gl::ActiveTexture(gl::TEXTURE0);
         }
         self.diffuse_texture.bind_texture();
-        self.vao.bind();
-        self.ibo.bind();
+        self.mesh.bind();
         if self.using_euler_angles {
             self.rotation = self.euler_angles.into();
         }
     }
     pub fn start_render_custom_shader_program(&mut self, shader_program: &Program) {
         shader_program.set();
-        self.vbo.bind();
         unsafe {
             gl::ActiveTexture(gl::TEXTURE0);
         }
         self.diffuse_texture.bind_texture();
-        self.vao.bind();
-        self.ibo.bind();
+        self.mesh.bind();
         if self.using_euler_angles {
             self.rotation = self.euler_angles.into();
         }
     }
 
-    pub fn render(&self, screen_size: Vector2<f32>, view_matrix: Matrix4<f32>, projection_matrix: Matrix4<f32>) {
-        let model_matrix: Matrix4<f32> =
-            transforms_to_matrix(self.position, self.rotation, self.scale);
+    /// the world matrix, through every parent in the scene graph
+    pub fn model_matrix(&self) -> Matrix4<f32> {
+        self.parent_matrix * self.local_matrix()
+    }
+
+    /// the mesh's bounds moved to where the model is
+    pub fn world_bounds(&self) -> Bounds {
+        self.mesh.bounds.transformed(&self.model_matrix())
+    }
+
+    /// the camera comes from the Camera uniform block
+    pub fn render(&self) {
+        let model_matrix: Matrix4<f32> = self.model_matrix();
         self.shader_program.set_matrix4_float("model", model_matrix);
-        self.shader_program.set_matrix4_float("view", view_matrix);
-        self.shader_program
-            .set_matrix4_float("projection", projection_matrix);
 
         self.shader_program.set_bool("renderFullbright", false);
         self.shader_program.set_int("texture1", 1);
         self.shader_program.set_int("texture2", 2);
         self.shader_program.set_int("texture3", 3);
-        self.shader_program.set_float("uWidth", screen_size.x);
-        self.shader_program.set_float("uHeight", screen_size.y);
-        unsafe {
-            gl::DrawElements(
-                gl::TRIANGLES,
-                self.indices.len() as i32,
-                gl::UNSIGNED_INT,
-                0 as *const c_void,
-            );
-        }
+        self.draw(&self.shader_program, true);
     }
 
-    pub fn render_fullbright(&self, screen_size: Vector2<f32>, view_matrix: Matrix4<f32>, projection_matrix: Matrix4<f32>) {
-        let model_matrix: Matrix4<f32> =
-            transforms_to_matrix(self.position, self.rotation, self.scale);
+    pub fn render_fullbright(&self) {
+        let model_matrix: Matrix4<f32> = self.model_matrix();
         self.shader_program.set_matrix4_float("model", model_matrix);
-        self.shader_program.set_matrix4_float("view", view_matrix);
-        self.shader_program
-            .set_matrix4_float("projection", projection_matrix);
 
         self.shader_program.set_bool("renderFullbright", true);
 
-        self.shader_program.set_float("uWidth", screen_size.x);
-        self.shader_program.set_float("uHeight", screen_size.y);
-
-        unsafe {
-            gl::DrawElements(
-                gl::TRIANGLES,
-                self.indices.len() as i32,
-                gl::UNSIGNED_INT,
-                0 as *const c_void,
-            );
-        }
+        self.draw(&self.shader_program, true);
     }
 
     pub fn render_custom_shader_program(
@@ -162,19 +235,121 @@ impl Model {
         projection_matrix: Matrix4<f32>,
         shader_program: &Program,
     ) {
-        let model_matrix: Matrix4<f32> =
-            transforms_to_matrix(self.position, self.rotation, self.scale);
+        let model_matrix: Matrix4<f32> = self.model_matrix();
         shader_program.set_matrix4_float("model", model_matrix);
         shader_program.set_matrix4_float("view", view_matrix);
         shader_program.set_matrix4_float("projection", projection_matrix);
 
+        self.draw(shader_program, false);
+    }
+
+    /// draws with another program and model matrix, without materials, the camera comes from the Camera block
+    pub fn render_with_model_matrix(&self, shader_program: &Program, model_matrix: Matrix4<f32>) {
+        shader_program.set_matrix4_float("model", model_matrix);
+        self.draw(shader_program, false);
+    }
+
+    /// binds the optional metallic roughness, occlusion and emissive maps to units 1 to 3,
+    /// the shader only samples the ones that are there
+    fn bind_material_maps(&self, shader_program: &Program) {
+        let maps = [
+            (
+                "metallicRoughnessMap",
+                "hasMetallicRoughnessMap",
+                &self.metallic_roughness_texture,
+            ),
+            ("occlusionMap", "hasOcclusionMap", &self.occlusion_texture),
+            ("emissiveMap", "hasEmissiveMap", &self.emissive_texture),
+        ];
+        for (i, (sampler_name, has_name, texture)) in maps.into_iter().enumerate() {
+            let unit = i as u32 + 1;
+            if let Some(texture) = texture {
+                unsafe {
+                    gl::ActiveTexture(gl::TEXTURE0 + unit);
+                }
+                texture.bind_texture();
+                shader_program.set_int(sampler_name, unit as i32);
+            }
+            shader_program.set_bool(has_name, texture.is_some());
+        }
+        Self::bind_normal_map(shader_program, self.normal_texture.as_deref());
         unsafe {
-            gl::DrawElements(
-                gl::TRIANGLES,
-                self.indices.len() as i32,
-                gl::UNSIGNED_INT,
-                0 as *const c_void,
-            );
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+
+    /// binds a tangent space normal map to unit 4, without one the shader keeps the vertex normal
+    fn bind_normal_map(shader_program: &Program, normal_texture: Option<&Texture>) {
+        if let Some(normal_texture) = normal_texture {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE4);
+            }
+            normal_texture.bind_texture();
+            shader_program.set_int("normalMap", 4);
+        }
+        shader_program.set_bool("hasNormalMap", normal_texture.is_some());
+    }
+
+    /// draws every sub mesh, binding its texture and material first when bind_materials is set
+    fn draw(&self, shader_program: &Program, bind_materials: bool) {
+        self.draw_instanced(shader_program, bind_materials, None);
+    }
+
+    /// like draw, but with instances the vertex array has to have that many instance transforms,
+    /// the shader's instanced uniform says whether to use them on top of model
+    pub fn draw_instanced(
+        &self,
+        shader_program: &Program,
+        bind_materials: bool,
+        instances: Option<usize>,
+    ) {
+        // shaders without instancing don't declare it, and would warn about it every frame
+        if shader_program.has_uniform("instanced") {
+            shader_program.set_bool("instanced", instances.is_some());
+        }
+        let instance_count = instances.unwrap_or(1) as i32;
+        if bind_materials {
+            self.bind_material_maps(shader_program);
+        }
+        if self.sub_meshes.is_empty() {
+            if bind_materials {
+                shader_program.set_material_info("material", self.material_info);
+            }
+            unsafe {
+                gl::DrawElementsInstanced(
+                    gl::TRIANGLES,
+                    self.mesh.indices.len() as i32,
+                    gl::UNSIGNED_INT,
+                    0 as *const c_void,
+                    instance_count,
+                );
+            }
+            return;
+        }
+        for sub_mesh in &self.sub_meshes {
+            if bind_materials {
+                Self::bind_normal_map(
+                    shader_program,
+                    sub_mesh
+                        .normal_texture
+                        .as_deref()
+                        .or(self.normal_texture.as_deref()),
+                );
+                unsafe {
+                    gl::ActiveTexture(gl::TEXTURE0);
+                }
+                sub_mesh.diffuse_texture.bind_texture();
+                shader_program.set_material_info("material", sub_mesh.material_info);
+            }
+            unsafe {
+                gl::DrawElementsInstanced(
+                    gl::TRIANGLES,
+                    sub_mesh.index_count as i32,
+                    gl::UNSIGNED_INT,
+                    (sub_mesh.index_offset * std::mem::size_of::<u32>()) as *const c_void,
+                    instance_count,
+                );
+            }
         }
     }
 
@@ -187,29 +362,85 @@ impl Model {
         self.shader_program
             .set_point_light_info("pointLights[0]", light, light_position);
 
-        self.shader_program.set_vector3("viewPos", cam.position);
+        self.shader_program.set_vector3("viewPos", cam.world_position());
 
         self.shader_program
             .set_material_info("material", self.material_info);
     }
 
-    pub fn from_obj_file(obj_file: String) -> Self {
+    /// loads every object of an obj file into one model, each object becomes a sub mesh with its own material
+    /// missing normals are generated, missing uvs are zero and textures that fail to load become a checkerboard.
+    /// tangents are always generated, obj files don't have them. a file that was loaded before is copied
+    /// from the asset cache instead, sharing its mesh and textures
+    pub fn from_obj_file(obj_file: String, assets: &mut AssetCache) -> Result<Self, EngineError> {
+        if let Some(models) = assets.loaded_file(Path::new(&obj_file)) {
+            return Ok(models[0].clone());
+        }
         let mut load_options = tobj::LoadOptions::default();
         load_options.triangulate = true;
         load_options.single_index = true;
         let (models, materials) =
-            tobj::load_obj(&obj_file, &load_options).expect("Failed to OBJ load file");
+            tobj::load_obj(&obj_file, &load_options).map_err(|source| EngineError::ObjLoad {
+                path: PathBuf::from(&obj_file),
+                source,
+            })?;
 
-        let mut vertices: Vec<(Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector2<f32>)> =
-            Vec::new();
+        let mut vertices: Vec<Vertex> = Vec::new();
         let mut indices: Vec<u32> = Vec::new();
+        let mut sub_meshes: Vec<SubMesh> = Vec::new();
 
-        // Note: If you don't mind missing the materials, you can generate a default.
-        let materials = materials.expect("Failed to load MTL file");
+        let materials = match materials {
+            Ok(materials) => materials,
+            Err(error) => {
+                println!(
+                    "warning: failed to load the mtl file of {}: {}, using the default material",
+                    obj_file, error
+                );
+                Vec::new()
+            }
+        };
 
         println!("Number of models          = {}", models.len());
         println!("Number of materials       = {}", materials.len());
 
+        // every material gets its texture loaded once, then the sub meshes using it share it
+        let material_infos: Vec<MaterialInfo> =
+            materials.iter().map(material_info_from_obj).collect();
+        let material_textures: Vec<Rc<Texture>> = materials
+            .iter()
+            .zip(&material_infos)
+            .map(|(material, material_info)| {
+                println!("material.name = \'{}\'", material.name);
+                match &material.diffuse_texture {
+                    Some(diffuse_texture) => {
+                        println!("    material.map_Kd = {}", diffuse_texture);
+                        assets
+                            .texture(&resolve_texture_path(&obj_file, diffuse_texture))
+                            .unwrap_or_else(|error| fall_back_to_checkerboard(assets, error))
+                    }
+                    None => assets.solid_color_texture(color_to_bytes(material_info.diffuse)),
+                }
+            })
+            .collect();
+        // a normal map that fails to load is left out, a checkerboard would make a mess of the lighting
+        let material_normal_textures: Vec<Option<Rc<Texture>>> = materials
+            .iter()
+            .map(|material| {
+                let normal_texture = material.normal_texture.as_ref()?;
+                println!("    material.map_Bump = {}", normal_texture);
+                // options like -bm 1.0 come before the file name
+                let file_name = normal_texture.split_whitespace().last()?;
+                match assets.texture(&resolve_texture_path(&obj_file, file_name)) {
+                    Ok(texture) => Some(texture),
+                    Err(error) => {
+                        println!("warning: {}, skipping the normal map", error);
+                        None
+                    }
+                }
+            })
+            .collect();
+        let default_texture = assets.solid_color_texture([255, 255, 255, 255]);
+
         for (i, m) in models.iter().enumerate() {
             let mesh = &m.mesh;
             println!("");
@@ -217,244 +448,316 @@ impl Model {
             println!("model[{}].mesh.material_id = {:?}", i, mesh.material_id);
 
             println!("model[{}].face_count       = {}", i, mesh.indices.len() / 3,);
-            assert!(mesh.indices.len() % 3 == 0);
-
-            let mut next_face = 0;
-            for _face in 0..mesh.indices.len() / 3 {
-                let end = next_face + 3 as usize;
-
-                let face_indices = &mesh.indices[next_face..end];
-                /*println!(" face[{}].indices          = {:?}", face, face_indices);*/
-                indices.extend_from_slice(face_indices);
-
-                /*if !mesh.texcoord_indices.is_empty() {
-                    let texcoord_face_indices = &mesh.texcoord_indices[next_face..end];
-                    println!(
-                        " face[{}].texcoord_indices = {:?}",
-                        face, texcoord_face_indices
-                    );
-                }
-                if !mesh.normal_indices.is_empty() {
-                    let normal_face_indices = &mesh.normal_indices[next_face..end];
-                    println!(
-                        " face[{}].normal_indices   = {:?}",
-                        face, normal_face_indices
-                    );
-                }*/
-
-                next_face = end;
+            let vertex_count = mesh.positions.len() / 3;
+            if mesh.indices.len() % 3 != 0
+                || mesh.positions.len() % 3 != 0
+                || mesh.indices.iter().any(|index| *index as usize >= vertex_count)
+            {
+                return Err(EngineError::ObjLoad {
+                    path: PathBuf::from(&obj_file),
+                    source: tobj::LoadError::FaceVertexOutOfBounds,
+                });
             }
-
-            // Normals and texture coordinates are also loaded, but not printed in
-            // this example.
-            /*println!(
-                "model[{}].positions        = {}",
-                i,
-                mesh.positions.len() / 3
-            );*/
-            assert!(mesh.positions.len() % 3 == 0);
-
-            /*println!(
-                "model[{}].vertex_color        = {}",
-                i,
-                mesh.vertex_color.len() / 3
-            );*/
-            assert!(mesh.vertex_color.len() % 3 == 0);
-
-            /*println!(
-                "model[{}].texcoords        = {}",
-                i,
-                mesh.texcoords.len() / 2
-            );*/
-            assert!(mesh.texcoords.len() % 2 == 0);
-            if mesh.vertex_color.len() != 0 {
-                for vtx in 0..mesh.positions.len() / 3 {
-                    vertices.push((
-                        Vector3::new(
-                            mesh.positions[3 * vtx],
-                            mesh.positions[3 * vtx + 1],
-                            mesh.positions[3 * vtx + 2],
-                        ),
+            let has_colors = mesh.vertex_color.len() == vertex_count * 3;
+            let has_texcoords = mesh.texcoords.len() == vertex_count * 2;
+            let positions: Vec<Vector3<f32>> = mesh
+                .positions
+                .chunks_exact(3)
+                .map(|position| Vector3::new(position[0], position[1], position[2]))
+                .collect();
+            let normals: Vec<Vector3<f32>> = if mesh.normals.len() == vertex_count * 3 {
+                mesh.normals
+                    .chunks_exact(3)
+                    .map(|normal| Vector3::new(normal[0], normal[1], normal[2]))
+                    .collect()
+            } else {
+                println!("model[{}] has no normals, generating them", i);
+                generate_normals(&positions, &mesh.indices)
+            };
+            if !has_texcoords {
+                println!("model[{}] has no texcoords, defaulting them to 0", i);
+            }
+            let texcoords: Vec<Vector2<f32>> = if has_texcoords {
+                mesh.texcoords
+                    .chunks_exact(2)
+                    .map(|texcoord| Vector2::new(texcoord[0], texcoord[1]))
+                    .collect()
+            } else {
+                vec![Vector2::zero(); vertex_count]
+            };
+            let mut mesh_vertices: Vec<Vertex> = (0..vertex_count)
+                .map(|vtx| {
+                    let color = if has_colors {
                         Vector3::new(
                             mesh.vertex_color[3 * vtx],
                             mesh.vertex_color[3 * vtx + 1],
                             mesh.vertex_color[3 * vtx + 2],
-                        ),
-                        Vector3::new(
-                            mesh.normals[3 * vtx],
-                            mesh.normals[3 * vtx + 1],
-                            mesh.normals[3 * vtx + 2],
-                        ),
-                        Vector2::new(mesh.texcoords[2 * vtx], mesh.texcoords[2 * vtx + 1]),
-                    ));
-                    println!(
-                        "              position[{}] = ({}, {}, {})",
-                        vtx,
-                        mesh.positions[3 * vtx],
-                        mesh.positions[3 * vtx + 1],
-                        mesh.positions[3 * vtx + 2]
-                    );
-                    println!(
-                        "              vertex_color[{}] = ({}, {}, {})",
-                        vtx,
-                        mesh.vertex_color[3 * vtx],
-                        mesh.vertex_color[3 * vtx + 1],
-                        mesh.vertex_color[3 * vtx + 2]
-                    );
-                    println!(
-                        "              texcoords[{}] = ({}, {})",
-                        vtx,
-                        mesh.texcoords[2 * vtx],
-                        mesh.texcoords[2 * vtx + 1],
-                    );
-                    println!(
-                        "              normals[{}] = ({}, {}, {})",
-                        vtx,
-                        mesh.normals[3 * vtx],
-                        mesh.normals[3 * vtx + 1],
-                        mesh.normals[3 * vtx + 2]
-                    );
-                }
-            } else {
-                for vtx in 0..mesh.positions.len() / 3 {
-                    vertices.push((
-                        Vector3::new(
-                            mesh.positions[3 * vtx],
-                            mesh.positions[3 * vtx + 1],
-                            mesh.positions[3 * vtx + 2],
-                        ),
-                        Vector3::new(1., 1., 1.),
-                        Vector3::new(
-                            mesh.normals[3 * vtx],
-                            mesh.normals[3 * vtx + 1],
-                            mesh.normals[3 * vtx + 2],
-                        ),
-                        Vector2::new(mesh.texcoords[2 * vtx], mesh.texcoords[2 * vtx + 1]),
-                    ));
-                    println!(
-                        "              position[{}] = ({}, {}, {})",
-                        vtx,
-                        mesh.positions[3 * vtx],
-                        mesh.positions[3 * vtx + 1],
-                        mesh.positions[3 * vtx + 2]
-                    );
-                    println!(
-                        "              vertex_color[{}] = ({}, {}, {})",
-                        vtx, 1., 1., 1.
-                    );
-                    println!(
-                        "              texcoords[{}] = ({}, {})",
-                        vtx,
-                        mesh.texcoords[2 * vtx],
-                        mesh.texcoords[2 * vtx + 1],
-                    );
-                    println!(
-                        "              normals[{}] = ({}, {}, {})",
-                        vtx,
-                        mesh.normals[3 * vtx],
-                        mesh.normals[3 * vtx + 1],
-                        mesh.normals[3 * vtx + 2]
-                    );
-                }
-            }
+                        )
+                    } else {
+                        Vector3::new(1., 1., 1.)
+                    };
+                    Vertex {
+                        position: positions[vtx],
+                        color,
+                        normal: normals[vtx],
+                        uv: texcoords[vtx],
+                        tangent: Vector4::zero(),
+                    }
+                })
+                .collect();
+            // this can split vertices, so it comes before the indices are offset
+            let mut mesh_indices = mesh.indices.clone();
+            generate_tangents(&mut mesh_vertices, &mut mesh_indices);
+
+            // every mesh indexes its own vertices, so offset them past the meshes before it
+            let base_vertex = vertices.len() as u32;
+            let index_offset = indices.len();
+            indices.extend(mesh_indices.iter().map(|index| index + base_vertex));
+            vertices.extend(mesh_vertices);
+
+            let (material_info, diffuse_texture, normal_texture) = match mesh.material_id {
+                Some(material_id) if material_id < materials.len() => (
+                    material_infos[material_id],
+                    material_textures[material_id].clone(),
+                    material_normal_textures[material_id].clone(),
+                ),
+                _ => (MaterialInfo::default(), default_texture.clone(), None),
+            };
+            sub_meshes.push(SubMesh {
+                name: m.name.clone(),
+                index_offset,
+                index_count: mesh.indices.len(),
+                material_info,
+                diffuse_texture,
+                normal_texture,
+            });
+        }
+
+        let name = Path::new(&obj_file)
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or(obj_file.clone());
+        let mesh = assets.add_mesh(obj_file.clone(), Mesh::new(&vertices, &indices));
+        let shader_program = assets.program(
+            "./shaders/BasicModel/shader.vert",
+            "./shaders/BasicModel/shader.frag",
+        )?;
+        let mut model = Model::new(mesh, shader_program, name);
+        model.source = Some((obj_file.clone(), 0));
+        if let Some(first) = sub_meshes.first() {
+            model.material_info = first.material_info;
         }
+        model.sub_meshes = sub_meshes;
 
-        let mut diffuse_texture_path: String = String::new();
+        assets.add_loaded_file(Path::new(&obj_file), &[model.clone()]);
+        return Ok(model);
+    }
 
-        for (i, m) in materials.iter().enumerate() {
-            println!("material[{}].name = \'{}\'", i, m.name);
-            println!(
-                "    material.Ka = ({}, {}, {})",
-                m.ambient.unwrap()[0],
-                m.ambient.unwrap()[1],
-                m.ambient.unwrap()[2]
+    /// loads every mesh primitive of a .gltf or .glb file as its own model,
+    /// placed where the node hierarchy puts it. like obj files, a file is only loaded once
+    pub fn from_gltf_file(
+        gltf_file: String,
+        assets: &mut AssetCache,
+    ) -> Result<Vec<Self>, EngineError> {
+        let gltf_path = Path::new(&gltf_file);
+        if let Some(models) = assets.loaded_file(gltf_path) {
+            return Ok(models);
+        }
+        let primitives = load_gltf(gltf_path)?;
+        let mut models: Vec<Model> = Vec::new();
+        for (i, primitive) in primitives.into_iter().enumerate() {
+            let mesh = assets.add_mesh(
+                format!("{}#{}", gltf_file, i),
+                Mesh::new(&primitive.vertices, &primitive.indices),
             );
-            if m.diffuse.is_some() {
-                println!(
-                    "    material.Kd = ({}, {}, {})",
-                    m.diffuse.unwrap()[0],
-                    m.diffuse.unwrap()[1],
-                    m.diffuse.unwrap()[2]
-                );
-            }
-            println!(
-                "    material.Ks = ({}, {}, {})",
-                m.specular.unwrap()[0],
-                m.specular.unwrap()[1],
-                m.specular.unwrap()[2]
+            let shader_program = assets.program(
+                "./shaders/BasicModel/shader.vert",
+                "./shaders/BasicModel/shader.frag",
+            )?;
+            let mut model = Model::new(mesh, shader_program, primitive.name);
+            model.source = Some((gltf_file.clone(), i));
+            model.position = primitive.position;
+            model.rotation = primitive.rotation;
+            model.scale = primitive.scale;
+
+            let material = &primitive.material;
+            let base_color = material.base_color_factor;
+            let emissive = material.emissive_factor;
+            model.material_info.model = MaterialModel::MetallicRoughness;
+            model.material_info.diffuse = Vector3::new(base_color[0], base_color[1], base_color[2]);
+            model.material_info.base_color = model.material_info.diffuse;
+            model.material_info.metallic = material.metallic_factor;
+            model.material_info.roughness = material.roughness_factor;
+            model.material_info.emissive = Vector3::new(emissive[0], emissive[1], emissive[2]);
+            // the base color factor multiplies the texture in the shader, so without one it's white
+            model.diffuse_texture = match &material.base_color_texture {
+                Some(image) => load_gltf_texture(assets, image, gltf_path)
+                    .unwrap_or_else(|error| fall_back_to_checkerboard(assets, error)),
+                None => assets.solid_color_texture([255, 255, 255, 255]),
+            };
+            // a broken normal, metallic roughness, occlusion or emissive map is left out
+            // rather than drawn as a checkerboard
+            model.normal_texture =
+                load_optional_gltf_texture(assets, &material.normal_texture, gltf_path, "normal");
+            model.metallic_roughness_texture = load_optional_gltf_texture(
+                assets,
+                &material.metallic_roughness_texture,
+                gltf_path,
+                "metallic roughness",
+            );
+            model.occlusion_texture = load_optional_gltf_texture(
+                assets,
+                &material.occlusion_texture,
+                gltf_path,
+                "occlusion",
+            );
+            model.emissive_texture = load_optional_gltf_texture(
+                assets,
+                &material.emissive_texture,
+                gltf_path,
+                "emissive",
             );
-            println!("    material.Ns = {}", m.shininess.unwrap());
-            println!("    material.d = {}", m.dissolve.unwrap());
-            if m.ambient_texture.is_some() {
-                println!(
-                    "    material.map_Ka = {}",
-                    m.ambient_texture.clone().unwrap()
-                );
-            }
-            if m.diffuse_texture.is_some() {
-                println!(
-                    "    material.map_Kd = {}",
-                    m.diffuse_texture.clone().unwrap()
-                );
-                diffuse_texture_path = m.diffuse_texture.clone().unwrap();
-                diffuse_texture_path = diffuse_texture_path.replacen("..", ".", 1);
-            }
-            if m.specular_texture.is_some() {
-                println!(
-                    "    material.map_Ks = {}",
-                    m.specular_texture.clone().unwrap()
-                );
-            }
-            if m.shininess_texture.is_some() {
-                println!(
-                    "    material.map_Ns = {}",
-                    m.shininess_texture.clone().unwrap()
-                );
-            }
-            if m.normal_texture.is_some() {
-                println!(
-                    "    material.map_Bump = {}",
-                    m.normal_texture.clone().unwrap()
-                );
-            }
-            if m.dissolve_texture.is_some() {
-                println!(
-                    "    material.map_d = {}",
-                    m.dissolve_texture.clone().unwrap()
-                );
-            }
 
-            for (k, v) in &m.unknown_param {
-                println!("    material.{} = {}", k, v);
-            }
+            models.push(model);
         }
+        assets.add_loaded_file(gltf_path, &models);
+        return Ok(models);
+    }
+}
 
-        println!("{:#?}", vertices);
-        println!("{:#?}", indices);
+/// turns an mtl material into the material uniform, anything missing keeps the default
+fn material_info_from_obj(material: &tobj::Material) -> MaterialInfo {
+    let default = MaterialInfo::default();
+    let to_vector = |color: [f32; 3]| Vector3::new(color[0], color[1], color[2]);
+    // tobj keeps the parameters it doesn't know as text
+    let parameter = |key: &str| -> Option<Vec<f32>> {
+        let value = material.unknown_param.get(key)?;
+        value
+            .split_whitespace()
+            .map(|number| number.parse::<f32>().ok())
+            .collect()
+    };
+    let roughness = parameter("Pr").and_then(|values| values.first().copied());
+    let metallic = parameter("Pm").and_then(|values| values.first().copied());
+    MaterialInfo {
+        ambient: material.ambient.map(to_vector).unwrap_or(default.ambient),
+        diffuse: material.diffuse.map(to_vector).unwrap_or(default.diffuse),
+        // the g-buffer only has room for one specular value
+        specular: material
+            .specular
+            .map(|specular| (specular[0] + specular[1] + specular[2]) / 3.0)
+            .unwrap_or(default.specular),
+        shininess: material.shininess.unwrap_or(default.shininess),
+        dissolve: material.dissolve.unwrap_or(default.dissolve),
+        // the pbr extension of mtl, a material with Pr or Pm is shaded as metallic roughness
+        model: if roughness.is_some() || metallic.is_some() {
+            MaterialModel::MetallicRoughness
+        } else {
+            MaterialModel::Phong
+        },
+        base_color: default.base_color,
+        metallic: metallic.unwrap_or(default.metallic),
+        roughness: roughness.unwrap_or(default.roughness),
+        ambient_occlusion: default.ambient_occlusion,
+        emissive: match parameter("Ke").as_deref() {
+            Some([r, g, b]) => Vector3::new(*r, *g, *b),
+            _ => default.emissive,
+        },
+    }
+}
 
-        let name = Path::new(&obj_file)
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .replace(".obj", "");
-        let texture = Texture::new();
-        texture.load(&Path::new(&diffuse_texture_path)).unwrap();
-        let mut model = Model::new(
-            &vertices,
-            &indices,
-            "./shaders/BasicModel/shader.vert",
-            "./shaders/BasicModel/shader.frag",
-            name,
-        );
-        model.diffuse_texture = texture;
+fn color_to_bytes(color: Vector3<f32>) -> [u8; 4] {
+    [
+        (color.x.clamp(0.0, 1.0) * 255.0) as u8,
+        (color.y.clamp(0.0, 1.0) * 255.0) as u8,
+        (color.z.clamp(0.0, 1.0) * 255.0) as u8,
+        255,
+    ]
+}
 
-        model.start();
+/// mtl files often point at textures with absolute paths from the machine they were exported on,
+/// so try the path itself, then relative to the obj file, then the textures folder
+fn resolve_texture_path(obj_file: &str, texture: &str) -> PathBuf {
+    let texture_path = PathBuf::from(texture);
+    if texture_path.exists() {
+        return texture_path;
+    }
+    let obj_dir = Path::new(obj_file).parent().unwrap_or(Path::new("."));
+    let relative_path = obj_dir.join(&texture_path);
+    if relative_path.exists() {
+        return relative_path;
+    }
+    if let Some(file_name) = texture_path.file_name() {
+        let textures_path = Path::new("./textures").join(file_name);
+        if textures_path.exists() {
+            return textures_path;
+        }
+    }
+    return texture_path;
+}
 
-        return model;
+/// loads a map a material may have, printing a warning and leaving it out if it fails
+fn load_optional_gltf_texture(
+    assets: &mut AssetCache,
+    image: &Option<GltfImage>,
+    gltf_path: &Path,
+    map_name: &str,
+) -> Option<Rc<Texture>> {
+    let image = image.as_ref()?;
+    match load_gltf_texture(assets, image, gltf_path) {
+        Ok(texture) => Some(texture),
+        Err(error) => {
+            println!("warning: {}, skipping the {} map", error, map_name);
+            None
+        }
     }
 }
 
+/// embedded images report the gltf file they came from
+fn load_gltf_texture(
+    assets: &mut AssetCache,
+    image: &GltfImage,
+    gltf_path: &Path,
+) -> Result<Rc<Texture>, EngineError> {
+    match image {
+        GltfImage::Path(path) => assets.texture(path),
+        GltfImage::Bytes(bytes) => assets.embedded_texture(gltf_path, bytes),
+    }
+}
+
+/// prints why a texture failed and gives the checkerboard to use in its place, so one bad file doesn't stop the model loading
+fn fall_back_to_checkerboard(assets: &mut AssetCache, error: EngineError) -> Rc<Texture> {
+    println!("warning: {}, using the checkerboard texture", error);
+    return assets.checkerboard_texture();
+}
+
+/// smooth normals for meshes that don't have any, every face adds its area weighted normal to its vertices
+pub fn generate_normals(positions: &[Vector3<f32>], indices: &[u32]) -> Vec<Vector3<f32>> {
+    let mut normals: Vec<Vector3<f32>> = vec![Vector3::zero(); positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let (a, b, c) = (
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        );
+        // the cross product's length is twice the triangle's area, so bigger faces count for more
+        let face_normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+        normals[a] += face_normal;
+        normals[b] += face_normal;
+        normals[c] += face_normal;
+    }
+    return normals
+        .into_iter()
+        .map(|normal| {
+            if normal.magnitude2() > 0.0 {
+                normal.normalize()
+            } else {
+                Vector3::unit_y()
+            }
+        })
+        .collect();
+}
+
 struct EulerWidget<'a> {
     euler: &'a mut Euler<Rad<f32>>,
     using: &'a mut bool,
@@ -574,6 +877,158 @@ impl<'a> Widget for &mut Color3Widget<'a> {
     }
 }
 
+struct ShadowSettingsWidget<'a> {
+    settings: &'a mut ShadowSettings,
+}
+
+impl<'a> From<&'a mut ShadowSettings> for ShadowSettingsWidget<'a> {
+    fn from(value: &'a mut ShadowSettings) -> Self {
+        return Self { settings: value };
+    }
+}
+
+impl<'a> Widget for &mut ShadowSettingsWidget<'a> {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let response = ui
+            .indent("", |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.settings.filter, ShadowFilter::Hard, "Hard");
+                    ui.selectable_value(&mut self.settings.filter, ShadowFilter::Pcf, "PCF");
+                    ui.selectable_value(
+                        &mut self.settings.filter,
+                        ShadowFilter::PoissonPcf,
+                        "Poisson",
+                    );
+                    ui.selectable_value(&mut self.settings.filter, ShadowFilter::Pcss, "PCSS");
+                });
+                ui.add(
+                    DragValue::new(&mut self.settings.pcf_kernel_size)
+                        .prefix("Kernel ")
+                        .speed(0.05)
+                        .range(1..=9),
+                );
+                ui.add(
+                    DragValue::new(&mut self.settings.light_size)
+                        .prefix("Light Size ")
+                        .speed(0.001)
+                        .range(0.0..=1.0)
+                        .fixed_decimals(3),
+                );
+                ui.add(
+                    DragValue::new(&mut self.settings.constant_bias)
+                        .prefix("Bias ")
+                        .speed(0.0001)
+                        .fixed_decimals(4),
+                );
+                ui.add(
+                    DragValue::new(&mut self.settings.slope_bias)
+                        .prefix("Slope Bias ")
+                        .speed(0.0001)
+                        .fixed_decimals(4),
+                );
+                ui.add(
+                    DragValue::new(&mut self.settings.normal_offset)
+                        .prefix("Normal Offset ")
+                        .suffix(" m")
+                        .speed(0.001)
+                        .fixed_decimals(3),
+                );
+            })
+            .response;
+        return response;
+    }
+}
+
+struct MaterialWidget<'a> {
+    material: &'a mut MaterialInfo,
+}
+
+impl<'a> From<&'a mut MaterialInfo> for MaterialWidget<'a> {
+    fn from(value: &'a mut MaterialInfo) -> Self {
+        return Self { material: value };
+    }
+}
+
+impl<'a> Widget for &mut MaterialWidget<'a> {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let response = ui
+            .indent("", |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.material.model, MaterialModel::Phong, "Phong");
+                    ui.selectable_value(
+                        &mut self.material.model,
+                        MaterialModel::MetallicRoughness,
+                        "Metallic Roughness",
+                    );
+                });
+                match self.material.model {
+                    MaterialModel::Phong => {
+                        ui.add(
+                            DragValue::new(&mut self.material.specular)
+                                .prefix("Specular ")
+                                .speed(0.01)
+                                .range(0.0..=1.0),
+                        );
+                        ui.add(
+                            DragValue::new(&mut self.material.shininess)
+                                .prefix("Shininess ")
+                                .speed(0.5)
+                                .range(1.0..=1000.0),
+                        );
+                    }
+                    MaterialModel::MetallicRoughness => {
+                        ui.label("Base Color");
+                        ui.add(&mut Color3Widget::from(&mut self.material.base_color));
+                        ui.add(
+                            DragValue::new(&mut self.material.metallic)
+                                .prefix("Metallic ")
+                                .speed(0.01)
+                                .range(0.0..=1.0),
+                        );
+                        ui.add(
+                            DragValue::new(&mut self.material.roughness)
+                                .prefix("Roughness ")
+                                .speed(0.01)
+                                .range(0.0..=1.0),
+                        );
+                    }
+                }
+                ui.add(
+                    DragValue::new(&mut self.material.ambient_occlusion)
+                        .prefix("Ambient Occlusion ")
+                        .speed(0.01)
+                        .range(0.0..=1.0),
+                );
+                ui.add(
+                    DragValue::new(&mut self.material.dissolve)
+                        .prefix("Opacity ")
+                        .speed(0.01)
+                        .range(0.0..=1.0),
+                );
+                ui.label("Emissive");
+                ui.add(&mut Color3Widget::from(&mut self.material.emissive));
+            })
+            .response;
+        return response;
+    }
+}
+
+impl SceneObject for Model {
+    fn local_matrix(&self) -> Matrix4<f32> {
+        // start_render only copies the euler angles into the rotation when the model is drawn
+        let rotation = if self.using_euler_angles {
+            self.euler_angles.into()
+        } else {
+            self.rotation
+        };
+        transforms_to_matrix(self.position, rotation, self.scale)
+    }
+
+    fn set_parent_matrix(&mut self, parent_matrix: Matrix4<f32>) {
+        self.parent_matrix = parent_matrix;
+    }
+}
+
 impl Widget for &mut Model {
     fn ui(self, ui: &mut egui_sdl2_gl::egui::Ui) -> egui_sdl2_gl::egui::Response {
         let mut euler_angles: Euler<Rad<f32>>;
@@ -597,6 +1052,17 @@ impl Widget for &mut Model {
                     ui.add(&mut rotation_widget);
                     ui.label("Scale");
                     ui.add(&mut scale_widget);
+                    egui::CollapsingHeader::new("Material")
+                        .id_source((&self.name, "material"))
+                        .show(ui, |ui| {
+                            if self.sub_meshes.is_empty() {
+                                ui.add(&mut MaterialWidget::from(&mut self.material_info));
+                            }
+                            for sub_mesh in &mut self.sub_meshes {
+                                ui.label(sub_mesh.name.clone());
+                                ui.add(&mut MaterialWidget::from(&mut sub_mesh.material_info));
+                            }
+                        });
                 });
             })
             .response;
@@ -611,6 +1077,8 @@ pub struct Camera {
     pub rotation: Quaternion<f32>,
     pub scale: Vector3<f32>,
     pub camera_rotation: Vector2<f32>,
+    /// the world matrix of the parent, set by the scene graph
+    pub parent_matrix: Matrix4<f32>,
 }
 
 impl Camera {
@@ -630,21 +1098,80 @@ impl Camera {
                 z: 1.,
             },
             camera_rotation: Vector2::zero(),
+            parent_matrix: Matrix4::identity(),
         }
     }
 
+    fn look_rotation(&self) -> Quaternion<f32> {
+        let pitch = self
+            .camera_rotation
+            .y
+            .clamp(-90.0f32.to_radians(), 90.0f32.to_radians());
+        Quaternion::from_angle_y(Rad(self.camera_rotation.x)) * Quaternion::from_angle_x(Rad(pitch))
+    }
+
+    /// the camera's world matrix, invert it for the view matrix
     pub fn view_transform(&mut self) -> Matrix4<f32> {
         self.camera_rotation.y = self
             .camera_rotation
             .y
             .clamp(-90.0f32.to_radians(), 90.0f32.to_radians());
-        self.rotation = Quaternion::from_angle_y(Rad(self.camera_rotation.x))
-            * Quaternion::from_angle_x(Rad(self.camera_rotation.y));
-        transforms_to_matrix(self.position, self.rotation, self.scale)
+        self.rotation = self.look_rotation();
+        self.parent_matrix * transforms_to_matrix(self.position, self.rotation, self.scale)
+    }
+
+    pub fn world_position(&self) -> Vector3<f32> {
+        (self.parent_matrix * self.position.extend(1.0)).truncate()
+    }
+}
+
+impl SceneObject for Camera {
+    fn local_matrix(&self) -> Matrix4<f32> {
+        transforms_to_matrix(self.position, self.look_rotation(), self.scale)
+    }
+
+    fn set_parent_matrix(&mut self, parent_matrix: Matrix4<f32>) {
+        self.parent_matrix = parent_matrix;
+    }
+}
+
+impl Widget for &mut Camera {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let mut position_widget: Vector3Widget = Vector3Widget::from(&mut self.position);
+        let response = ui
+            .group(|ui| {
+                ui.label("Position");
+                ui.add(&mut position_widget);
+                ui.label("Look");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        DragValue::new(&mut self.camera_rotation.x)
+                            .prefix("Yaw ")
+                            .suffix(" Rad")
+                            .speed(0.01),
+                    );
+                    ui.add(
+                        DragValue::new(&mut self.camera_rotation.y)
+                            .prefix("Pitch ")
+                            .suffix(" Rad")
+                            .speed(0.01),
+                    );
+                });
+            })
+            .response;
+        return response;
     }
 }
 
-fn transforms_to_matrix(
+/// a light at position shining down direction, so whatever is parented to it turns with it
+fn light_matrix(position: Vector3<f32>, direction: Vector3<f32>) -> Matrix4<f32> {
+    let rotation: Matrix4<f32> =
+        Quaternion::from_arc(-Vector3::unit_z(), direction.normalize(), Some(Vector3::unit_y()))
+            .into();
+    Matrix4::from_translation(position) * rotation
+}
+
+pub fn transforms_to_matrix(
     position: Vector3<f32>,
     rotation: Quaternion<f32>,
     scale: Vector3<f32>,
@@ -655,16 +1182,37 @@ fn transforms_to_matrix(
     position_matrix * scale_matrix * rotation_matrix
 }
 
+/// the most cascades a directional light can split the camera frustum into
+pub const MAX_CASCADES: usize = 8;
+/// the range the width and height of a shadow map can be set in
+pub const MIN_SHADOW_RESOLUTION: u32 = 16;
+pub const MAX_SHADOW_RESOLUTION: u32 = 8192;
+/// how far behind each cascade's frustum slice shadow casters are still rendered, in meters
+const CASCADE_CASTER_MARGIN: f32 = 50.0;
+
 pub struct DirectionalLight {
+    /// position and direction are relative to the light's parent in the scene graph
     pub position: Vector3<f32>,
     pub direction: Vector3<f32>,
+    /// the world matrix of the parent, set by the scene graph
+    pub parent_matrix: Matrix4<f32>,
 
     pub info: DirectionalLightInfo,
-    pub shadow_texture: Texture,
+    /// one layer per cascade
+    pub shadow_texture: TextureArray,
     pub shadow_framebuffer: FrameBuffer,
+    /// resolution of every cascade
     pub resolution: (u32, u32),
-    pub light_projection: Matrix4<f32>,
-    pub light_view: Matrix4<f32>,
+    pub cascade_count: usize,
+    /// blends between evenly spaced (0.0) and logarithmic (1.0) cascade splits
+    pub split_lambda: f32,
+    /// tints every cascade with its own color in the lighting pass
+    pub debug_cascades: bool,
+    /// the view space distance each cascade ends at
+    pub cascade_splits: Vec<f32>,
+    /// light_projection * light_view of each cascade
+    pub light_space_matrices: Vec<Matrix4<f32>>,
+    pub shadow_settings: ShadowSettings,
 }
 
 impl DirectionalLight {
@@ -675,67 +1223,217 @@ impl DirectionalLight {
             color: Vector3::new(1.0, 1.0, 1.0),
         };
 
+        let cascade_count = 4;
         let (shadow_texture, shadow_framebuffer) =
-            create_framebuffer_depthbuffer(shadow_resolution);
-        let (near_plane, far_plane) = (0.1f32, 100.0f32);
-        let light_projection =
-            cgmath::ortho(-10.0f32, 10.0f32, -10.0f32, 10.0f32, near_plane, far_plane);
-        let light_pos = Vector3::zero();
-        let light_pos_point = Point3::new(light_pos.x, light_pos.y, light_pos.z);
-        let light_view: Matrix4<f32> = cgmath::Matrix4::look_at_rh(
-            light_pos_point,
-            light_pos_point + direction,
-            Vector3::unit_y(),
-        );
+            create_framebuffer_depth_texture_array(shadow_resolution, cascade_count as u32);
 
         Self {
             position: Vector3::zero(),
             direction,
+            parent_matrix: Matrix4::identity(),
             info,
             shadow_texture,
             shadow_framebuffer,
             resolution: shadow_resolution,
-            light_projection,
-            light_view,
+            cascade_count,
+            split_lambda: 0.75,
+            debug_cascades: false,
+            cascade_splits: Vec::new(),
+            light_space_matrices: Vec::new(),
+            shadow_settings: ShadowSettings {
+                light_size: 0.5,
+                ..Default::default()
+            },
+        }
+    }
+    /// this light's entry in the DirectionalLights block, call after render so the cascades are up to date
+    pub fn uniform_data(&self) -> DirectionalLightData {
+        let mut data = DirectionalLightData::empty();
+        for (i, matrix) in self.light_space_matrices.iter().take(MAX_CASCADES).enumerate() {
+            data.light_space_matrices[i] = *matrix;
         }
+        for (i, split) in self.cascade_splits.iter().take(MAX_CASCADES).enumerate() {
+            data.cascade_plane_distances[i / 4][i % 4] = *split;
+        }
+        data.position = self.world_position();
+        data.cascade_count = self.light_space_matrices.len().min(MAX_CASCADES) as i32;
+        data.direction = self.world_direction();
+        data.debug_cascades = self.debug_cascades as i32;
+        data.color = self.info.color;
+        return data;
+    }
+    pub fn world_position(&self) -> Vector3<f32> {
+        (self.parent_matrix * self.position.extend(1.0)).truncate()
+    }
+    pub fn world_direction(&self) -> Vector3<f32> {
+        world_direction(self.parent_matrix, self.direction)
     }
     pub fn render(&mut self, globals: &mut Globals, depth_only_shader: &Program) {
-        let (near_plane, far_plane) = (0.01f32, 200.0f32);
-        let light_projection = cgmath::ortho(
-            -far_plane / 2.0,
-            far_plane / 2.0,
-            -far_plane / 2.0,
-            far_plane / 2.0,
-            near_plane,
-            far_plane,
+        (self.cascade_splits, self.light_space_matrices) = cascade_light_space_matrices(
+            &mut globals.cam,
+            self.world_direction(),
+            self.cascade_count,
+            self.split_lambda,
+            self.resolution.0,
         );
-        let mut light_pos = globals.cam.position - self.direction * far_plane / 2.0;
-        light_pos.x = light_pos.x.round();
-        light_pos.y = light_pos.y.round();
-        light_pos.z = light_pos.z.round();
-        let light_pos_point = Point3::new(light_pos.x, light_pos.y, light_pos.z);
-        let light_view: Matrix4<f32> = cgmath::Matrix4::look_at_rh(
-            light_pos_point,
-            light_pos_point + self.direction,
-            Vector3::unit_y(),
-        );
-        self.light_projection = light_projection;
-        self.light_view = light_view;
+
         unsafe {
             gl::Viewport(0, 0, self.resolution.0 as i32, self.resolution.1 as i32);
         }
-        self.shadow_framebuffer.bind();
-        draw_scene_custom_shader_program(globals, light_projection, light_view, depth_only_shader, true);
+        for (i, light_space_matrix) in self.light_space_matrices.iter().enumerate() {
+            self.shadow_framebuffer
+                .load_depth_texture_array_layer(&self.shadow_texture, i as u32)
+                .unwrap();
+            draw_scene_custom_shader_program(
+                globals,
+                *light_space_matrix,
+                Matrix4::identity(),
+                depth_only_shader,
+                true,
+                CullingVolume::from_matrix(*light_space_matrix),
+                "Directional Light Shadows",
+            );
+        }
         self.shadow_framebuffer.unbind();
     }
 }
 
+/// splits the range between near and far into cascade_count slices using the "practical split scheme",
+/// returns the far distance of every slice
+pub fn cascade_split_distances(near: f32, far: f32, cascade_count: usize, lambda: f32) -> Vec<f32> {
+    (1..=cascade_count)
+        .map(|i| {
+            let fraction = i as f32 / cascade_count as f32;
+            let logarithmic = near * (far / near).powf(fraction);
+            let uniform = near + (far - near) * fraction;
+            lambda * logarithmic + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// a direction relative to a parent turned into world space
+fn world_direction(parent_matrix: Matrix4<f32>, direction: Vector3<f32>) -> Vector3<f32> {
+    (parent_matrix * direction.extend(0.0))
+        .truncate()
+        .normalize()
+}
+
+/// the view space distance every cascade ends at and the light_projection * light_view of every cascade,
+/// for a light shining along the world space direction
+pub fn cascade_light_space_matrices(
+    camera: &mut Camera,
+    direction: Vector3<f32>,
+    cascade_count: usize,
+    lambda: f32,
+    resolution: u32,
+) -> (Vec<f32>, Vec<Matrix4<f32>>) {
+    let projection = camera.projection_matrix;
+    let camera_transform = camera.view_transform();
+
+    let splits = cascade_split_distances(projection.near, projection.far, cascade_count, lambda);
+    let mut matrices = Vec::with_capacity(splits.len());
+    let mut cascade_near = projection.near;
+    for cascade_far in splits.iter().copied() {
+        let corners = frustum_slice_corners(
+            camera_transform,
+            projection.fovy.0,
+            projection.aspect,
+            cascade_near,
+            cascade_far,
+        );
+        matrices.push(cascade_light_space_matrix(&corners, direction, resolution));
+        cascade_near = cascade_far;
+    }
+    (splits, matrices)
+}
+
+/// the eight world space corners of the part of the camera frustum between near and far
+fn frustum_slice_corners(
+    camera_transform: Matrix4<f32>,
+    fovy: f32,
+    aspect: f32,
+    near: f32,
+    far: f32,
+) -> [Vector3<f32>; 8] {
+    let tan_half_fovy = (fovy * 0.5).tan();
+    let mut corners = [Vector3::zero(); 8];
+    for (i, distance) in [near, far].iter().enumerate() {
+        let half_height = distance * tan_half_fovy;
+        let half_width = half_height * aspect;
+        for (j, (x, y)) in [(-1., -1.), (1., -1.), (-1., 1.), (1., 1.)].iter().enumerate() {
+            let corner = camera_transform
+                * Vector4::new(x * half_width, y * half_height, -distance, 1.0);
+            corners[i * 4 + j] = corner.truncate();
+        }
+    }
+    corners
+}
+
+/// fits an orthographic projection around a bounding sphere of the corners,
+/// then snaps it to whole shadow map texels so the shadows don't shimmer when the camera moves
+fn cascade_light_space_matrix(
+    corners: &[Vector3<f32>; 8],
+    direction: Vector3<f32>,
+    resolution: u32,
+) -> Matrix4<f32> {
+    let center: Vector3<f32> = corners.iter().sum::<Vector3<f32>>() / 8.0;
+    let radius = corners
+        .iter()
+        .map(|corner| (corner - center).magnitude())
+        .fold(0.0f32, f32::max);
+    // round the radius so the projection size doesn't change while the camera rotates
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    };
+    let center_point = Point3::new(center.x, center.y, center.z);
+    let light_view = Matrix4::look_at_rh(
+        center_point - direction * (radius + CASCADE_CASTER_MARGIN),
+        center_point,
+        up,
+    );
+    let mut light_projection = cgmath::ortho(
+        -radius,
+        radius,
+        -radius,
+        radius,
+        0.01,
+        2.0 * radius + CASCADE_CASTER_MARGIN,
+    );
+
+    let half_resolution = resolution as f32 / 2.0;
+    let origin = (light_projection * light_view) * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    let origin = Vector2::new(origin.x, origin.y) * half_resolution;
+    let offset = Vector2::new(origin.x.round() - origin.x, origin.y.round() - origin.y)
+        / half_resolution;
+    light_projection.w.x += offset.x;
+    light_projection.w.y += offset.y;
+
+    light_projection * light_view
+}
+
+impl SceneObject for DirectionalLight {
+    fn local_matrix(&self) -> Matrix4<f32> {
+        light_matrix(self.position, self.direction)
+    }
+
+    fn set_parent_matrix(&mut self, parent_matrix: Matrix4<f32>) {
+        self.parent_matrix = parent_matrix;
+    }
+}
+
 impl Widget for &mut DirectionalLight {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
         let mut direction_thing: Vector3<f32> = self.direction;
+        let mut cascade_count = self.cascade_count;
         let mut position_widget: Vector3Widget = Vector3Widget::from(&mut self.position);
         let mut direction_widget: Vector3Widget = Vector3Widget::from(&mut direction_thing);
         let mut color_widget: Color3Widget = Color3Widget::from(&mut self.info.color);
+        let mut shadow_widget: ShadowSettingsWidget =
+            ShadowSettingsWidget::from(&mut self.shadow_settings);
         let response = ui
             .group(|ui| {
                 ui.label("Position");
@@ -744,16 +1442,46 @@ impl Widget for &mut DirectionalLight {
                 ui.add(&mut direction_widget);
                 ui.label("Color");
                 ui.add(&mut color_widget);
+                ui.label("Shadows");
+                ui.add(&mut shadow_widget);
+                ui.label("Cascades");
+                ui.add(DragValue::new(&mut cascade_count).speed(0.05).range(1..=MAX_CASCADES));
+                ui.label("Split Lambda");
+                ui.add(
+                    DragValue::new(&mut self.split_lambda)
+                        .speed(0.01)
+                        .range(0.0..=1.0)
+                        .fixed_decimals(2),
+                );
+                ui.checkbox(&mut self.debug_cascades, "Show Cascades");
             })
             .response;
         self.direction = direction_thing.normalize();
+        if cascade_count != self.cascade_count {
+            // the old cascades are kept if the new ones can't be made
+            match self
+                .shadow_texture
+                .make_empty_depth_buffer(self.resolution, cascade_count as u32)
+            {
+                Ok(()) => self.cascade_count = cascade_count,
+                Err(error) => {
+                    println!("warning: failed to make {} cascades: {}", cascade_count, error)
+                }
+            }
+        }
         return response;
     }
 }
 
+/// near plane of the spot light shadow projection, the lighting pass needs it to linearize depth
+pub const SPOT_LIGHT_NEAR_PLANE: f32 = 0.01;
+
 pub struct SpotLight {
+    /// position and direction are relative to the light's parent in the scene graph
     pub position: Vector3<f32>,
     pub direction: Vector3<f32>,
+    /// the world matrix of the parent, set by the scene graph
+    pub parent_matrix: Matrix4<f32>,
     pub horizontal_fov: f32,
     pub info: SpotLightInfo,
 
@@ -762,9 +1490,22 @@ pub struct SpotLight {
     pub resolution: (u32, u32),
     pub light_projection: Matrix4<f32>,
     pub light_view: Matrix4<f32>,
+    pub shadow_settings: ShadowSettings,
 }
 
 impl SpotLight {
+    /// this light's entry in the SpotLights block
+    pub fn uniform_data(&self) -> SpotLightData {
+        SpotLightData {
+            light_space_matrix: self.light_projection * self.light_view,
+            position: self.world_position(),
+            radius: self.info.radius,
+            direction: self.world_direction(),
+            fov: self.horizontal_fov,
+            color: self.info.color,
+            near_plane: SPOT_LIGHT_NEAR_PLANE,
+        }
+    }
     pub fn new(shadow_resolution: (u32, u32), horizontal_fov: f32) -> Self {
         let direction: Vector3<f32> = Vector3::new(0., -1., 0.);
 
@@ -775,7 +1516,7 @@ impl SpotLight {
 
         let (shadow_texture, shadow_framebuffer) =
             create_framebuffer_depthbuffer(shadow_resolution);
-        let (near_plane, far_plane) = (0.01f32, info.radius);
+        let (near_plane, far_plane) = (SPOT_LIGHT_NEAR_PLANE, info.radius);
         let fovy = 0.5 * shadow_resolution.1 as f32
             / (0.5 * shadow_resolution.0 as f32 / (0.5 * horizontal_fov).tan());
         let light_projection = perspective(
@@ -795,6 +1536,7 @@ impl SpotLight {
         Self {
             position: Vector3::zero(),
             direction,
+            parent_matrix: Matrix4::identity(),
             horizontal_fov,
             info,
             shadow_texture,
@@ -802,10 +1544,33 @@ impl SpotLight {
             resolution: shadow_resolution,
             light_projection,
             light_view,
+            shadow_settings: ShadowSettings::default(),
+        }
+    }
+    pub fn world_position(&self) -> Vector3<f32> {
+        (self.parent_matrix * self.position.extend(1.0)).truncate()
+    }
+    pub fn world_direction(&self) -> Vector3<f32> {
+        world_direction(self.parent_matrix, self.direction)
+    }
+    /// wide spot lights are drawn with the sphere volume instead of the cone
+    pub fn uses_cone_volume(&self) -> bool {
+        self.horizontal_fov * 0.5 <= MAX_CONE_VOLUME_HALF_ANGLE
+    }
+    /// the model matrix of the light volume that covers everything this light can reach
+    pub fn volume_matrix(&self) -> Matrix4<f32> {
+        if self.uses_cone_volume() {
+            return cone_volume_matrix(
+                self.world_position(),
+                self.world_direction(),
+                self.info.radius,
+                self.horizontal_fov * 0.5,
+            );
         }
+        return sphere_volume_matrix(self.world_position(), self.info.radius);
     }
     pub fn render(&mut self, globals: &mut Globals, depth_only_shader: &Program) {
-        let (near_plane, far_plane) = (0.01f32, self.info.radius);
+        let (near_plane, far_plane) = (SPOT_LIGHT_NEAR_PLANE, self.info.radius);
         let fovy = self.horizontal_fov * self.resolution.1 as f32 / self.resolution.0 as f32;
         let light_projection = perspective(
             Rad(fovy),
@@ -813,11 +1578,11 @@ impl SpotLight {
             near_plane,
             far_plane,
         );
-        let light_pos = self.position;
+        let light_pos = self.world_position();
         let light_pos_point = Point3::new(light_pos.x, light_pos.y, light_pos.z);
         let light_view: Matrix4<f32> = cgmath::Matrix4::look_at_rh(
             light_pos_point,
-            light_pos_point + self.direction,
+            light_pos_point + self.world_direction(),
             Vector3::unit_y(),
         );
         self.light_projection = light_projection;
@@ -826,17 +1591,37 @@ impl SpotLight {
             gl::Viewport(0, 0, self.resolution.0 as i32, self.resolution.1 as i32);
         }
         self.shadow_framebuffer.bind();
-        draw_scene_custom_shader_program(globals, light_projection, light_view, depth_only_shader, true);
+        draw_scene_custom_shader_program(
+            globals,
+            light_projection,
+            light_view,
+            depth_only_shader,
+            true,
+            CullingVolume::from_matrix(light_projection * light_view),
+            "Spot Light Shadows",
+        );
         self.shadow_framebuffer.unbind();
     }
 }
 
+impl SceneObject for SpotLight {
+    fn local_matrix(&self) -> Matrix4<f32> {
+        light_matrix(self.position, self.direction)
+    }
+
+    fn set_parent_matrix(&mut self, parent_matrix: Matrix4<f32>) {
+        self.parent_matrix = parent_matrix;
+    }
+}
+
 impl Widget for &mut SpotLight {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
         let mut direction_thing: Vector3<f32> = self.direction;
         let mut position_widget: Vector3Widget = Vector3Widget::from(&mut self.position);
         let mut direction_widget: Vector3Widget = Vector3Widget::from(&mut direction_thing);
         let mut color_widget: Color3Widget = Color3Widget::from(&mut self.info.color);
+        let mut shadow_widget: ShadowSettingsWidget =
+            ShadowSettingsWidget::from(&mut self.shadow_settings);
         let response = ui
             .group(|ui| {
                 ui.label("Position");
@@ -845,6 +1630,8 @@ impl Widget for &mut SpotLight {
                 ui.add(&mut direction_widget);
                 ui.label("Color");
                 ui.add(&mut color_widget);
+                ui.label("Shadows");
+                ui.add(&mut shadow_widget);
                 ui.label("Radius");
                 ui.add(
                     DragValue::new(&mut self.horizontal_fov)
@@ -860,12 +1647,34 @@ impl Widget for &mut SpotLight {
 }
 
 pub struct PointLight {
+    /// relative to the light's parent in the scene graph
     pub position: Vector3<f32>,
+    /// the world matrix of the parent, set by the scene graph
+    pub parent_matrix: Matrix4<f32>,
     pub info: PointLightInfo,
+
+    /// the shadow cubemap and its framebuffer, made the first time the light renders shadows
+    /// so lights that never cast them don't take up any memory
+    pub shadow_map: Option<(Cubemap, FrameBuffer)>,
+    /// size of each face of the shadow cubemap
+    pub resolution: u32,
+    /// distance in meters subtracted from a fragment's distance before it's compared to the shadow map
+    pub shadow_bias: f32,
+    /// when false the shadow cubemap isn't rendered or sampled
+    pub cast_shadows: bool,
 }
 
 impl PointLight {
-    pub fn new() -> Self {
+    /// this light's entry in the PointLights block
+    pub fn uniform_data(&self) -> PointLightData {
+        PointLightData {
+            position: self.world_position(),
+            radius: self.info.radius,
+            color: self.info.color,
+            shadow_bias: self.shadow_bias,
+        }
+    }
+    pub fn new(shadow_resolution: u32) -> Self {
         let info = PointLightInfo {
             color: Vector3::new(1.0, 1.0, 1.0),
             radius: 10.0,
@@ -873,13 +1682,92 @@ impl PointLight {
 
         Self {
             position: zero(),
+            parent_matrix: Matrix4::identity(),
             info,
+            shadow_map: None,
+            resolution: shadow_resolution,
+            shadow_bias: 0.05,
+            cast_shadows: true,
+        }
+    }
+    pub fn world_position(&self) -> Vector3<f32> {
+        (self.parent_matrix * self.position.extend(1.0)).truncate()
+    }
+    /// the model matrix of the light volume that covers everything this light can reach
+    pub fn volume_matrix(&self) -> Matrix4<f32> {
+        sphere_volume_matrix(self.world_position(), self.info.radius)
+    }
+    /// the six view projection matrices used to render each face of the shadow cubemap,
+    /// in the order of GL_TEXTURE_CUBE_MAP_POSITIVE_X + i
+    pub fn shadow_matrices(&self) -> [Matrix4<f32>; 6] {
+        let (near_plane, far_plane) = (0.01f32, self.info.radius);
+        let shadow_projection = perspective(Rad(f32::consts::FRAC_PI_2), 1.0, near_plane, far_plane);
+        let position = self.world_position();
+        let light_pos_point = Point3::new(position.x, position.y, position.z);
+        let faces: [(Vector3<f32>, Vector3<f32>); 6] = [
+            (Vector3::unit_x(), -Vector3::unit_y()),
+            (-Vector3::unit_x(), -Vector3::unit_y()),
+            (Vector3::unit_y(), Vector3::unit_z()),
+            (-Vector3::unit_y(), -Vector3::unit_z()),
+            (Vector3::unit_z(), -Vector3::unit_y()),
+            (-Vector3::unit_z(), -Vector3::unit_y()),
+        ];
+        faces.map(|(direction, up)| {
+            shadow_projection
+                * Matrix4::look_at_rh(light_pos_point, light_pos_point + direction, up)
+        })
+    }
+    pub fn render(&mut self, globals: &mut Globals, point_shadow_shader: &Program) {
+        point_shadow_shader.set();
+        point_shadow_shader.set_matrix4_array("shadowMatrices", &self.shadow_matrices());
+        point_shadow_shader.set_vector3("lightPos", self.world_position());
+        point_shadow_shader.set_float("farPlane", self.info.radius);
+        unsafe {
+            gl::Viewport(0, 0, self.resolution as i32, self.resolution as i32);
+        }
+        // the cube map sees everything within the light's radius
+        let culling_volume = CullingVolume::Sphere(BoundingSphere {
+            center: self.world_position(),
+            radius: self.info.radius,
+        });
+        let resolution = self.resolution;
+        let (_, shadow_framebuffer) = self
+            .shadow_map
+            .get_or_insert_with(|| create_framebuffer_depth_cubemap((resolution, resolution)));
+        shadow_framebuffer.bind();
+        draw_scene_custom_shader_program(
+            globals,
+            Matrix4::identity(),
+            Matrix4::identity(),
+            point_shadow_shader,
+            true,
+            culling_volume,
+            "Point Light Shadows",
+        );
+        shadow_framebuffer.unbind();
+    }
+    /// the shadow cubemap if shadows are on and it has been rendered
+    pub fn shadow_cubemap(&self) -> Option<&Cubemap> {
+        if !self.cast_shadows {
+            return None;
         }
+        return self.shadow_map.as_ref().map(|(cubemap, _)| cubemap);
+    }
+}
+
+impl SceneObject for PointLight {
+    fn local_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.position)
+    }
+
+    fn set_parent_matrix(&mut self, parent_matrix: Matrix4<f32>) {
+        self.parent_matrix = parent_matrix;
     }
 }
 
 impl Widget for &mut PointLight {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let mut resolution = self.resolution;
         let mut position_widget: Vector3Widget = Vector3Widget::from(&mut self.position);
         let mut color_widget: Color3Widget = Color3Widget::from(&mut self.info.color);
         let response = ui
@@ -888,8 +1776,38 @@ impl Widget for &mut PointLight {
                 ui.add(&mut position_widget);
                 ui.label("Color");
                 ui.add(&mut color_widget);
+                ui.label("Radius");
+                ui.add(
+                    DragValue::new(&mut self.info.radius)
+                        .suffix(" m")
+                        .speed(0.01)
+                        .range(0.01..=f32::MAX),
+                );
+                ui.checkbox(&mut self.cast_shadows, "Cast Shadows");
+                ui.label("Shadow Resolution");
+                ui.add(
+                    DragValue::new(&mut resolution)
+                        .speed(1.0)
+                        .range(MIN_SHADOW_RESOLUTION..=MAX_SHADOW_RESOLUTION),
+                );
+                ui.label("Shadow Bias");
+                ui.add(
+                    DragValue::new(&mut self.shadow_bias)
+                        .suffix(" m")
+                        .speed(0.001)
+                        .fixed_decimals(3),
+                );
             })
             .response;
+        if resolution != self.resolution {
+            self.resolution = resolution;
+            if let Some((shadow_cubemap, _)) = &self.shadow_map {
+                let remade = shadow_cubemap.make_empty_depth_buffer((resolution, resolution));
+                if let Err(error) = remade {
+                    println!("warning: failed to resize the shadow cubemap: {}", error);
+                }
+            }
+        }
         return response;
     }
 }
@@ -974,4 +1892,57 @@ impl Portal {
         draw_scene(globals, self.portal_projection, self.portal_view);
         self.portal_framebuffer.unbind();
     }
-} */
\ No newline at end of file
+} */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cascade_splits_span_near_to_far() {
+        for lambda in [0.0, 0.5, 0.75, 1.0] {
+            let splits = cascade_split_distances(0.1, 100.0, 4, lambda);
+            assert_eq!(splits.len(), 4);
+            assert!((splits[3] - 100.0).abs() < 1e-3);
+            let mut previous = 0.1;
+            for split in splits {
+                assert!(split > previous);
+                previous = split;
+            }
+        }
+        // the first cascade starts at near, so a single cascade has to reach all the way to far
+        assert!((cascade_split_distances(0.1, 100.0, 1, 0.75)[0] - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn cascade_split_lambda_blends_uniform_and_logarithmic() {
+        let uniform = cascade_split_distances(1.0, 16.0, 4, 0.0);
+        let logarithmic = cascade_split_distances(1.0, 16.0, 4, 1.0);
+        let half = cascade_split_distances(1.0, 16.0, 4, 0.5);
+        let expected_uniform = [4.75, 8.5, 12.25, 16.0];
+        let expected_logarithmic = [2.0, 4.0, 8.0, 16.0];
+        for i in 0..4 {
+            assert!((uniform[i] - expected_uniform[i]).abs() < 1e-4);
+            assert!((logarithmic[i] - expected_logarithmic[i]).abs() < 1e-4);
+            let blended = 0.5 * expected_uniform[i] + 0.5 * expected_logarithmic[i];
+            assert!((half[i] - blended).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn cascades_follow_a_rotated_parent() {
+        let mut camera = Camera::new(16.0 / 9.0, 1.0, 0.1, 100.0);
+        let parent_matrix = Matrix4::from_angle_z(Rad(f32::consts::FRAC_PI_2));
+        let direction = world_direction(parent_matrix, Vector3::new(0.0, -1.0, 0.0));
+        assert!((direction - Vector3::new(1.0, 0.0, 0.0)).magnitude() < 1e-5);
+
+        let (_, matrices) = cascade_light_space_matrices(&mut camera, direction, 4, 0.75, 1024);
+        assert_eq!(matrices.len(), 4);
+        for matrix in matrices {
+            // the light looks along its world direction, so moving along it only changes the depth
+            let moved = matrix * direction.extend(0.0);
+            assert!(moved.x.abs() < 1e-5 && moved.y.abs() < 1e-5);
+            assert!(moved.z > 0.0);
+        }
+    }
+}
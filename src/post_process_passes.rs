@@ -0,0 +1,444 @@
+use std::path::Path;
+
+use cgmath::Vector3;
+use egui_sdl2_gl::egui;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    assets::AssetCache,
+    buffers::{FrameBuffer, Texture, Texture3D},
+    errors::EngineError,
+    post_processing::{
+        bind_output, Parameter, PostProcessContext, PostProcessInput, PostProcessPass,
+        PostProcessStage, SCREEN_QUAD_VERTEX_SHADER,
+    },
+    shaders::Program,
+};
+
+/// the size of the lut used when none is loaded
+const IDENTITY_LUT_SIZE: u32 = 16;
+
+/// blurs the parts of the hdr scene brighter than a threshold and adds them back on top
+pub struct Bloom {
+    parameters: Vec<Parameter>,
+    bright_pass: Option<Program>,
+    blur: Option<Program>,
+    /// half the size of the screen, the blur goes back and forth between them
+    targets: Vec<(Texture, FrameBuffer)>,
+    target_size: (u32, u32),
+}
+
+impl Bloom {
+    pub fn new() -> Self {
+        Self {
+            parameters: vec![
+                Parameter::float("Threshold", "threshold", 1.0, 0.0..=20.0),
+                Parameter::float("Knee", "knee", 0.5, 0.0..=1.0),
+                Parameter::float("Intensity", "intensity", 0.1, 0.0..=2.0),
+                Parameter::int("Blur Iterations", "iterations", 4, 1..=16),
+            ],
+            bright_pass: None,
+            blur: None,
+            targets: Vec::new(),
+            target_size: (0, 0),
+        }
+    }
+}
+
+impl PostProcessPass for Bloom {
+    fn name(&self) -> &str {
+        "Bloom"
+    }
+
+    fn stage(&self) -> PostProcessStage {
+        PostProcessStage::Hdr
+    }
+
+    fn fragment_shader(&self) -> &str {
+        "./shaders/Bloom/shader.frag"
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    fn parameters_mut(&mut self) -> &mut [Parameter] {
+        &mut self.parameters
+    }
+
+    fn prepare(&mut self, assets: &mut AssetCache, size: (u32, u32)) -> Result<(), EngineError> {
+        if self.bright_pass.is_none() {
+            self.bright_pass = Some(assets.program(
+                SCREEN_QUAD_VERTEX_SHADER,
+                "./shaders/BloomBrightPass/shader.frag",
+            )?);
+        }
+        if self.blur.is_none() {
+            self.blur =
+                Some(assets.program(SCREEN_QUAD_VERTEX_SHADER, "./shaders/BloomBlur/shader.frag")?);
+        }
+        let target_size = ((size.0 / 2).max(1), (size.1 / 2).max(1));
+        if self.targets.is_empty() {
+            self.targets = (0..2)
+                .map(|_| (Texture::new(), FrameBuffer::new()))
+                .collect();
+        } else if self.target_size == target_size {
+            return Ok(());
+        }
+        for (texture, framebuffer) in &self.targets {
+            framebuffer.bind();
+            texture.make_empty_hdr(target_size);
+            framebuffer.load(texture).unwrap();
+        }
+        self.targets[0].1.unbind();
+        self.target_size = target_size;
+        return Ok(());
+    }
+
+    fn render(
+        &mut self,
+        context: &PostProcessContext,
+        program: &Program,
+        image: &Texture,
+        output: Option<&FrameBuffer>,
+    ) {
+        let (Some(bright_pass), Some(blur)) = (&self.bright_pass, &self.blur) else {
+            return;
+        };
+        unsafe {
+            gl::Viewport(0, 0, self.target_size.0 as i32, self.target_size.1 as i32);
+        }
+        self.targets[0].1.bind();
+        context.draw(bright_pass, image, &[], &self.parameters);
+
+        let iterations = self.parameters[3].as_f32() as usize;
+        for _ in 0..iterations {
+            for (horizontal, from, to) in [(true, 0, 1), (false, 1, 0)] {
+                self.targets[to].1.bind();
+                blur.set();
+                blur.set_bool("horizontal", horizontal);
+                context.draw(blur, &self.targets[from].0, &[], &[]);
+            }
+        }
+
+        unsafe {
+            gl::Viewport(0, 0, context.size.0 as i32, context.size.1 as i32);
+        }
+        bind_output(output);
+        let bloom = PostProcessInput::Texture(&self.targets[0].0);
+        context.draw(program, image, &[("bloom", bloom)], &self.parameters);
+    }
+}
+
+/// finds the edges of the tone mapped image by their contrast and smooths them
+pub struct Fxaa {
+    parameters: Vec<Parameter>,
+}
+
+impl Fxaa {
+    pub fn new() -> Self {
+        Self {
+            parameters: vec![
+                Parameter::float("Edge Threshold", "edgeThreshold", 0.125, 0.03..=0.5),
+                Parameter::float(
+                    "Minimum Edge Threshold",
+                    "edgeThresholdMin",
+                    0.0312,
+                    0.0..=0.1,
+                ),
+                Parameter::float("Subpixel Quality", "subpixelQuality", 0.75, 0.0..=1.0),
+            ],
+        }
+    }
+}
+
+impl PostProcessPass for Fxaa {
+    fn name(&self) -> &str {
+        "FXAA"
+    }
+
+    fn stage(&self) -> PostProcessStage {
+        PostProcessStage::Ldr
+    }
+
+    fn fragment_shader(&self) -> &str {
+        "./shaders/Fxaa/shader.frag"
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    fn parameters_mut(&mut self) -> &mut [Parameter] {
+        &mut self.parameters
+    }
+}
+
+/// looks up every color in a 3d lookup table, loaded from a strip of square slices like the ones
+/// photo editors export
+pub struct ColorGrading {
+    parameters: Vec<Parameter>,
+    /// an image with a slice for every blue value side by side, empty for no grading
+    pub lut_path: String,
+    /// the path lut was loaded from, None to load it again
+    loaded_path: Option<String>,
+    lut: Option<Texture3D>,
+}
+
+impl ColorGrading {
+    pub fn new() -> Self {
+        Self {
+            parameters: vec![Parameter::float("Strength", "strength", 1.0, 0.0..=1.0)],
+            lut_path: String::new(),
+            loaded_path: None,
+            lut: None,
+        }
+    }
+}
+
+/// a lut strip that maps every color to itself, slices are size x size
+pub fn identity_lut_strip(size: u32) -> RgbaImage {
+    let scale = 255.0 / (size - 1) as f32;
+    return RgbaImage::from_fn(size * size, size, |x, y| {
+        let (red, green, blue) = (x % size, y, x / size);
+        Rgba([
+            (red as f32 * scale).round() as u8,
+            (green as f32 * scale).round() as u8,
+            (blue as f32 * scale).round() as u8,
+            255,
+        ])
+    });
+}
+
+/// the size and texels of the 3d texture of a lut strip. the strip is size * size wide and size
+/// high, the slice along x is the blue, and in a slice red goes right and green goes down
+pub fn lut_strip_to_volume(strip: &RgbaImage) -> Result<(u32, Vec<u8>), String> {
+    let size = strip.height();
+    if size < 2 || strip.width() != size * size {
+        return Err(format!(
+            "the image is {}x{}, a lut of size n has to be n * n wide and n high",
+            strip.width(),
+            strip.height()
+        ));
+    }
+    let mut texels = Vec::with_capacity((size * size * size * 4) as usize);
+    for blue in 0..size {
+        for green in 0..size {
+            for red in 0..size {
+                texels.extend_from_slice(&strip.get_pixel(blue * size + red, green).0);
+            }
+        }
+    }
+    return Ok((size, texels));
+}
+
+fn load_lut(path: &str) -> Result<(u32, Vec<u8>), EngineError> {
+    let strip = image::open(path).map_err(|source| EngineError::Image {
+        path: Path::new(path).to_path_buf(),
+        source,
+    })?;
+    return lut_strip_to_volume(&strip.to_rgba8()).map_err(|message| EngineError::Lut {
+        path: Path::new(path).to_path_buf(),
+        message,
+    });
+}
+
+impl PostProcessPass for ColorGrading {
+    fn name(&self) -> &str {
+        "Color Grading"
+    }
+
+    fn stage(&self) -> PostProcessStage {
+        PostProcessStage::Ldr
+    }
+
+    fn fragment_shader(&self) -> &str {
+        "./shaders/ColorGrading/shader.frag"
+    }
+
+    fn inputs(&self) -> Vec<(&'static str, PostProcessInput<'_>)> {
+        match &self.lut {
+            Some(lut) => vec![("lut", PostProcessInput::Texture3D(lut))],
+            None => Vec::new(),
+        }
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    fn parameters_mut(&mut self) -> &mut [Parameter] {
+        &mut self.parameters
+    }
+
+    /// a lut that fails to load is replaced by one that changes nothing
+    fn prepare(&mut self, _assets: &mut AssetCache, _size: (u32, u32)) -> Result<(), EngineError> {
+        if self.loaded_path.as_ref() == Some(&self.lut_path) {
+            return Ok(());
+        }
+        let identity = || lut_strip_to_volume(&identity_lut_strip(IDENTITY_LUT_SIZE)).unwrap();
+        let (size, texels) = if self.lut_path.is_empty() {
+            identity()
+        } else {
+            load_lut(&self.lut_path).unwrap_or_else(|error| {
+                println!("warning: {}, color grading is off", error);
+                identity()
+            })
+        };
+        let lut = self.lut.get_or_insert_with(Texture3D::new);
+        lut.load_rgba(size, &texels);
+        self.loaded_path = Some(self.lut_path.clone());
+        return Ok(());
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.lut_path);
+            if ui.button("Reload LUT").clicked() {
+                self.loaded_path = None;
+            }
+        });
+        for parameter in &mut self.parameters {
+            parameter.ui(ui);
+        }
+    }
+}
+
+/// splits red and blue apart towards the edges of the screen, like a cheap lens
+pub struct ChromaticAberration {
+    parameters: Vec<Parameter>,
+}
+
+impl ChromaticAberration {
+    pub fn new() -> Self {
+        Self {
+            parameters: vec![Parameter::float("Strength", "strength", 0.01, 0.0..=0.1)],
+        }
+    }
+}
+
+impl PostProcessPass for ChromaticAberration {
+    fn name(&self) -> &str {
+        "Chromatic Aberration"
+    }
+
+    fn stage(&self) -> PostProcessStage {
+        PostProcessStage::Ldr
+    }
+
+    fn fragment_shader(&self) -> &str {
+        "./shaders/ChromaticAberration/shader.frag"
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    fn parameters_mut(&mut self) -> &mut [Parameter] {
+        &mut self.parameters
+    }
+}
+
+/// fades the corners of the screen to a color
+pub struct Vignette {
+    parameters: Vec<Parameter>,
+}
+
+impl Vignette {
+    pub fn new() -> Self {
+        Self {
+            parameters: vec![
+                Parameter::float("Intensity", "intensity", 0.5, 0.0..=1.0),
+                Parameter::float("Radius", "radius", 0.75, 0.0..=2.0),
+                Parameter::float("Softness", "softness", 0.45, 0.01..=2.0),
+                Parameter::color("Color", "color", Vector3::new(0.0, 0.0, 0.0)),
+            ],
+        }
+    }
+}
+
+impl PostProcessPass for Vignette {
+    fn name(&self) -> &str {
+        "Vignette"
+    }
+
+    fn stage(&self) -> PostProcessStage {
+        PostProcessStage::Ldr
+    }
+
+    fn fragment_shader(&self) -> &str {
+        "./shaders/Vignette/shader.frag"
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    fn parameters_mut(&mut self) -> &mut [Parameter] {
+        &mut self.parameters
+    }
+}
+
+/// noise that changes every frame, strongest in the dark parts
+pub struct FilmGrain {
+    parameters: Vec<Parameter>,
+}
+
+impl FilmGrain {
+    pub fn new() -> Self {
+        Self {
+            parameters: vec![
+                Parameter::float("Intensity", "intensity", 0.08, 0.0..=0.5),
+                Parameter::float("Highlights", "highlights", 0.3, 0.0..=1.0),
+            ],
+        }
+    }
+}
+
+impl PostProcessPass for FilmGrain {
+    fn name(&self) -> &str {
+        "Film Grain"
+    }
+
+    fn stage(&self) -> PostProcessStage {
+        PostProcessStage::Ldr
+    }
+
+    fn fragment_shader(&self) -> &str {
+        "./shaders/FilmGrain/shader.frag"
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    fn parameters_mut(&mut self) -> &mut [Parameter] {
+        &mut self.parameters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lut_strips_become_volumes() {
+        let strip = identity_lut_strip(4);
+        assert_eq!(strip.dimensions(), (16, 4));
+        let (size, texels) = lut_strip_to_volume(&strip).unwrap();
+        assert_eq!(size, 4);
+        assert_eq!(texels.len(), 4 * 4 * 4 * 4);
+        // red changes fastest, then green, then blue
+        let texel = |red: usize, green: usize, blue: usize| {
+            let index = ((blue * 4 + green) * 4 + red) * 4;
+            [texels[index], texels[index + 1], texels[index + 2]]
+        };
+        assert_eq!(texel(0, 0, 0), [0, 0, 0]);
+        assert_eq!(texel(3, 0, 0), [255, 0, 0]);
+        assert_eq!(texel(0, 3, 0), [0, 255, 0]);
+        assert_eq!(texel(1, 2, 3), [85, 170, 255]);
+
+        let error = lut_strip_to_volume(&RgbaImage::new(16, 8)).unwrap_err();
+        assert!(error.starts_with("the image is 16x8"), "{}", error);
+    }
+}
@@ -0,0 +1,585 @@
+use std::ops::RangeInclusive;
+
+use cgmath::{Vector2, Vector3};
+use egui_sdl2_gl::egui;
+
+use crate::{
+    assets::AssetCache,
+    buffers::{FrameBuffer, ModelTexture, Texture, Texture3D},
+    errors::EngineError,
+    models::Model,
+    post_process_passes::{Bloom, ChromaticAberration, ColorGrading, FilmGrain, Fxaa, Vignette},
+    shaders::Program,
+};
+
+/// the vertex shader of every pass, it covers the screen with the screen model
+pub const SCREEN_QUAD_VERTEX_SHADER: &str = "./shaders/FinalPass/shader.vert";
+
+/// when a pass runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostProcessStage {
+    /// on the hdr scene, before tone mapping
+    Hdr,
+    /// on the tone mapped image
+    Ldr,
+}
+
+/// a texture a pass reads besides the image of the pass before it
+pub enum PostProcessInput<'a> {
+    /// the hdr scene before any pass
+    Scene,
+    Position,
+    Normal,
+    Texture(&'a Texture),
+    Texture3D(&'a Texture3D),
+}
+
+pub enum ParameterValue {
+    Float {
+        value: f32,
+        range: RangeInclusive<f32>,
+    },
+    Int {
+        value: i32,
+        range: RangeInclusive<i32>,
+    },
+    Color(Vector3<f32>),
+    Bool(bool),
+}
+
+/// a setting of a pass, set as the uniform of the same name and shown in the debug window
+pub struct Parameter {
+    pub label: &'static str,
+    pub uniform: &'static str,
+    pub value: ParameterValue,
+}
+
+impl Parameter {
+    pub fn float(
+        label: &'static str,
+        uniform: &'static str,
+        value: f32,
+        range: RangeInclusive<f32>,
+    ) -> Self {
+        Self {
+            label,
+            uniform,
+            value: ParameterValue::Float { value, range },
+        }
+    }
+
+    pub fn int(
+        label: &'static str,
+        uniform: &'static str,
+        value: i32,
+        range: RangeInclusive<i32>,
+    ) -> Self {
+        Self {
+            label,
+            uniform,
+            value: ParameterValue::Int { value, range },
+        }
+    }
+
+    pub fn color(label: &'static str, uniform: &'static str, value: Vector3<f32>) -> Self {
+        Self {
+            label,
+            uniform,
+            value: ParameterValue::Color(value),
+        }
+    }
+
+    pub fn bool(label: &'static str, uniform: &'static str, value: bool) -> Self {
+        Self {
+            label,
+            uniform,
+            value: ParameterValue::Bool(value),
+        }
+    }
+
+    /// the value as a float, for passes that use their parameters on the cpu too
+    pub fn as_f32(&self) -> f32 {
+        match &self.value {
+            ParameterValue::Float { value, .. } => *value,
+            ParameterValue::Int { value, .. } => *value as f32,
+            ParameterValue::Color(color) => color.x,
+            ParameterValue::Bool(value) => *value as i32 as f32,
+        }
+    }
+
+    /// uniforms the shader doesn't have are skipped, passes can keep parameters only used on the cpu
+    pub fn set(&self, program: &Program) {
+        if !program.has_uniform(self.uniform) {
+            return;
+        }
+        match &self.value {
+            ParameterValue::Float { value, .. } => program.set_float(self.uniform, *value),
+            ParameterValue::Int { value, .. } => program.set_int(self.uniform, *value),
+            ParameterValue::Color(color) => program.set_vector3(self.uniform, *color),
+            ParameterValue::Bool(value) => program.set_bool(self.uniform, *value),
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        match &mut self.value {
+            ParameterValue::Float { value, range } => {
+                ui.add(egui::Slider::new(value, range.clone()).text(self.label));
+            }
+            ParameterValue::Int { value, range } => {
+                ui.add(egui::Slider::new(value, range.clone()).text(self.label));
+            }
+            ParameterValue::Color(color) => {
+                ui.horizontal(|ui| {
+                    let mut rgb = [color.x, color.y, color.z];
+                    ui.color_edit_button_rgb(&mut rgb);
+                    *color = Vector3::new(rgb[0], rgb[1], rgb[2]);
+                    ui.label(self.label);
+                });
+            }
+            ParameterValue::Bool(value) => {
+                ui.checkbox(value, self.label);
+            }
+        }
+    }
+}
+
+/// what every pass can draw with
+pub struct PostProcessContext<'a> {
+    /// covers the screen, every pass is drawn with it
+    pub screen_model: &'a Model,
+    pub size: (u32, u32),
+    /// the time in the Camera block
+    pub time: f32,
+    /// the hdr scene before any pass
+    pub scene: &'a Texture,
+    pub g_position: &'a Texture,
+    pub g_normal: &'a Texture,
+}
+
+impl PostProcessContext<'_> {
+    /// draws program over the bound framebuffer with image on unit 0 as `image`, then the inputs on
+    /// the units after it. screenSize and time are set when the shader has them
+    pub fn draw(
+        &self,
+        program: &Program,
+        image: &Texture,
+        inputs: &[(&str, PostProcessInput)],
+        parameters: &[Parameter],
+    ) {
+        program.set();
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+        image.bind_texture();
+        program.set_int("image", 0);
+        for (i, (sampler, input)) in inputs.iter().enumerate() {
+            let unit = i as u32 + 1;
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+            }
+            match input {
+                PostProcessInput::Scene => self.scene.bind_texture(),
+                PostProcessInput::Position => self.g_position.bind_texture(),
+                PostProcessInput::Normal => self.g_normal.bind_texture(),
+                PostProcessInput::Texture(texture) => texture.bind_texture(),
+                PostProcessInput::Texture3D(texture) => texture.bind(),
+            }
+            program.set_int(sampler, unit as i32);
+        }
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+        if program.has_uniform("screenSize") {
+            program.set_vector2(
+                "screenSize",
+                Vector2::new(self.size.0 as f32, self.size.1 as f32),
+            );
+        }
+        if program.has_uniform("time") {
+            program.set_float("time", self.time);
+        }
+        for parameter in parameters {
+            parameter.set(program);
+        }
+        self.draw_screen_quad();
+    }
+
+    /// draws the screen model with the program that is set
+    pub fn draw_screen_quad(&self) {
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::CULL_FACE);
+            gl::Disable(gl::BLEND);
+        }
+        self.screen_model.mesh.vbo.bind();
+        self.screen_model.mesh.vao.bind();
+        self.screen_model.mesh.ibo.bind();
+        self.screen_model.render_fullbright();
+    }
+}
+
+/// binds output, or the window with None
+pub fn bind_output(output: Option<&FrameBuffer>) {
+    match output {
+        Some(framebuffer) => framebuffer.bind(),
+        None => unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0) },
+    }
+}
+
+/// a full screen effect in the post processing stack. a pass declares its shader, the textures it
+/// reads and its parameters, the stack builds the program, binds everything and draws it
+pub trait PostProcessPass {
+    fn name(&self) -> &str;
+
+    fn stage(&self) -> PostProcessStage;
+
+    /// the fragment shader, drawn over the screen with SCREEN_QUAD_VERTEX_SHADER
+    fn fragment_shader(&self) -> &str;
+
+    /// sampler uniforms and the textures bound to them, the image from the pass before is always
+    /// bound as `image`
+    fn inputs(&self) -> Vec<(&'static str, PostProcessInput<'_>)> {
+        return Vec::new();
+    }
+
+    fn parameters(&self) -> &[Parameter];
+
+    fn parameters_mut(&mut self) -> &mut [Parameter];
+
+    /// called every frame the pass is enabled before it is drawn, for passes with their own
+    /// programs or textures to make them
+    fn prepare(&mut self, _assets: &mut AssetCache, _size: (u32, u32)) -> Result<(), EngineError> {
+        return Ok(());
+    }
+
+    /// draws the pass into output, passes that need more than one draw, like bloom, replace this
+    fn render(
+        &mut self,
+        context: &PostProcessContext,
+        program: &Program,
+        image: &Texture,
+        output: Option<&FrameBuffer>,
+    ) {
+        bind_output(output);
+        context.draw(program, image, &self.inputs(), self.parameters());
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        for parameter in self.parameters_mut() {
+            parameter.ui(ui);
+        }
+    }
+}
+
+/// a pass in the stack
+pub struct PostProcessEntry {
+    pub pass: Box<dyn PostProcessPass>,
+    pub enabled: bool,
+    /// built the first time the pass is enabled
+    program: Option<Program>,
+}
+
+/// two color targets the passes of a stage draw into in turn
+struct PingPong {
+    hdr: bool,
+    size: (u32, u32),
+    targets: Vec<(Texture, FrameBuffer)>,
+}
+
+impl PingPong {
+    fn new(hdr: bool) -> Self {
+        Self {
+            hdr,
+            size: (0, 0),
+            targets: Vec::new(),
+        }
+    }
+
+    fn resize(&mut self, size: (u32, u32)) {
+        if self.size == size && !self.targets.is_empty() {
+            return;
+        }
+        if self.targets.is_empty() {
+            self.targets = (0..2)
+                .map(|_| (Texture::new(), FrameBuffer::new()))
+                .collect();
+        }
+        for (texture, framebuffer) in &self.targets {
+            framebuffer.bind();
+            if self.hdr {
+                texture.make_empty_hdr(size);
+            } else {
+                texture.make_empty_clamped(size);
+            }
+            framebuffer.load(texture).unwrap();
+        }
+        self.targets[0].1.unbind();
+        self.size = size;
+    }
+}
+
+/// where the nth enabled pass of a stage draws, with passes passes. the last draws into the
+/// output when there is one, the others take turns with the two ping pong targets
+pub fn ping_pong_target(pass: usize, passes: usize, has_output: bool) -> Option<usize> {
+    if has_output && pass + 1 == passes {
+        return None;
+    }
+    return Some(pass % 2);
+}
+
+/// effects drawn over the scene after the final pass, in order, before and after tone mapping
+pub struct PostProcessStack {
+    pub passes: Vec<PostProcessEntry>,
+    hdr_targets: PingPong,
+    ldr_targets: PingPong,
+}
+
+impl PostProcessStack {
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            hdr_targets: PingPong::new(true),
+            ldr_targets: PingPong::new(false),
+        }
+    }
+
+    /// every pass there is, turned off so the scene looks the same until one is turned on
+    pub fn with_default_passes() -> Self {
+        let mut stack = Self::new();
+        stack.add(Bloom::new());
+        stack.add(Fxaa::new());
+        stack.add(ColorGrading::new());
+        stack.add(ChromaticAberration::new());
+        stack.add(Vignette::new());
+        stack.add(FilmGrain::new());
+        return stack;
+    }
+
+    /// adds a pass at the end, turned off
+    pub fn add(&mut self, pass: impl PostProcessPass + 'static) {
+        self.passes.push(PostProcessEntry {
+            pass: Box::new(pass),
+            enabled: false,
+            program: None,
+        });
+    }
+
+    /// swaps the pass at index with the one before it
+    pub fn move_up(&mut self, index: usize) {
+        if index > 0 && index < self.passes.len() {
+            self.passes.swap(index - 1, index);
+        }
+    }
+
+    pub fn has_enabled(&self, stage: PostProcessStage) -> bool {
+        self.passes
+            .iter()
+            .any(|entry| entry.enabled && entry.pass.stage() == stage)
+    }
+
+    /// builds the programs of the enabled passes and sizes the targets, a pass that fails is turned off
+    pub fn prepare(&mut self, assets: &mut AssetCache, size: (u32, u32)) {
+        for entry in &mut self.passes {
+            if !entry.enabled {
+                continue;
+            }
+            let prepared = prepare_entry(entry, assets, size);
+            if let Err(error) = prepared {
+                println!(
+                    "warning: turned off the {} pass: {}",
+                    entry.pass.name(),
+                    error
+                );
+                entry.enabled = false;
+            }
+        }
+        if self.has_enabled(PostProcessStage::Hdr) {
+            self.hdr_targets.resize(size);
+        }
+        if self.has_enabled(PostProcessStage::Ldr) {
+            self.ldr_targets.resize(size);
+        }
+    }
+
+    /// runs the passes before tone mapping on the scene, returns the index of the target the last
+    /// one drew into for hdr_texture, or None when none are enabled
+    pub fn run_hdr(&mut self, context: &PostProcessContext) -> Option<usize> {
+        return run_stage(
+            &mut self.passes,
+            PostProcessStage::Hdr,
+            &self.hdr_targets,
+            context,
+            context.scene,
+            None,
+        );
+    }
+
+    /// the image a pass before tone mapping drew, index comes from run_hdr
+    pub fn hdr_texture(&self, index: usize) -> &Texture {
+        return &self.hdr_targets.targets[index].0;
+    }
+
+    /// the framebuffer tone mapping draws into for the passes after it, None when there are none
+    pub fn ldr_input(&self) -> Option<&FrameBuffer> {
+        if !self.has_enabled(PostProcessStage::Ldr) {
+            return None;
+        }
+        return Some(&self.ldr_targets.targets[1].1);
+    }
+
+    /// runs the passes after tone mapping on the ldr input, the last draws into output, or the
+    /// window with None
+    pub fn run_ldr(&mut self, context: &PostProcessContext, output: Option<&FrameBuffer>) {
+        if !self.has_enabled(PostProcessStage::Ldr) {
+            return;
+        }
+        let input = &self.ldr_targets.targets[1].0;
+        run_stage(
+            &mut self.passes,
+            PostProcessStage::Ldr,
+            &self.ldr_targets,
+            context,
+            input,
+            Some(output),
+        );
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let mut move_up = None;
+        let count = self.passes.len();
+        for (i, entry) in self.passes.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut entry.enabled, entry.pass.name());
+                if entry.pass.stage() == PostProcessStage::Hdr {
+                    ui.label("(before tone mapping)");
+                }
+                if ui.add_enabled(i > 0, egui::Button::new("Up")).clicked() {
+                    move_up = Some(i);
+                }
+                if ui
+                    .add_enabled(i + 1 < count, egui::Button::new("Down"))
+                    .clicked()
+                {
+                    move_up = Some(i + 1);
+                }
+            });
+            if entry.enabled {
+                egui::CollapsingHeader::new(format!("{} Settings", entry.pass.name()))
+                    .id_source(("post_process_pass", i))
+                    .show(ui, |ui| entry.pass.ui(ui));
+            }
+        }
+        if let Some(index) = move_up {
+            self.move_up(index);
+        }
+    }
+}
+
+/// builds the program of the pass the first time, then lets the pass make what else it needs
+fn prepare_entry(
+    entry: &mut PostProcessEntry,
+    assets: &mut AssetCache,
+    size: (u32, u32),
+) -> Result<(), EngineError> {
+    if entry.program.is_none() {
+        let program = assets.program(SCREEN_QUAD_VERTEX_SHADER, entry.pass.fragment_shader())?;
+        entry.program = Some(program);
+    }
+    return entry.pass.prepare(assets, size);
+}
+
+/// draws the enabled passes of stage in order, the first reads input. with an output the last one
+/// draws into it, Some(None) being the window, without one the ping pong target the last one drew
+/// into is returned
+fn run_stage(
+    passes: &mut [PostProcessEntry],
+    stage: PostProcessStage,
+    targets: &PingPong,
+    context: &PostProcessContext,
+    input: &Texture,
+    output: Option<Option<&FrameBuffer>>,
+) -> Option<usize> {
+    let count = passes
+        .iter()
+        .filter(|entry| entry.enabled && entry.pass.stage() == stage)
+        .count();
+    let mut last_target: Option<usize> = None;
+    let stage_passes = passes
+        .iter_mut()
+        .filter(|entry| entry.enabled && entry.pass.stage() == stage);
+    for (i, entry) in stage_passes.enumerate() {
+        let Some(program) = &entry.program else {
+            continue;
+        };
+        let image = match last_target {
+            Some(index) => &targets.targets[index].0,
+            None => input,
+        };
+        let target = ping_pong_target(i, count, output.is_some());
+        let framebuffer = match target {
+            Some(index) => Some(&targets.targets[index].1),
+            None => output.flatten(),
+        };
+        entry.pass.render(context, program, image, framebuffer);
+        last_target = target;
+    }
+    return last_target;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_take_turns_with_the_targets() {
+        let targets: Vec<Option<usize>> = (0..4).map(|i| ping_pong_target(i, 4, true)).collect();
+        assert_eq!(targets, [Some(0), Some(1), Some(0), None]);
+        let targets: Vec<Option<usize>> = (0..3).map(|i| ping_pong_target(i, 3, false)).collect();
+        assert_eq!(targets, [Some(0), Some(1), Some(0)]);
+        assert_eq!(ping_pong_target(0, 1, true), None);
+    }
+
+    struct TestPass(&'static str, PostProcessStage, Vec<Parameter>);
+
+    impl PostProcessPass for TestPass {
+        fn name(&self) -> &str {
+            self.0
+        }
+        fn stage(&self) -> PostProcessStage {
+            self.1
+        }
+        fn fragment_shader(&self) -> &str {
+            "./shaders/Test/shader.frag"
+        }
+        fn parameters(&self) -> &[Parameter] {
+            &self.2
+        }
+        fn parameters_mut(&mut self) -> &mut [Parameter] {
+            &mut self.2
+        }
+    }
+
+    #[test]
+    fn passes_can_be_reordered_and_enabled() {
+        let mut stack = PostProcessStack::new();
+        stack.add(TestPass("a", PostProcessStage::Hdr, Vec::new()));
+        stack.add(TestPass(
+            "b",
+            PostProcessStage::Ldr,
+            vec![Parameter::int("Count", "count", 3, 1..=8)],
+        ));
+        stack.add(TestPass("c", PostProcessStage::Ldr, Vec::new()));
+        assert!(!stack.has_enabled(PostProcessStage::Ldr));
+
+        stack.passes[1].enabled = true;
+        assert!(stack.has_enabled(PostProcessStage::Ldr));
+        assert!(!stack.has_enabled(PostProcessStage::Hdr));
+
+        stack.move_up(2);
+        stack.move_up(0);
+        stack.move_up(3);
+        let names: Vec<&str> = stack.passes.iter().map(|entry| entry.pass.name()).collect();
+        assert_eq!(names, ["a", "c", "b"]);
+        assert_eq!(stack.passes[2].pass.parameters()[0].as_f32(), 3.0);
+    }
+}
@@ -0,0 +1,1009 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use cgmath::{Euler, Quaternion, Rad, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::AssetCache,
+    errors::EngineError,
+    globals::Globals,
+    material_structs::{
+        DirectionalLightInfo, MaterialInfo, PointLightInfo, ShadowSettings, SpotLightInfo,
+    },
+    models::{
+        DirectionalLight, Model, PointLight, SpotLight, MAX_CASCADES, MAX_SHADOW_RESOLUTION,
+        MIN_SHADOW_RESOLUTION,
+    },
+    scene_graph::{Group, NodeObject, SceneGraph, SceneObjects},
+};
+
+/// a model in a scene file, loaded again from the file it came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelDescription {
+    pub name: String,
+    /// obj, gltf or glb file
+    pub source: String,
+    /// which of the file's models it is, a gltf file can have several
+    pub source_index: usize,
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub position: Vector3<f32>,
+    #[serde(with = "crate::serde_vectors::quaternion")]
+    pub rotation: Quaternion<f32>,
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub scale: Vector3<f32>,
+    pub render_shadows: bool,
+    /// replaces the material from the file
+    pub material: MaterialInfo,
+    /// replaces the materials of the sub meshes, in order
+    pub sub_mesh_materials: Vec<MaterialInfo>,
+    #[serde(default, with = "parent_name")]
+    pub parent: Option<NodeObject>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupDescription {
+    pub name: String,
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub position: Vector3<f32>,
+    /// euler angles in radians
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub rotation: Vector3<f32>,
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub scale: Vector3<f32>,
+    #[serde(default, with = "parent_name")]
+    pub parent: Option<NodeObject>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirectionalLightDescription {
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub position: Vector3<f32>,
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub direction: Vector3<f32>,
+    #[serde(flatten)]
+    pub info: DirectionalLightInfo,
+    pub resolution: (u32, u32),
+    pub cascade_count: usize,
+    pub split_lambda: f32,
+    pub debug_cascades: bool,
+    pub shadow_settings: ShadowSettings,
+    #[serde(default, with = "parent_name")]
+    pub parent: Option<NodeObject>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpotLightDescription {
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub position: Vector3<f32>,
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub direction: Vector3<f32>,
+    pub horizontal_fov: f32,
+    #[serde(flatten)]
+    pub info: SpotLightInfo,
+    pub resolution: (u32, u32),
+    pub shadow_settings: ShadowSettings,
+    #[serde(default, with = "parent_name")]
+    pub parent: Option<NodeObject>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PointLightDescription {
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub position: Vector3<f32>,
+    #[serde(flatten)]
+    pub info: PointLightInfo,
+    pub resolution: u32,
+    pub shadow_bias: f32,
+    pub cast_shadows: bool,
+    #[serde(default, with = "parent_name")]
+    pub parent: Option<NodeObject>,
+}
+
+/// the aspect ratio isn't kept, it comes from the window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraDescription {
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub position: Vector3<f32>,
+    /// yaw and pitch in radians
+    #[serde(with = "crate::serde_vectors::vector2")]
+    pub rotation: Vector2<f32>,
+    pub fovy: f32,
+    pub near: f32,
+    pub far: f32,
+    #[serde(default, with = "parent_name")]
+    pub parent: Option<NodeObject>,
+}
+
+/// the tunables from Globals that belong to a scene rather than to the engine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneSettings {
+    pub movement_speed: f32,
+    pub look_sensitivity: f32,
+    pub mouse_look_sensitivity: f32,
+    #[serde(with = "crate::serde_vectors::vector3")]
+    pub ambient_light: Vector3<f32>,
+    pub frustum_culling: bool,
+    pub point_light_volumes: bool,
+    pub spot_light_volumes: bool,
+    pub clustered_lighting: bool,
+}
+
+impl SceneSettings {
+    pub fn from_globals(globals: &Globals) -> Self {
+        Self {
+            movement_speed: globals.movement_speed,
+            look_sensitivity: globals.look_sensitivity,
+            mouse_look_sensitivity: globals.mouse_look_sensitivity,
+            ambient_light: globals.ambient_light,
+            frustum_culling: globals.frustum_culling,
+            point_light_volumes: globals.point_light_volumes,
+            spot_light_volumes: globals.spot_light_volumes,
+            clustered_lighting: globals.clustered_lighting,
+        }
+    }
+
+    pub fn apply(&self, globals: &mut Globals) {
+        globals.movement_speed = self.movement_speed;
+        globals.look_sensitivity = self.look_sensitivity;
+        globals.mouse_look_sensitivity = self.mouse_look_sensitivity;
+        globals.ambient_light = self.ambient_light;
+        globals.frustum_culling = self.frustum_culling;
+        globals.point_light_volumes = self.point_light_volumes;
+        globals.spot_light_volumes = self.spot_light_volumes;
+        globals.clustered_lighting = self.clustered_lighting;
+    }
+}
+
+/// everything a scene file holds. parents refer to the other objects by their index in the file,
+/// instanced models and models made in code aren't kept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub settings: SceneSettings,
+    pub camera: CameraDescription,
+    pub groups: Vec<GroupDescription>,
+    pub models: Vec<ModelDescription>,
+    pub directional_lights: Vec<DirectionalLightDescription>,
+    pub spot_lights: Vec<SpotLightDescription>,
+    pub point_lights: Vec<PointLightDescription>,
+}
+
+impl Scene {
+    pub fn load(path: &Path) -> Result<Scene, EngineError> {
+        let text = fs::read_to_string(path).map_err(|source| EngineError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let scene_error = |message: String| EngineError::Scene {
+            path: path.to_path_buf(),
+            message,
+        };
+        return Scene::parse(&text).map_err(scene_error);
+    }
+
+    /// writes the scene as json, making the folder it goes in if there isn't one
+    pub fn save(&self, path: &Path) -> Result<(), EngineError> {
+        let write_error = |source| EngineError::Write {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder).map_err(write_error)?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|error| EngineError::Scene {
+            path: path.to_path_buf(),
+            message: error.to_string(),
+        })?;
+        return fs::write(path, text).map_err(write_error);
+    }
+
+    /// describes the objects and how the scene graph parents them
+    pub fn capture(graph: &SceneGraph, objects: &SceneObjects, settings: SceneSettings) -> Scene {
+        // models without a file are left out, so the ones after them move down
+        let mut model_indices: Vec<Option<usize>> = Vec::new();
+        let mut kept_models = 0;
+        for model in objects.models.iter() {
+            if model.source.is_some() {
+                model_indices.push(Some(kept_models));
+                kept_models += 1;
+            } else {
+                model_indices.push(None);
+            }
+        }
+        let parents: HashMap<NodeObject, NodeObject> = graph
+            .nodes
+            .iter()
+            .filter_map(|node| Some((node.object, graph.nodes[node.parent?].object)))
+            .collect();
+        let parent = |object: NodeObject| {
+            let parent = match parents.get(&object)? {
+                NodeObject::Model(i) => model_indices[*i].map(NodeObject::Model),
+                NodeObject::InstancedModel(_) => None,
+                parent => Some(*parent),
+            };
+            if parent.is_none() {
+                println!(
+                    "warning: {} is parented to {}, which isn't saved, so it is saved without a parent",
+                    node_object_name(object),
+                    node_object_name(parents[&object])
+                );
+            }
+            parent
+        };
+
+        let projection = objects.camera.projection_matrix;
+        let camera = CameraDescription {
+            position: objects.camera.position,
+            rotation: objects.camera.camera_rotation,
+            fovy: projection.fovy.0,
+            near: projection.near,
+            far: projection.far,
+            parent: parent(NodeObject::Camera),
+        };
+        let groups = graph
+            .groups
+            .iter()
+            .enumerate()
+            .map(|(i, group)| GroupDescription {
+                name: group.name.clone(),
+                position: group.position,
+                rotation: Vector3::new(group.rotation.x.0, group.rotation.y.0, group.rotation.z.0),
+                scale: group.scale,
+                parent: parent(NodeObject::Group(i)),
+            })
+            .collect();
+        let models = objects
+            .models
+            .iter()
+            .enumerate()
+            .filter_map(|(i, model)| {
+                let (source, source_index) = model.source.clone()?;
+                Some(ModelDescription {
+                    name: model.name.clone(),
+                    source,
+                    source_index,
+                    position: model.position,
+                    rotation: if model.using_euler_angles {
+                        Quaternion::from(model.euler_angles)
+                    } else {
+                        model.rotation
+                    },
+                    scale: model.scale,
+                    render_shadows: model.render_shadows,
+                    material: model.material_info,
+                    sub_mesh_materials: model
+                        .sub_meshes
+                        .iter()
+                        .map(|sub_mesh| sub_mesh.material_info)
+                        .collect(),
+                    parent: parent(NodeObject::Model(i)),
+                })
+            })
+            .collect();
+        let directional_lights = objects
+            .directional_lights
+            .iter()
+            .enumerate()
+            .map(|(i, light)| DirectionalLightDescription {
+                position: light.position,
+                direction: light.direction,
+                info: light.info,
+                resolution: light.resolution,
+                cascade_count: light.cascade_count,
+                split_lambda: light.split_lambda,
+                debug_cascades: light.debug_cascades,
+                shadow_settings: light.shadow_settings,
+                parent: parent(NodeObject::DirectionalLight(i)),
+            })
+            .collect();
+        let spot_lights = objects
+            .spot_lights
+            .iter()
+            .enumerate()
+            .map(|(i, light)| SpotLightDescription {
+                position: light.position,
+                direction: light.direction,
+                horizontal_fov: light.horizontal_fov,
+                info: light.info,
+                resolution: light.resolution,
+                shadow_settings: light.shadow_settings,
+                parent: parent(NodeObject::SpotLight(i)),
+            })
+            .collect();
+        let point_lights = objects
+            .point_lights
+            .iter()
+            .enumerate()
+            .map(|(i, light)| PointLightDescription {
+                position: light.position,
+                info: light.info,
+                resolution: light.resolution,
+                shadow_bias: light.shadow_bias,
+                cast_shadows: light.cast_shadows,
+                parent: parent(NodeObject::PointLight(i)),
+            })
+            .collect();
+        return Scene {
+            settings,
+            camera,
+            groups,
+            models,
+            directional_lights,
+            spot_lights,
+            point_lights,
+        };
+    }
+
+    /// replaces the objects and the scene graph with the scene's. instanced models are removed.
+    /// nothing is changed if a model fails to load
+    pub fn instantiate(
+        &self,
+        graph: &mut SceneGraph,
+        objects: &mut SceneObjects,
+        assets: &mut AssetCache,
+    ) -> Result<(), EngineError> {
+        let mut models = Vec::new();
+        for description in &self.models {
+            let mut model = load_model(&description.source, description.source_index, assets)?;
+            model.name = description.name.clone();
+            model.position = description.position;
+            model.rotation = description.rotation;
+            model.using_euler_angles = false;
+            model.scale = description.scale;
+            model.render_shadows = description.render_shadows;
+            model.material_info = description.material;
+            for (sub_mesh, material) in model
+                .sub_meshes
+                .iter_mut()
+                .zip(&description.sub_mesh_materials)
+            {
+                sub_mesh.material_info = *material;
+            }
+            models.push(model);
+        }
+        *objects.models = models;
+        objects.instanced_models.clear();
+
+        let mut directional_lights = Vec::new();
+        for (i, description) in self.directional_lights.iter().enumerate() {
+            let mut light = DirectionalLight::new(description.resolution);
+            light.position = description.position;
+            light.direction = description.direction;
+            light.info = description.info;
+            light.split_lambda = description.split_lambda;
+            light.debug_cascades = description.debug_cascades;
+            light.shadow_settings = description.shadow_settings;
+            if description.cascade_count != light.cascade_count {
+                light.cascade_count = description.cascade_count;
+                light
+                    .shadow_texture
+                    .make_empty_depth_buffer(light.resolution, light.cascade_count as u32)
+                    .map_err(|source| EngineError::Texture {
+                        name: format!("directional_lights[{}] shadow map", i),
+                        source,
+                    })?;
+            }
+            directional_lights.push(light);
+        }
+        *objects.directional_lights = directional_lights;
+        *objects.spot_lights = self
+            .spot_lights
+            .iter()
+            .map(|description| {
+                let mut light = SpotLight::new(description.resolution, description.horizontal_fov);
+                light.position = description.position;
+                light.direction = description.direction;
+                light.info = description.info;
+                light.shadow_settings = description.shadow_settings;
+                light
+            })
+            .collect();
+        *objects.point_lights = self
+            .point_lights
+            .iter()
+            .map(|description| {
+                let mut light = PointLight::new(description.resolution);
+                light.position = description.position;
+                light.info = description.info;
+                light.shadow_bias = description.shadow_bias;
+                light.cast_shadows = description.cast_shadows;
+                light
+            })
+            .collect();
+
+        let camera = &mut *objects.camera;
+        camera.position = self.camera.position;
+        camera.camera_rotation = self.camera.rotation;
+        camera.projection_matrix.fovy = Rad(self.camera.fovy);
+        camera.projection_matrix.near = self.camera.near;
+        camera.projection_matrix.far = self.camera.far;
+
+        *graph = SceneGraph::new();
+        let mut nodes: HashMap<NodeObject, usize> = HashMap::new();
+        for description in &self.groups {
+            let mut group = Group::new(description.name.clone());
+            group.position = description.position;
+            group.rotation = Euler::new(
+                Rad(description.rotation.x),
+                Rad(description.rotation.y),
+                Rad(description.rotation.z),
+            );
+            group.scale = description.scale;
+            let node = graph.add_group(group);
+            nodes.insert(graph.nodes[node].object, node);
+        }
+        for (object, _) in self.parents() {
+            // groups got their nodes when they were added
+            if !matches!(object, NodeObject::Group(_)) {
+                nodes.insert(object, graph.add_node(object));
+            }
+        }
+        for (object, parent) in self.parents() {
+            if let Some(parent) = parent {
+                if !graph.set_parent(nodes[&object], Some(nodes[&parent])) {
+                    println!(
+                        "warning: {:?} can't be parented to {:?}, it would be its own ancestor",
+                        object, parent
+                    );
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /// every object in the scene and its parent
+    fn parents(&self) -> Vec<(NodeObject, Option<NodeObject>)> {
+        let mut parents = vec![(NodeObject::Camera, self.camera.parent)];
+        parents.extend(
+            self.groups
+                .iter()
+                .enumerate()
+                .map(|(i, group)| (NodeObject::Group(i), group.parent)),
+        );
+        parents.extend(
+            self.models
+                .iter()
+                .enumerate()
+                .map(|(i, model)| (NodeObject::Model(i), model.parent)),
+        );
+        parents.extend(
+            self.directional_lights
+                .iter()
+                .enumerate()
+                .map(|(i, light)| (NodeObject::DirectionalLight(i), light.parent)),
+        );
+        parents.extend(
+            self.spot_lights
+                .iter()
+                .enumerate()
+                .map(|(i, light)| (NodeObject::SpotLight(i), light.parent)),
+        );
+        parents.extend(
+            self.point_lights
+                .iter()
+                .enumerate()
+                .map(|(i, light)| (NodeObject::PointLight(i), light.parent)),
+        );
+        return parents;
+    }
+
+    /// errors name the key that is missing or has the wrong type, like models[2].position
+    fn parse(text: &str) -> Result<Scene, String> {
+        let scene: Scene =
+            serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(text))
+                .map_err(|error| error.to_string())?;
+        scene.validate()?;
+        return Ok(scene);
+    }
+
+    /// what the types can't check, so a broken file is an error instead of a panic later
+    fn validate(&self) -> Result<(), String> {
+        // cgmath asserts on these when the projection matrix is made
+        let camera = &self.camera;
+        if camera.fovy <= 0.0 || camera.fovy >= std::f32::consts::PI {
+            return Err(format!(
+                "camera.fovy should be more than 0 and less than pi radians, not {}",
+                camera.fovy
+            ));
+        }
+        if camera.near <= 0.0 {
+            return Err(format!(
+                "camera.near should be more than 0, not {}",
+                camera.near
+            ));
+        }
+        if camera.far <= camera.near {
+            return Err(format!(
+                "camera.far should be more than camera.near ({}), not {}",
+                camera.near, camera.far
+            ));
+        }
+        for (i, light) in self.directional_lights.iter().enumerate() {
+            let path = format!("directional_lights[{}]", i);
+            check_resolution(&path, light.resolution)?;
+            if !(1..=MAX_CASCADES).contains(&light.cascade_count) {
+                return Err(format!(
+                    "{}.cascade_count should be from 1 to {}, not {}",
+                    path, MAX_CASCADES, light.cascade_count
+                ));
+            }
+        }
+        for (i, light) in self.spot_lights.iter().enumerate() {
+            check_resolution(&format!("spot_lights[{}]", i), light.resolution)?;
+        }
+        for (i, light) in self.point_lights.iter().enumerate() {
+            if !(MIN_SHADOW_RESOLUTION..=MAX_SHADOW_RESOLUTION).contains(&light.resolution) {
+                return Err(format!(
+                    "point_lights[{}].resolution should be from {} to {}, not {}",
+                    i, MIN_SHADOW_RESOLUTION, MAX_SHADOW_RESOLUTION, light.resolution
+                ));
+            }
+        }
+        return self.check_parents();
+    }
+
+    /// every parent has to be an object in the file
+    fn check_parents(&self) -> Result<(), String> {
+        let objects = self.parents();
+        for (object, parent) in &objects {
+            if let Some(parent) = parent {
+                if !objects.iter().any(|(other, _)| other == parent) {
+                    return Err(format!(
+                        "the parent of {} is {}, which isn't in the scene",
+                        node_object_name(*object),
+                        node_object_name(*parent)
+                    ));
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// takes the model out of what the file loads into, from the asset cache if it was loaded before
+fn load_model(source: &str, index: usize, assets: &mut AssetCache) -> Result<Model, EngineError> {
+    let extension = Path::new(source)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let mut models = match extension.as_deref() {
+        Some("gltf") | Some("glb") => Model::from_gltf_file(source.to_owned(), assets)?,
+        _ => vec![Model::from_obj_file(source.to_owned(), assets)?],
+    };
+    if index >= models.len() {
+        return Err(EngineError::Scene {
+            path: PathBuf::from(source),
+            message: format!(
+                "the file has {} models, there is no model {}",
+                models.len(),
+                index
+            ),
+        });
+    }
+    return Ok(models.swap_remove(index));
+}
+
+/// how a parent is written in a scene file, like "group 0", "point_light 3" or "camera"
+fn node_object_name(object: NodeObject) -> String {
+    match object {
+        NodeObject::Group(i) => format!("group {}", i),
+        NodeObject::Model(i) => format!("model {}", i),
+        NodeObject::InstancedModel(i) => format!("instanced_model {}", i),
+        NodeObject::PointLight(i) => format!("point_light {}", i),
+        NodeObject::SpotLight(i) => format!("spot_light {}", i),
+        NodeObject::DirectionalLight(i) => format!("directional_light {}", i),
+        NodeObject::Camera => "camera".to_owned(),
+    }
+}
+
+fn node_object_from_name(name: &str) -> Option<NodeObject> {
+    if name == "camera" {
+        return Some(NodeObject::Camera);
+    }
+    let (kind, index) = name.split_once(' ')?;
+    let index: usize = index.parse().ok()?;
+    match kind {
+        "group" => Some(NodeObject::Group(index)),
+        "model" => Some(NodeObject::Model(index)),
+        "point_light" => Some(NodeObject::PointLight(index)),
+        "spot_light" => Some(NodeObject::SpotLight(index)),
+        "directional_light" => Some(NodeObject::DirectionalLight(index)),
+        _ => None,
+    }
+}
+
+/// the width and height of a spot or directional light's shadow map
+fn check_resolution(path: &str, (width, height): (u32, u32)) -> Result<(), String> {
+    let range = MIN_SHADOW_RESOLUTION..=MAX_SHADOW_RESOLUTION;
+    if !range.contains(&width) || !range.contains(&height) {
+        return Err(format!(
+            "{}.resolution should be 2 whole numbers from {} to {}",
+            path, MIN_SHADOW_RESOLUTION, MAX_SHADOW_RESOLUTION
+        ));
+    }
+    return Ok(());
+}
+
+/// a parent written by its name, null or missing for objects at the top of the tree
+mod parent_name {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{node_object_from_name, node_object_name};
+    use crate::scene_graph::NodeObject;
+
+    pub fn serialize<S: Serializer>(
+        parent: &Option<NodeObject>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        parent.map(node_object_name).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<NodeObject>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(name) => node_object_from_name(&name).map(Some).ok_or_else(|| {
+                D::Error::custom(format!(
+                    "should be like \"group 0\" or \"camera\", not \"{}\"",
+                    name
+                ))
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        material_structs::{MaterialModel, ShadowFilter},
+        models::Camera,
+    };
+
+    fn shadow_settings(seed: f32) -> ShadowSettings {
+        ShadowSettings {
+            filter: ShadowFilter::PoissonPcf,
+            pcf_kernel_size: 5,
+            light_size: 0.37 * seed,
+            constant_bias: 0.000_31 * seed,
+            slope_bias: 0.0027 * seed,
+            normal_offset: 0.013 * seed,
+        }
+    }
+
+    fn material(seed: f32) -> MaterialInfo {
+        MaterialInfo {
+            model: MaterialModel::MetallicRoughness,
+            ambient: Vector3::new(0.1, 0.2, 0.3) * seed,
+            diffuse: Vector3::new(0.7, 0.6, 0.5) * seed,
+            specular: 0.33 * seed,
+            shininess: 17.5 * seed,
+            dissolve: 0.9,
+            base_color: Vector3::new(0.81, 0.27, 0.12) * seed,
+            metallic: 0.61,
+            roughness: 0.29 * seed,
+            ambient_occlusion: 0.77,
+            emissive: Vector3::new(1.5, 0.25, 0.0) * seed,
+        }
+    }
+
+    /// no field is left at a default, so a field that isn't written or read fails the test
+    fn test_scene() -> Scene {
+        Scene {
+            settings: SceneSettings {
+                movement_speed: 12.5,
+                look_sensitivity: 0.35,
+                mouse_look_sensitivity: 1.7,
+                ambient_light: Vector3::new(0.05, 0.07, 0.11),
+                frustum_culling: false,
+                point_light_volumes: false,
+                spot_light_volumes: true,
+                clustered_lighting: true,
+            },
+            camera: CameraDescription {
+                position: Vector3::new(-3.25, 1.8, 7.1),
+                rotation: Vector2::new(0.6, -0.2),
+                fovy: 1.1,
+                near: 0.05,
+                far: 250.0,
+                parent: Some(NodeObject::Group(1)),
+            },
+            groups: vec![
+                GroupDescription {
+                    name: "Cart \"A\"\n".to_owned(),
+                    position: Vector3::new(10.0, 0.0, -2.5),
+                    rotation: Vector3::new(0.1, 1.25, -0.3),
+                    scale: Vector3::new(2.0, 2.0, 0.5),
+                    parent: None,
+                },
+                GroupDescription {
+                    name: "Rig".to_owned(),
+                    position: Vector3::new(0.0, 1.0, 0.0),
+                    rotation: Vector3::new(0.0, 0.0, 0.0),
+                    scale: Vector3::new(1.0, 1.0, 1.0),
+                    parent: Some(NodeObject::Group(0)),
+                },
+            ],
+            models: vec![
+                ModelDescription {
+                    name: "Lamp".to_owned(),
+                    source: "./models/Cube.obj".to_owned(),
+                    source_index: 0,
+                    position: Vector3::new(1.0, 2.0, 3.0),
+                    rotation: Quaternion::new(0.9238795, 0.0, 0.38268343, 0.0),
+                    scale: Vector3::new(0.25, 0.5, 0.75),
+                    render_shadows: false,
+                    material: material(1.0),
+                    sub_mesh_materials: vec![material(0.5), material(0.25)],
+                    parent: Some(NodeObject::Group(0)),
+                },
+                ModelDescription {
+                    name: "Helmet".to_owned(),
+                    source: "./models/helmet/DamagedHelmet.glb".to_owned(),
+                    source_index: 3,
+                    position: Vector3::new(-1e-7, 123456.79, 0.1),
+                    rotation: Quaternion::new(0.0, 0.0, 0.0, 0.0),
+                    scale: Vector3::new(1.0, 1.0, 1.0),
+                    render_shadows: true,
+                    material: material(0.9),
+                    sub_mesh_materials: Vec::new(),
+                    parent: Some(NodeObject::Model(0)),
+                },
+            ],
+            directional_lights: vec![DirectionalLightDescription {
+                position: Vector3::new(-2.0, 4.0, -1.0),
+                direction: Vector3::new(0.4364358, -0.8728716, 0.2182179),
+                info: DirectionalLightInfo {
+                    color: Vector3::new(1.0, 0.95, 0.8),
+                },
+                resolution: (2048, 1024),
+                cascade_count: 3,
+                split_lambda: 0.65,
+                debug_cascades: true,
+                shadow_settings: shadow_settings(1.0),
+                parent: None,
+            }],
+            spot_lights: vec![SpotLightDescription {
+                position: Vector3::new(-2.0, 0.0, 0.0),
+                direction: Vector3::new(0.0, -0.6, 0.8),
+                horizontal_fov: 1.2,
+                info: SpotLightInfo {
+                    color: Vector3::new(0.2, 0.4, 1.0),
+                    radius: 14.5,
+                },
+                resolution: (4096, 2048),
+                shadow_settings: shadow_settings(2.0),
+                parent: Some(NodeObject::Camera),
+            }],
+            point_lights: vec![
+                PointLightDescription {
+                    position: Vector3::new(0.5, 1.5, -0.5),
+                    info: PointLightInfo {
+                        color: Vector3::new(1.0, 0.3, 0.1),
+                        radius: 6.5,
+                    },
+                    resolution: 512,
+                    shadow_bias: 0.07,
+                    cast_shadows: false,
+                    parent: Some(NodeObject::Model(1)),
+                },
+                PointLightDescription {
+                    position: Vector3::new(3.0, 0.25, 8.0),
+                    info: PointLightInfo {
+                        color: Vector3::new(0.0, 1.0, 0.5),
+                        radius: 2.0,
+                    },
+                    resolution: 256,
+                    shadow_bias: 0.01,
+                    cast_shadows: true,
+                    parent: Some(NodeObject::SpotLight(0)),
+                },
+            ],
+        }
+    }
+
+    fn to_json(scene: &Scene) -> Value {
+        return serde_json::to_value(scene).unwrap();
+    }
+
+    fn from_json(json: &Value) -> Result<Scene, String> {
+        return Scene::parse(&json.to_string());
+    }
+
+    #[test]
+    fn save_then_load_keeps_every_field() {
+        let scene = test_scene();
+        let text = serde_json::to_string_pretty(&scene).unwrap();
+        let loaded = Scene::parse(&text).unwrap();
+        assert_eq!(loaded, scene);
+
+        let path = std::env::temp_dir().join(format!("scene_test_{}", std::process::id()));
+        let file = path.join("scene.json");
+        scene.save(&file).unwrap();
+        let loaded = Scene::load(&file).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+        assert_eq!(loaded, scene);
+    }
+
+    #[test]
+    fn errors_name_the_broken_key() {
+        let mut json = to_json(&test_scene());
+        json["models"][1].as_object_mut().unwrap().remove("scale");
+        assert!(from_json(&json)
+            .unwrap_err()
+            .starts_with("models[1]: missing field `scale` at line"));
+        let mut json = to_json(&test_scene());
+        json["spot_lights"][0]["shadow_settings"]["filter"] = Value::from("blurry");
+        assert!(from_json(&json)
+            .unwrap_err()
+            .starts_with("spot_lights[0].shadow_settings.filter: unknown variant `blurry`"));
+        let mut json = to_json(&test_scene());
+        json["groups"][1]["parent"] = Value::from("grup 0");
+        assert!(from_json(&json).unwrap_err().starts_with(
+            "groups[1].parent: should be like \"group 0\" or \"camera\", not \"grup 0\""
+        ));
+
+        let mut scene = test_scene();
+        scene.point_lights[0].parent = Some(NodeObject::Model(7));
+        assert_eq!(
+            from_json(&to_json(&scene)).unwrap_err(),
+            "the parent of point_light 0 is model 7, which isn't in the scene"
+        );
+    }
+
+    #[test]
+    fn broken_cameras_are_errors() {
+        let mut scene = test_scene();
+        scene.camera.fovy = 3.5;
+        assert_eq!(
+            from_json(&to_json(&scene)).unwrap_err(),
+            "camera.fovy should be more than 0 and less than pi radians, not 3.5"
+        );
+        let mut scene = test_scene();
+        scene.camera.near = 0.0;
+        assert_eq!(
+            from_json(&to_json(&scene)).unwrap_err(),
+            "camera.near should be more than 0, not 0"
+        );
+        let mut scene = test_scene();
+        scene.camera.far = scene.camera.near;
+        assert_eq!(
+            from_json(&to_json(&scene)).unwrap_err(),
+            "camera.far should be more than camera.near (0.05), not 0.05"
+        );
+    }
+
+    #[test]
+    fn shadow_maps_out_of_range_are_errors() {
+        let mut scene = test_scene();
+        scene.directional_lights[0].cascade_count = MAX_CASCADES + 4;
+        assert_eq!(
+            from_json(&to_json(&scene)).unwrap_err(),
+            "directional_lights[0].cascade_count should be from 1 to 8, not 12"
+        );
+        scene.directional_lights[0].cascade_count = 0;
+        assert!(from_json(&to_json(&scene)).is_err());
+        let mut scene = test_scene();
+        scene.directional_lights[0].resolution = (2048, 10_000);
+        assert_eq!(
+            from_json(&to_json(&scene)).unwrap_err(),
+            "directional_lights[0].resolution should be 2 whole numbers from 16 to 8192"
+        );
+
+        let mut scene = test_scene();
+        scene.spot_lights[0].resolution = (0, 512);
+        assert_eq!(
+            from_json(&to_json(&scene)).unwrap_err(),
+            "spot_lights[0].resolution should be 2 whole numbers from 16 to 8192"
+        );
+        let mut scene = test_scene();
+        scene.point_lights[1].resolution = 100_000;
+        assert_eq!(
+            from_json(&to_json(&scene)).unwrap_err(),
+            "point_lights[1].resolution should be from 16 to 8192, not 100000"
+        );
+    }
+
+    /// the object the node of object is parented to
+    fn parent_of(graph: &SceneGraph, object: NodeObject) -> Option<NodeObject> {
+        let node = graph.nodes.iter().find(|node| node.object == object)?;
+        return node.parent.map(|parent| graph.nodes[parent].object);
+    }
+
+    #[test]
+    fn parents_survive_capture_save_load_and_instantiate() {
+        // point lights and groups don't need a gl context
+        let mut graph = SceneGraph::new();
+        let mut group = Group::new("Rig".to_owned());
+        group.position = Vector3::new(1.0, 2.0, 3.0);
+        let rig = graph.add_group(group);
+        let mut group = Group::new("Arm".to_owned());
+        group.rotation = Euler::new(Rad(0.0), Rad(1.0), Rad(0.0));
+        let arm = graph.add_group(group);
+        graph.set_parent(arm, Some(rig));
+        let mut point_lights: Vec<PointLight> = (0..3)
+            .map(|i| {
+                let mut light = PointLight::new(256);
+                light.position = Vector3::new(i as f32, 0.5, -1.0);
+                light
+            })
+            .collect();
+        let mut camera = Camera::new(16.0 / 9.0, 1.0, 0.1, 100.0);
+        let light_nodes: Vec<usize> = (0..3)
+            .map(|i| graph.add_node(NodeObject::PointLight(i)))
+            .collect();
+        let camera_node = graph.add_node(NodeObject::Camera);
+        graph.set_parent(light_nodes[0], Some(arm));
+        graph.set_parent(light_nodes[1], Some(light_nodes[0]));
+        graph.set_parent(camera_node, Some(rig));
+
+        let mut models = Vec::new();
+        let mut instanced_models = Vec::new();
+        let mut spot_lights = Vec::new();
+        let mut directional_lights = Vec::new();
+        let mut objects = SceneObjects {
+            models: &mut models,
+            instanced_models: &mut instanced_models,
+            point_lights: &mut point_lights,
+            spot_lights: &mut spot_lights,
+            directional_lights: &mut directional_lights,
+            camera: &mut camera,
+        };
+        graph.update(&mut objects);
+        let scene = Scene::capture(&graph, &objects, test_scene().settings);
+        let path = std::env::temp_dir().join(format!("scene_parents_{}", std::process::id()));
+        let file = path.join("scene.json");
+        scene.save(&file).unwrap();
+        let loaded = Scene::load(&file).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+
+        let mut loaded_graph = SceneGraph::new();
+        let mut loaded_point_lights = Vec::new();
+        let mut loaded_camera = Camera::new(16.0 / 9.0, 1.0, 0.1, 100.0);
+        let mut loaded_objects = SceneObjects {
+            models: &mut Vec::new(),
+            instanced_models: &mut Vec::new(),
+            point_lights: &mut loaded_point_lights,
+            spot_lights: &mut Vec::new(),
+            directional_lights: &mut Vec::new(),
+            camera: &mut loaded_camera,
+        };
+        loaded
+            .instantiate(
+                &mut loaded_graph,
+                &mut loaded_objects,
+                &mut AssetCache::new(),
+            )
+            .unwrap();
+        loaded_graph.update(&mut loaded_objects);
+
+        for object in [
+            NodeObject::Group(0),
+            NodeObject::Group(1),
+            NodeObject::PointLight(0),
+            NodeObject::PointLight(1),
+            NodeObject::PointLight(2),
+            NodeObject::Camera,
+        ] {
+            assert_eq!(
+                parent_of(&loaded_graph, object),
+                parent_of(&graph, object),
+                "{:?}",
+                object
+            );
+        }
+        assert_eq!(
+            parent_of(&loaded_graph, NodeObject::PointLight(1)),
+            Some(NodeObject::PointLight(0))
+        );
+        for (loaded, light) in loaded_point_lights.iter().zip(objects.point_lights.iter()) {
+            assert!((loaded.world_position() - light.world_position()).magnitude() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,528 @@
+use std::collections::HashSet;
+
+use cgmath::{Euler, Matrix4, Rad, SquareMatrix, Vector3, Zero};
+use egui_sdl2_gl::egui::{self, collapsing_header::CollapsingState, Widget};
+
+use crate::{
+    instancing::InstancedModel,
+    models::{transforms_to_matrix, Camera, DirectionalLight, Model, PointLight, SpotLight},
+};
+
+/// something with a transform that can go in the scene graph
+pub trait SceneObject {
+    /// the transform relative to the parent
+    fn local_matrix(&self) -> Matrix4<f32>;
+    /// called with the parent's world matrix whenever it changes
+    fn set_parent_matrix(&mut self, parent_matrix: Matrix4<f32>);
+}
+
+/// a node with only a transform, to move several things together
+pub struct Group {
+    pub name: String,
+    pub position: Vector3<f32>,
+    pub rotation: Euler<Rad<f32>>,
+    pub scale: Vector3<f32>,
+}
+
+impl Group {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            position: Vector3::zero(),
+            rotation: Euler::new(Rad(0.0), Rad(0.0), Rad(0.0)),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl SceneObject for Group {
+    fn local_matrix(&self) -> Matrix4<f32> {
+        transforms_to_matrix(self.position, self.rotation.into(), self.scale)
+    }
+
+    fn set_parent_matrix(&mut self, _parent_matrix: Matrix4<f32>) {}
+}
+
+impl Widget for &mut Group {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let drag_vector = |ui: &mut egui::Ui, vector: &mut Vector3<f32>, speed: f64| {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut vector.x)
+                        .prefix("X ")
+                        .speed(speed),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut vector.y)
+                        .prefix("Y ")
+                        .speed(speed),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut vector.z)
+                        .prefix("Z ")
+                        .speed(speed),
+                );
+            });
+        };
+        ui.group(|ui| {
+            ui.text_edit_singleline(&mut self.name);
+            ui.label("Position");
+            drag_vector(ui, &mut self.position, 0.01);
+            ui.label("Rotation");
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut self.rotation.x.0)
+                        .prefix("X ")
+                        .suffix(" Rad")
+                        .speed(0.01),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut self.rotation.y.0)
+                        .prefix("Y ")
+                        .suffix(" Rad")
+                        .speed(0.01),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut self.rotation.z.0)
+                        .prefix("Z ")
+                        .suffix(" Rad")
+                        .speed(0.01),
+                );
+            });
+            ui.label("Scale");
+            drag_vector(ui, &mut self.scale, 0.01);
+        })
+        .response
+    }
+}
+
+/// what a node stands for, the index is into the list of that kind of object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeObject {
+    Group(usize),
+    Model(usize),
+    InstancedModel(usize),
+    PointLight(usize),
+    SpotLight(usize),
+    DirectionalLight(usize),
+    Camera,
+}
+
+pub struct SceneNode {
+    pub object: NodeObject,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// the object's local matrix when the world matrix was last worked out
+    local_matrix: Matrix4<f32>,
+    world_matrix: Matrix4<f32>,
+    /// set when the node is added or moved, so its world matrix is worked out even if its
+    /// transform didn't change
+    dirty: bool,
+}
+
+/// looks up the objects the nodes stand for, groups are kept by the scene graph itself
+pub trait SceneObjectSource {
+    fn object(&mut self, object: NodeObject) -> Option<&mut dyn SceneObject>;
+}
+
+/// the objects that live outside of the scene graph, borrowed from wherever they are kept
+pub struct SceneObjects<'a> {
+    pub models: &'a mut Vec<Model>,
+    pub instanced_models: &'a mut Vec<InstancedModel>,
+    pub point_lights: &'a mut Vec<PointLight>,
+    pub spot_lights: &'a mut Vec<SpotLight>,
+    pub directional_lights: &'a mut Vec<DirectionalLight>,
+    pub camera: &'a mut Camera,
+}
+
+impl SceneObjects<'_> {
+    /// every object there should be a node for
+    fn all(&self) -> Vec<NodeObject> {
+        let mut objects = vec![NodeObject::Camera];
+        objects.extend((0..self.models.len()).map(NodeObject::Model));
+        objects.extend((0..self.instanced_models.len()).map(NodeObject::InstancedModel));
+        objects.extend((0..self.directional_lights.len()).map(NodeObject::DirectionalLight));
+        objects.extend((0..self.spot_lights.len()).map(NodeObject::SpotLight));
+        objects.extend((0..self.point_lights.len()).map(NodeObject::PointLight));
+        return objects;
+    }
+
+    fn label(&self, object: NodeObject) -> String {
+        match object {
+            NodeObject::Model(i) => self.models[i].name.clone(),
+            NodeObject::InstancedModel(i) => self.instanced_models[i].model.name.clone(),
+            NodeObject::PointLight(i) => format!("Point Light {}", i),
+            NodeObject::SpotLight(i) => format!("Spot Light {}", i),
+            NodeObject::DirectionalLight(i) => format!("Directional Light {}", i),
+            NodeObject::Camera => "Camera".to_owned(),
+            NodeObject::Group(_) => String::new(),
+        }
+    }
+
+    fn ui(&mut self, object: NodeObject, ui: &mut egui::Ui) {
+        match object {
+            NodeObject::Model(i) => ui.add(&mut self.models[i]),
+            NodeObject::InstancedModel(i) => ui.add(&mut self.instanced_models[i]),
+            NodeObject::PointLight(i) => ui.add(&mut self.point_lights[i]),
+            NodeObject::SpotLight(i) => ui.add(&mut self.spot_lights[i]),
+            NodeObject::DirectionalLight(i) => ui.add(&mut self.directional_lights[i]),
+            NodeObject::Camera => ui.add(&mut *self.camera),
+            NodeObject::Group(_) => return,
+        };
+    }
+}
+
+impl SceneObjectSource for SceneObjects<'_> {
+    fn object(&mut self, object: NodeObject) -> Option<&mut dyn SceneObject> {
+        match object {
+            NodeObject::Model(i) => Some(&mut self.models[i]),
+            NodeObject::InstancedModel(i) => Some(&mut self.instanced_models[i]),
+            NodeObject::PointLight(i) => Some(&mut self.point_lights[i]),
+            NodeObject::SpotLight(i) => Some(&mut self.spot_lights[i]),
+            NodeObject::DirectionalLight(i) => Some(&mut self.directional_lights[i]),
+            NodeObject::Camera => Some(&mut *self.camera),
+            NodeObject::Group(_) => None,
+        }
+    }
+}
+
+/// parents models, lights and the camera to each other. every object keeps its own transform
+/// relative to its parent, the scene graph chains them and hands every object its parent's
+/// world matrix, only working them out again for the nodes that moved
+pub struct SceneGraph {
+    pub nodes: Vec<SceneNode>,
+    pub groups: Vec<Group>,
+    /// the objects that have a node
+    known_objects: HashSet<NodeObject>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            groups: Vec::new(),
+            known_objects: HashSet::new(),
+        }
+    }
+
+    /// a node at the top of the tree
+    pub fn add_node(&mut self, object: NodeObject) -> usize {
+        self.nodes.push(SceneNode {
+            object,
+            parent: None,
+            children: Vec::new(),
+            local_matrix: Matrix4::identity(),
+            world_matrix: Matrix4::identity(),
+            dirty: true,
+        });
+        self.known_objects.insert(object);
+        return self.nodes.len() - 1;
+    }
+
+    pub fn add_group(&mut self, group: Group) -> usize {
+        self.groups.push(group);
+        return self.add_node(NodeObject::Group(self.groups.len() - 1));
+    }
+
+    /// gives the objects that were added since the last frame a node at the top of the tree
+    fn add_missing_nodes(&mut self, objects: &SceneObjects) {
+        for object in objects.all() {
+            if !self.known_objects.contains(&object) {
+                self.add_node(object);
+            }
+        }
+    }
+
+    pub fn world_matrix(&self, node: usize) -> Matrix4<f32> {
+        self.nodes[node].world_matrix
+    }
+
+    /// whether ancestor is node or one of the nodes above it
+    pub fn is_ancestor(&self, ancestor: usize, node: usize) -> bool {
+        let mut current = Some(node);
+        while let Some(index) = current {
+            if index == ancestor {
+                return true;
+            }
+            current = self.nodes[index].parent;
+        }
+        return false;
+    }
+
+    /// moves node under parent, or to the top of the tree with None. the node keeps its local
+    /// transform, so it moves with its new parent. returns false without changing anything if
+    /// parent is node or one of its children
+    pub fn set_parent(&mut self, node: usize, parent: Option<usize>) -> bool {
+        if let Some(parent) = parent {
+            if self.is_ancestor(node, parent) {
+                return false;
+            }
+        }
+        if let Some(old_parent) = self.nodes[node].parent {
+            self.nodes[old_parent]
+                .children
+                .retain(|child| *child != node);
+        }
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(node);
+        }
+        self.nodes[node].parent = parent;
+        self.nodes[node].dirty = true;
+        return true;
+    }
+
+    pub fn roots(&self) -> Vec<usize> {
+        (0..self.nodes.len())
+            .filter(|node| self.nodes[*node].parent.is_none())
+            .collect()
+    }
+
+    /// call once a frame before anything is drawn
+    pub fn update(&mut self, objects: &mut SceneObjects) {
+        self.add_missing_nodes(objects);
+        self.update_world_matrices(objects);
+    }
+
+    /// works out the world matrices of the nodes whose transform or parent changed, and of
+    /// everything below them
+    pub fn update_world_matrices(&mut self, objects: &mut impl SceneObjectSource) {
+        // node, parent world matrix, whether the parent's world matrix changed
+        let mut stack: Vec<(usize, Matrix4<f32>, bool)> = self
+            .roots()
+            .into_iter()
+            .rev()
+            .map(|root| (root, Matrix4::identity(), false))
+            .collect();
+        while let Some((index, parent_matrix, parent_changed)) = stack.pop() {
+            let object = self.nodes[index].object;
+            let local_matrix = match object {
+                NodeObject::Group(group) => self.groups[group].local_matrix(),
+                _ => match objects.object(object) {
+                    Some(object) => object.local_matrix(),
+                    None => Matrix4::identity(),
+                },
+            };
+            let node = &mut self.nodes[index];
+            let changed = node.dirty || parent_changed || node.local_matrix != local_matrix;
+            if changed {
+                node.local_matrix = local_matrix;
+                node.world_matrix = parent_matrix * local_matrix;
+                if node.dirty || parent_changed {
+                    if let Some(object) = objects.object(object) {
+                        object.set_parent_matrix(parent_matrix);
+                    }
+                }
+                node.dirty = false;
+            }
+            let world_matrix = node.world_matrix;
+            for child in node.children.iter().rev() {
+                stack.push((*child, world_matrix, changed));
+            }
+        }
+    }
+
+    fn label(&self, node: usize, objects: &SceneObjects) -> String {
+        match self.nodes[node].object {
+            NodeObject::Group(group) => self.groups[group].name.clone(),
+            object => objects.label(object),
+        }
+    }
+
+    /// the tree of every node, drag a node's name onto another node to parent it there
+    pub fn explorer_ui(&mut self, ui: &mut egui::Ui, objects: &mut SceneObjects) {
+        if ui.button("Add Group").clicked() {
+            self.add_group(Group::new(format!("Group {}", self.groups.len())));
+        }
+        // nodes are only moved once the whole tree is drawn
+        let mut moved: Option<(usize, Option<usize>)> = None;
+        for root in self.roots() {
+            self.node_ui(ui, objects, root, &mut moved);
+        }
+        let (_, dropped) =
+            ui.dnd_drop_zone::<usize, ()>(egui::Frame::default().inner_margin(4.0), |ui| {
+                ui.label("Drop here to unparent");
+            });
+        if let Some(node) = dropped {
+            moved = Some((*node, None));
+        }
+        if let Some((node, parent)) = moved {
+            self.set_parent(node, parent);
+        }
+    }
+
+    fn node_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        objects: &mut SceneObjects,
+        node: usize,
+        moved: &mut Option<(usize, Option<usize>)>,
+    ) {
+        let id = egui::Id::new(("scene_node", node));
+        let label = self.label(node, objects);
+        let children = self.nodes[node].children.clone();
+        let (_, header, _) = CollapsingState::load_with_default_open(ui.ctx(), id, false)
+            .show_header(ui, |ui| {
+                ui.dnd_drag_source(id.with("drag"), node, |ui| {
+                    ui.label(label);
+                });
+            })
+            .body(|ui| {
+                match self.nodes[node].object {
+                    NodeObject::Group(group) => {
+                        ui.add(&mut self.groups[group]);
+                    }
+                    object => objects.ui(object, ui),
+                }
+                for child in children {
+                    self.node_ui(ui, objects, child, moved);
+                }
+            });
+        if let Some(dragged) = header.response.dnd_release_payload::<usize>() {
+            *moved = Some((*dragged, Some(node)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, InnerSpace, Transform};
+
+    use super::*;
+
+    /// stands in for a model, counting how often its parent matrix is set
+    struct TestObject {
+        position: Vector3<f32>,
+        parent_matrix: Matrix4<f32>,
+        parent_updates: usize,
+    }
+
+    impl SceneObject for TestObject {
+        fn local_matrix(&self) -> Matrix4<f32> {
+            Matrix4::from_translation(self.position)
+        }
+
+        fn set_parent_matrix(&mut self, parent_matrix: Matrix4<f32>) {
+            self.parent_matrix = parent_matrix;
+            self.parent_updates += 1;
+        }
+    }
+
+    struct TestObjects(Vec<TestObject>);
+
+    impl SceneObjectSource for TestObjects {
+        fn object(&mut self, object: NodeObject) -> Option<&mut dyn SceneObject> {
+            match object {
+                NodeObject::Model(i) => Some(&mut self.0[i]),
+                _ => None,
+            }
+        }
+    }
+
+    fn test_object(position: Vector3<f32>) -> TestObject {
+        TestObject {
+            position,
+            parent_matrix: Matrix4::identity(),
+            parent_updates: 0,
+        }
+    }
+
+    fn world_position(graph: &SceneGraph, node: usize) -> Vector3<f32> {
+        graph.world_matrix(node).w.truncate()
+    }
+
+    #[test]
+    fn world_matrices_chain_through_parents() {
+        let mut graph = SceneGraph::new();
+        let mut cart = Group::new("Cart".to_owned());
+        cart.position = Vector3::new(10.0, 0.0, 0.0);
+        cart.rotation = Euler::new(Rad(0.0), Deg(90.0).into(), Rad(0.0));
+        let cart = graph.add_group(cart);
+        let lamp = graph.add_node(NodeObject::Model(0));
+        assert!(graph.set_parent(lamp, Some(cart)));
+        let mut objects = TestObjects(vec![test_object(Vector3::new(1.0, 2.0, 0.0))]);
+        graph.update_world_matrices(&mut objects);
+
+        // a quarter turn around y takes +x to -z
+        let expected = Vector3::new(10.0, 2.0, -1.0);
+        assert!((world_position(&graph, lamp) - expected).magnitude() < 1e-5);
+        let lamp_object = &objects.0[0];
+        let through_parent = (lamp_object.parent_matrix * lamp_object.local_matrix())
+            .transform_point(cgmath::Point3::new(0.0, 0.0, 0.0));
+        assert!(
+            (Vector3::new(through_parent.x, through_parent.y, through_parent.z) - expected)
+                .magnitude()
+                < 1e-5
+        );
+    }
+
+    #[test]
+    fn only_moved_nodes_are_updated() {
+        let mut graph = SceneGraph::new();
+        let cart = graph.add_group(Group::new("Cart".to_owned()));
+        let lamp = graph.add_node(NodeObject::Model(0));
+        let other = graph.add_node(NodeObject::Model(1));
+        graph.set_parent(lamp, Some(cart));
+        let mut objects = TestObjects(vec![
+            test_object(Vector3::new(0.0, 1.0, 0.0)),
+            test_object(Vector3::zero()),
+        ]);
+        graph.update_world_matrices(&mut objects);
+        assert_eq!(objects.0[0].parent_updates, 1);
+        assert_eq!(objects.0[1].parent_updates, 1);
+
+        // nothing moved
+        graph.update_world_matrices(&mut objects);
+        assert_eq!(objects.0[0].parent_updates, 1);
+
+        // moving the lamp itself changes its world matrix but not its parent's
+        objects.0[0].position = Vector3::new(0.0, 2.0, 0.0);
+        graph.update_world_matrices(&mut objects);
+        assert_eq!(objects.0[0].parent_updates, 1);
+        assert!((world_position(&graph, lamp) - Vector3::new(0.0, 2.0, 0.0)).magnitude() < 1e-5);
+
+        // moving the cart moves the lamp with it, and leaves the other model alone
+        graph.groups[0].position = Vector3::new(5.0, 0.0, 0.0);
+        graph.update_world_matrices(&mut objects);
+        assert_eq!(objects.0[0].parent_updates, 2);
+        assert_eq!(objects.0[1].parent_updates, 1);
+        assert!((world_position(&graph, lamp) - Vector3::new(5.0, 2.0, 0.0)).magnitude() < 1e-5);
+        assert!((world_position(&graph, other) - Vector3::zero()).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn reparenting_keeps_the_local_transform() {
+        let mut graph = SceneGraph::new();
+        let mut cart = Group::new("Cart".to_owned());
+        cart.position = Vector3::new(3.0, 0.0, 0.0);
+        let cart = graph.add_group(cart);
+        let lamp = graph.add_node(NodeObject::Model(0));
+        let mut objects = TestObjects(vec![test_object(Vector3::new(0.0, 1.0, 0.0))]);
+        graph.update_world_matrices(&mut objects);
+        assert!((world_position(&graph, lamp) - Vector3::new(0.0, 1.0, 0.0)).magnitude() < 1e-5);
+
+        graph.set_parent(lamp, Some(cart));
+        graph.update_world_matrices(&mut objects);
+        assert!((world_position(&graph, lamp) - Vector3::new(3.0, 1.0, 0.0)).magnitude() < 1e-5);
+        assert_eq!(graph.nodes[cart].children, vec![lamp]);
+
+        graph.set_parent(lamp, None);
+        graph.update_world_matrices(&mut objects);
+        assert!((world_position(&graph, lamp) - Vector3::new(0.0, 1.0, 0.0)).magnitude() < 1e-5);
+        assert!(graph.nodes[cart].children.is_empty());
+        assert_eq!(objects.0[0].parent_matrix, Matrix4::identity());
+    }
+
+    #[test]
+    fn nodes_cant_be_parented_to_their_children() {
+        let mut graph = SceneGraph::new();
+        let a = graph.add_group(Group::new("A".to_owned()));
+        let b = graph.add_group(Group::new("B".to_owned()));
+        let c = graph.add_group(Group::new("C".to_owned()));
+        assert!(graph.set_parent(b, Some(a)));
+        assert!(graph.set_parent(c, Some(b)));
+        assert!(!graph.set_parent(a, Some(c)));
+        assert!(!graph.set_parent(a, Some(a)));
+        assert_eq!(graph.nodes[a].parent, None);
+        assert_eq!(graph.roots(), vec![a]);
+    }
+}
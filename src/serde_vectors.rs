@@ -0,0 +1,57 @@
+use cgmath::{Quaternion, Vector2, Vector3};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// a Vector2 written as [x, y], use with #[serde(with = "crate::serde_vectors::vector2")]
+pub mod vector2 {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        vector: &Vector2<f32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        [vector.x, vector.y].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vector2<f32>, D::Error> {
+        <[f32; 2]>::deserialize(deserializer).map(Vector2::from)
+    }
+}
+
+/// a Vector3 written as [x, y, z]
+pub mod vector3 {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        vector: &Vector3<f32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        [vector.x, vector.y, vector.z].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vector3<f32>, D::Error> {
+        <[f32; 3]>::deserialize(deserializer).map(Vector3::from)
+    }
+}
+
+/// a Quaternion written as [x, y, z, w], the order gltf uses
+pub mod quaternion {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        quaternion: &Quaternion<f32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        [quaternion.v.x, quaternion.v.y, quaternion.v.z, quaternion.s].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Quaternion<f32>, D::Error> {
+        let [x, y, z, w] = <[f32; 4]>::deserialize(deserializer)?;
+        Ok(Quaternion::new(w, x, y, z))
+    }
+}
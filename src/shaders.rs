@@ -1,13 +1,21 @@
 use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashSet},
     ffi::{CStr, CString},
     fs,
+    path::{Path, PathBuf},
     ptr::{null, null_mut},
+    rc::{Rc, Weak},
+    time::{Instant, SystemTime},
 };
 
-use cgmath::{Matrix, Matrix4, Vector2, Vector3};
+use cgmath::{Matrix, Matrix3, Matrix4, Vector2, Vector3, Vector4};
+use egui_sdl2_gl::egui::{self, Color32, RichText, Widget};
 use gl::types::GLenum;
 
-use crate::material_structs::{MaterialInfo, PointLightInfo, SpotLightInfo};
+use crate::errors::{shader_stage_name, EngineError};
+use crate::material_structs::{MaterialInfo, PointLightInfo, ShadowSettings, SpotLightInfo};
+use crate::uniform_blocks::UNIFORM_BLOCK_BINDINGS;
 
 pub struct Shader {
     id: u32,
@@ -53,13 +61,220 @@ impl Drop for Shader {
         }
     }
 }
+/// a shader file a program was built from
+#[derive(Debug)]
+struct ShaderSource {
+    path: PathBuf,
+    kind: GLenum,
+    modified: Cell<Option<SystemTime>>,
+}
+
+impl ShaderSource {
+    fn new(path: &str, kind: GLenum) -> Self {
+        let path = PathBuf::from(path);
+        let modified = Cell::new(source_modified_time(&path));
+        Self {
+            path,
+            kind,
+            modified,
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// the newest of a shader file and the files it includes, so editing an include reloads its shaders too
+fn source_modified_time(path: &Path) -> Option<SystemTime> {
+    let includes: Vec<PathBuf> = fs::read_to_string(path)
+        .map(|text| {
+            text.lines()
+                .filter_map(|line| include_path(path, line))
+                .collect()
+        })
+        .unwrap_or_default();
+    includes
+        .iter()
+        .map(|include| modified_time(include))
+        .fold(modified_time(path), |newest, modified| newest.max(modified))
+}
+
+/// the file an `#include "file"` line names, relative to the shader it is in
+fn include_path(path: &Path, line: &str) -> Option<PathBuf> {
+    let name = line
+        .trim()
+        .strip_prefix("#include")?
+        .trim()
+        .strip_prefix('"')?
+        .strip_suffix('"')?;
+    return Some(path.parent().unwrap_or(Path::new("")).join(name));
+}
+
+/// pastes the file of every `#include "file"` line in place of the line, includes can't include
+/// anything themselves. a #line after each one keeps the line numbers in the gl log right
+fn resolve_includes(path: &Path, text: &str) -> Result<String, EngineError> {
+    let mut resolved = String::with_capacity(text.len());
+    for (index, line) in text.lines().enumerate() {
+        match include_path(path, line) {
+            Some(include) => {
+                let included = fs::read_to_string(&include).map_err(|source| EngineError::Io {
+                    path: include.clone(),
+                    source,
+                })?;
+                resolved.push_str(&included);
+                if !included.ends_with('\n') {
+                    resolved.push('\n');
+                }
+                resolved.push_str(&format!("#line {}\n", index + 2));
+            }
+            None => {
+                resolved.push_str(line);
+                resolved.push('\n');
+            }
+        }
+    }
+    return Ok(resolved);
+}
+
+/// an active uniform found by reflecting a linked program
+#[derive(Debug, Clone, Copy)]
+pub struct UniformInfo {
+    pub location: i32,
+    /// the glsl type, like gl::FLOAT_VEC3
+    pub kind: GLenum,
+    /// the array length, 1 when it isn't an array
+    pub size: i32,
+    /// a single element of an array, the whole array is also listed under its name without the index
+    pub array_element: bool,
+}
+
+/// asks gl for every active uniform of a linked program, arrays are listed by their name and by each element
+fn reflect_uniforms(id: u32) -> BTreeMap<String, UniformInfo> {
+    let mut uniforms: BTreeMap<String, UniformInfo> = BTreeMap::new();
+    let mut count: i32 = 0;
+    let mut max_name_length: i32 = 0;
+    unsafe {
+        gl::GetProgramiv(id, gl::ACTIVE_UNIFORMS, &mut count);
+        gl::GetProgramiv(id, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_name_length);
+    }
+    for index in 0..count as u32 {
+        let mut name_buffer: Vec<u8> = vec![0; max_name_length.max(1) as usize];
+        let mut name_length: i32 = 0;
+        let mut size: i32 = 0;
+        let mut kind: GLenum = 0;
+        unsafe {
+            gl::GetActiveUniform(
+                id,
+                index,
+                name_buffer.len() as i32,
+                &mut name_length,
+                &mut size,
+                &mut kind,
+                name_buffer.as_mut_ptr() as *mut i8,
+            );
+        }
+        let name = String::from_utf8_lossy(&name_buffer[..name_length as usize]).into_owned();
+        let location = uniform_location(id, &name);
+        // uniforms inside uniform blocks don't have a location
+        if location < 0 {
+            continue;
+        }
+        let base_name = name.strip_suffix("[0]").unwrap_or(&name).to_owned();
+        uniforms.insert(
+            base_name.clone(),
+            UniformInfo {
+                location,
+                kind,
+                size,
+                array_element: false,
+            },
+        );
+        if name.ends_with("[0]") {
+            for element in 0..size {
+                let element_name = format!("{}[{}]", base_name, element);
+                uniforms.insert(
+                    element_name.clone(),
+                    UniformInfo {
+                        location: uniform_location(id, &element_name),
+                        kind,
+                        size: 1,
+                        array_element: true,
+                    },
+                );
+            }
+        }
+    }
+    return uniforms;
+}
+
+fn uniform_location(id: u32, name: &str) -> i32 {
+    let name: &CStr = &CString::new(name).unwrap();
+    unsafe { gl::GetUniformLocation(id, name.as_ptr()) }
+}
+
+#[derive(Debug)]
+struct ProgramInner {
+    /// swapped by the shader registry when the sources are reloaded
+    id: Cell<u32>,
+    sources: Vec<ShaderSource>,
+    /// name to location and type, filled after every link so the setters don't have to ask gl
+    uniforms: RefCell<BTreeMap<String, UniformInfo>>,
+    /// uniforms that were set but don't exist or have another type, each is only reported once
+    warnings: RefCell<HashSet<String>>,
+}
+
+impl ProgramInner {
+    /// replaces the gl program, the old one is deleted and the uniforms are reflected again
+    fn swap(&self, id: u32) {
+        unsafe {
+            gl::DeleteProgram(self.id.get());
+        }
+        self.id.set(id);
+        *self.uniforms.borrow_mut() = reflect_uniforms(id);
+        self.warnings.borrow_mut().clear();
+    }
+}
+
+impl Drop for ProgramInner {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.id.get());
+        }
+    }
+}
+
+thread_local! {
+    /// every program created from files, so the shader registry can find them to reload
+    static PROGRAMS: RefCell<Vec<Weak<ProgramInner>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// clones share the same gl program, it is deleted when the last clone is dropped
 #[derive(Debug, Clone)]
 pub struct Program {
-    id: u32,
+    inner: Rc<ProgramInner>,
 }
 
 impl Program {
-    fn from_shaders(shaders: &[Shader]) -> Result<Self, String> {
+    fn from_sources(sources: Vec<ShaderSource>) -> Result<Self, EngineError> {
+        let id = build_program(&sources)?;
+        let inner = Rc::new(ProgramInner {
+            id: Cell::new(id),
+            sources,
+            uniforms: RefCell::new(reflect_uniforms(id)),
+            warnings: RefCell::new(HashSet::new()),
+        });
+        PROGRAMS.with(|programs| programs.borrow_mut().push(Rc::downgrade(&inner)));
+        Ok(Program { inner })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.inner.id.get()
+    }
+
+    fn link_shaders(shaders: &[Shader]) -> Result<u32, String> {
         let id = unsafe { gl::CreateProgram() };
         for shader in shaders {
             unsafe {
@@ -72,7 +287,7 @@ impl Program {
         }
         let mut success: i32 = 1;
         unsafe {
-            gl::GetProgramiv(id, gl::COMPILE_STATUS, &mut success);
+            gl::GetProgramiv(id, gl::LINK_STATUS, &mut success);
         }
         if success == 0 {
             let mut len: i32 = 0;
@@ -82,6 +297,9 @@ impl Program {
             unsafe {
                 gl::GetProgramInfoLog(id, len, null_mut(), error.as_ptr() as *mut i8);
             }
+            unsafe {
+                gl::DeleteProgram(id);
+            }
 
             return Err(error.to_string_lossy().into_owned());
         }
@@ -91,62 +309,217 @@ impl Program {
                 gl::DetachShader(id, shader.id());
             }
         }
-        Ok(Program { id })
+        // glsl 330 can't give blocks a binding, so every program gets its blocks bound here
+        for (block_name, binding) in UNIFORM_BLOCK_BINDINGS {
+            let block_name: &CStr = &CString::new(*block_name).unwrap();
+            let index = unsafe { gl::GetUniformBlockIndex(id, block_name.as_ptr()) };
+            if index != gl::INVALID_INDEX {
+                unsafe {
+                    gl::UniformBlockBinding(id, index, *binding);
+                }
+            }
+        }
+        Ok(id)
     }
 
     pub fn set(&self) {
         unsafe {
-            gl::UseProgram(self.id);
+            gl::UseProgram(self.id());
+        }
+    }
+    /// a copy of the reflected uniforms, sorted by name
+    pub fn uniforms(&self) -> BTreeMap<String, UniformInfo> {
+        self.inner.uniforms.borrow().clone()
+    }
+    pub fn has_uniform(&self, name: &str) -> bool {
+        self.inner.uniforms.borrow().contains_key(name)
+    }
+    /// how many clones of this program there are, this one included
+    pub fn ref_count(&self) -> usize {
+        Rc::strong_count(&self.inner)
+    }
+    /// whether both are clones of the same program
+    pub fn is_same_program(&self, other: &Program) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+    /// looks a uniform up in the reflection cache, warns once and returns None if it's missing or
+    /// isn't one of the expected types
+    fn location(&self, name: &str, expected: &[GLenum]) -> Option<i32> {
+        let uniforms = self.inner.uniforms.borrow();
+        let warning = match uniforms.get(name) {
+            Some(uniform) if expected.contains(&uniform.kind) => return Some(uniform.location),
+            Some(uniform) => format!(
+                "uniform {} is a {}, not a {}",
+                name,
+                glsl_type_name(uniform.kind),
+                glsl_type_name(expected[0])
+            ),
+            None => format!("uniform {} doesn't exist or isn't used", name),
+        };
+        if self.inner.warnings.borrow_mut().insert(warning.clone()) {
+            println!("warning: {} in {}", warning, self.name());
         }
+        return None;
+    }
+    /// the shader files this program was built from
+    pub fn name(&self) -> String {
+        let paths: Vec<String> = self
+            .inner
+            .sources
+            .iter()
+            .map(|source| source.path.display().to_string())
+            .collect();
+        return paths.join(", ");
     }
     pub fn set_bool(&self, name_a: &str, value: bool) {
-        let name: &CStr = &CString::new(name_a).unwrap();
-        unsafe {
-            gl::Uniform1i(gl::GetUniformLocation(self.id, name.as_ptr()), value as i32);
+        if let Some(location) = self.location(name_a, &[gl::BOOL, gl::INT]) {
+            unsafe {
+                gl::Uniform1i(location, value as i32);
+            }
         }
     }
+    /// also used for samplers, the value is the texture unit
     pub fn set_int(&self, name_a: &str, value: i32) {
-        let name: &CStr = &CString::new(name_a).unwrap();
-        unsafe {
-            gl::Uniform1i(gl::GetUniformLocation(self.id, name.as_ptr()), value as i32);
+        if let Some(location) = self.location(name_a, INT_TYPES) {
+            unsafe {
+                gl::Uniform1i(location, value);
+            }
+        }
+    }
+    pub fn set_uint(&self, name_a: &str, value: u32) {
+        if let Some(location) = self.location(name_a, &[gl::UNSIGNED_INT]) {
+            unsafe {
+                gl::Uniform1ui(location, value);
+            }
         }
     }
     pub fn set_float(&self, name_a: &str, value: f32) {
-        let name: &CStr = &CString::new(name_a).unwrap();
-        unsafe {
-            gl::Uniform1f(gl::GetUniformLocation(self.id, name.as_ptr()), value as f32);
+        if let Some(location) = self.location(name_a, &[gl::FLOAT]) {
+            unsafe {
+                gl::Uniform1f(location, value);
+            }
+        }
+    }
+    pub fn set_matrix3_float(&self, name_a: &str, value: Matrix3<f32>) {
+        if let Some(location) = self.location(name_a, &[gl::FLOAT_MAT3]) {
+            unsafe {
+                gl::UniformMatrix3fv(location, 1, gl::FALSE, value.as_ptr());
+            }
         }
     }
     pub fn set_matrix4_float(&self, name_a: &str, value: Matrix4<f32>) {
-        let name: &CStr = &CString::new(name_a).unwrap();
-        unsafe {
-            gl::UniformMatrix4fv(
-                gl::GetUniformLocation(self.id, name.as_ptr()),
-                1,
-                gl::FALSE,
-                value.as_ptr(),
-            );
+        if let Some(location) = self.location(name_a, &[gl::FLOAT_MAT4]) {
+            unsafe {
+                gl::UniformMatrix4fv(location, 1, gl::FALSE, value.as_ptr());
+            }
+        }
+    }
+    pub fn set_vector4(&self, name_a: &str, value: Vector4<f32>) {
+        if let Some(location) = self.location(name_a, &[gl::FLOAT_VEC4]) {
+            unsafe {
+                gl::Uniform4f(location, value.x, value.y, value.z, value.w);
+            }
         }
     }
     pub fn set_vector3(&self, name_a: &str, value: Vector3<f32>) {
-        let name: &CStr = &CString::new(name_a).unwrap();
-        unsafe {
-            gl::Uniform3f(
-                gl::GetUniformLocation(self.id, name.as_ptr()),
-                value.x,
-                value.y,
-                value.z,
-            );
+        if let Some(location) = self.location(name_a, &[gl::FLOAT_VEC3]) {
+            unsafe {
+                gl::Uniform3f(location, value.x, value.y, value.z);
+            }
         }
     }
     pub fn set_vector2(&self, name_a: &str, value: Vector2<f32>) {
-        let name: &CStr = &CString::new(name_a).unwrap();
-        unsafe {
-            gl::Uniform2f(
-                gl::GetUniformLocation(self.id, name.as_ptr()),
-                value.x,
-                value.y,
-            );
+        if let Some(location) = self.location(name_a, &[gl::FLOAT_VEC2]) {
+            unsafe {
+                gl::Uniform2f(location, value.x, value.y);
+            }
+        }
+    }
+    pub fn set_ivec2(&self, name_a: &str, value: Vector2<i32>) {
+        if let Some(location) = self.location(name_a, &[gl::INT_VEC2]) {
+            unsafe {
+                gl::Uniform2i(location, value.x, value.y);
+            }
+        }
+    }
+    pub fn set_ivec3(&self, name_a: &str, value: Vector3<i32>) {
+        if let Some(location) = self.location(name_a, &[gl::INT_VEC3]) {
+            unsafe {
+                gl::Uniform3i(location, value.x, value.y, value.z);
+            }
+        }
+    }
+    pub fn set_ivec4(&self, name_a: &str, value: Vector4<i32>) {
+        if let Some(location) = self.location(name_a, &[gl::INT_VEC4]) {
+            unsafe {
+                gl::Uniform4i(location, value.x, value.y, value.z, value.w);
+            }
+        }
+    }
+    pub fn set_uvec2(&self, name_a: &str, value: Vector2<u32>) {
+        if let Some(location) = self.location(name_a, &[gl::UNSIGNED_INT_VEC2]) {
+            unsafe {
+                gl::Uniform2ui(location, value.x, value.y);
+            }
+        }
+    }
+    pub fn set_uvec3(&self, name_a: &str, value: Vector3<u32>) {
+        if let Some(location) = self.location(name_a, &[gl::UNSIGNED_INT_VEC3]) {
+            unsafe {
+                gl::Uniform3ui(location, value.x, value.y, value.z);
+            }
+        }
+    }
+    pub fn set_uvec4(&self, name_a: &str, value: Vector4<u32>) {
+        if let Some(location) = self.location(name_a, &[gl::UNSIGNED_INT_VEC4]) {
+            unsafe {
+                gl::Uniform4ui(location, value.x, value.y, value.z, value.w);
+            }
+        }
+    }
+    /// looks up an array by its name without an index and returns how many elements can be set,
+    /// extra values are dropped with a warning
+    fn array_location(&self, name_a: &str, expected: &[GLenum], len: usize) -> Option<(i32, i32)> {
+        let location = self.location(name_a, expected)?;
+        let size = self.inner.uniforms.borrow()[name_a].size;
+        if len > size as usize {
+            let warning = format!("uniform {} has {} elements, not {}", name_a, size, len);
+            if self.inner.warnings.borrow_mut().insert(warning.clone()) {
+                println!("warning: {} in {}", warning, self.name());
+            }
+        }
+        return Some((location, (len as i32).min(size)));
+    }
+    pub fn set_float_array(&self, name_a: &str, values: &[f32]) {
+        if let Some((location, count)) = self.array_location(name_a, &[gl::FLOAT], values.len()) {
+            unsafe {
+                gl::Uniform1fv(location, count, values.as_ptr());
+            }
+        }
+    }
+    pub fn set_int_array(&self, name_a: &str, values: &[i32]) {
+        if let Some((location, count)) = self.array_location(name_a, INT_TYPES, values.len()) {
+            unsafe {
+                gl::Uniform1iv(location, count, values.as_ptr());
+            }
+        }
+    }
+    pub fn set_vector3_array(&self, name_a: &str, values: &[Vector3<f32>]) {
+        if let Some((location, count)) =
+            self.array_location(name_a, &[gl::FLOAT_VEC3], values.len())
+        {
+            unsafe {
+                gl::Uniform3fv(location, count, values.as_ptr() as *const f32);
+            }
+        }
+    }
+    pub fn set_matrix4_array(&self, name_a: &str, values: &[Matrix4<f32>]) {
+        if let Some((location, count)) =
+            self.array_location(name_a, &[gl::FLOAT_MAT4], values.len())
+        {
+            unsafe {
+                gl::UniformMatrix4fv(location, count, gl::FALSE, values.as_ptr() as *const f32);
+            }
         }
     }
     pub fn set_material_info(&self, name_a: &str, value: MaterialInfo) {
@@ -154,6 +527,16 @@ impl Program {
         self.set_vector3(&(name_a.to_owned() + ".diffuse"), value.diffuse);
         self.set_float(&(name_a.to_owned() + ".specular"), value.specular);
         self.set_float(&(name_a.to_owned() + ".shininess"), value.shininess);
+        self.set_float(&(name_a.to_owned() + ".dissolve"), value.dissolve);
+        self.set_int(&(name_a.to_owned() + ".model"), value.model as i32);
+        self.set_vector3(&(name_a.to_owned() + ".baseColor"), value.base_color);
+        self.set_float(&(name_a.to_owned() + ".metallic"), value.metallic);
+        self.set_float(&(name_a.to_owned() + ".roughness"), value.roughness);
+        self.set_float(
+            &(name_a.to_owned() + ".ambientOcclusion"),
+            value.ambient_occlusion,
+        );
+        self.set_vector3(&(name_a.to_owned() + ".emissive"), value.emissive);
     }
     pub fn set_point_light_info(
         &self,
@@ -174,13 +557,13 @@ impl Program {
 
         self.set_vector3(&(name_a.to_owned() + ".Position"), position);
     }
-}
-
-impl Drop for Program {
-    fn drop(&mut self) {
-        unsafe {
-            gl::DeleteProgram(self.id);
-        }
+    pub fn set_shadow_settings(&self, name_a: &str, value: ShadowSettings) {
+        self.set_int(&(name_a.to_owned() + ".filterMode"), value.filter as i32);
+        self.set_int(&(name_a.to_owned() + ".kernelSize"), value.pcf_kernel_size);
+        self.set_float(&(name_a.to_owned() + ".lightSize"), value.light_size);
+        self.set_float(&(name_a.to_owned() + ".constantBias"), value.constant_bias);
+        self.set_float(&(name_a.to_owned() + ".slopeBias"), value.slope_bias);
+        self.set_float(&(name_a.to_owned() + ".normalOffset"), value.normal_offset);
     }
 }
 
@@ -190,31 +573,305 @@ fn create_whitespace_cstring_with_len(len: usize) -> CString {
     unsafe { CString::from_vec_unchecked(buffer) }
 }
 
+/// reads and compiles a single shader file, errors keep the path and stage so the gl log makes sense
+fn compile_shader_file(path: &Path, kind: GLenum) -> Result<Shader, EngineError> {
+    let text = fs::read_to_string(path).map_err(|source| EngineError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let text = resolve_includes(path, &text)?;
+    Shader::from_source(&text, kind).map_err(|log| EngineError::ShaderCompile {
+        path: path.to_path_buf(),
+        stage: shader_stage_name(kind),
+        log,
+    })
+}
+
+/// compiles and links every source, the returned program id is only made if all of them succeed
+fn build_program(sources: &[ShaderSource]) -> Result<u32, EngineError> {
+    let mut shaders: Vec<Shader> = Vec::new();
+    for source in sources {
+        shaders.push(compile_shader_file(&source.path, source.kind)?);
+    }
+    Program::link_shaders(&shaders).map_err(|log| EngineError::ProgramLink {
+        paths: sources.iter().map(|source| source.path.clone()).collect(),
+        log,
+    })
+}
+
 pub fn create_program(
     vert_shader_path: &str,
     frag_shader_path: &str,
-) -> Result<Program, &'static str> {
-    let vert_shader_text = fs::read_to_string(vert_shader_path).unwrap();
-    let frag_shader_text = fs::read_to_string(frag_shader_path).unwrap();
-
-    let vert_shader = Shader::from_source(&vert_shader_text, gl::VERTEX_SHADER).unwrap();
-    let frag_shader = Shader::from_source(&frag_shader_text, gl::FRAGMENT_SHADER).unwrap();
-    let program = Program::from_shaders(&[vert_shader, frag_shader]).unwrap();
-    Ok(program)
+) -> Result<Program, EngineError> {
+    Program::from_sources(vec![
+        ShaderSource::new(vert_shader_path, gl::VERTEX_SHADER),
+        ShaderSource::new(frag_shader_path, gl::FRAGMENT_SHADER),
+    ])
 }
 
 pub fn create_program_with_geometry_shader(
     vert_shader_path: &str,
     frag_shader_path: &str,
     geom_shader_path: &str,
-) -> Result<Program, &'static str> {
-    let vert_shader_text = fs::read_to_string(vert_shader_path).unwrap();
-    let frag_shader_text = fs::read_to_string(frag_shader_path).unwrap();
-    let geom_shader_text = fs::read_to_string(geom_shader_path).unwrap();
-
-    let vert_shader = Shader::from_source(&vert_shader_text, gl::VERTEX_SHADER).unwrap();
-    let frag_shader = Shader::from_source(&frag_shader_text, gl::FRAGMENT_SHADER).unwrap();
-    let geom_shader = Shader::from_source(&geom_shader_text, gl::GEOMETRY_SHADER).unwrap();
-    let program = Program::from_shaders(&[vert_shader, frag_shader, geom_shader]).unwrap();
-    Ok(program)
+) -> Result<Program, EngineError> {
+    Program::from_sources(vec![
+        ShaderSource::new(vert_shader_path, gl::VERTEX_SHADER),
+        ShaderSource::new(frag_shader_path, gl::FRAGMENT_SHADER),
+        ShaderSource::new(geom_shader_path, gl::GEOMETRY_SHADER),
+    ])
+}
+
+pub fn create_compute_program(comp_shader_path: &str) -> Result<Program, EngineError> {
+    Program::from_sources(vec![ShaderSource::new(
+        comp_shader_path,
+        gl::COMPUTE_SHADER,
+    )])
+}
+
+/// types that are set with glUniform1i
+const INT_TYPES: &[GLenum] = &[
+    gl::INT,
+    gl::BOOL,
+    gl::SAMPLER_2D,
+    gl::SAMPLER_3D,
+    gl::SAMPLER_CUBE,
+    gl::SAMPLER_2D_SHADOW,
+    gl::SAMPLER_2D_ARRAY,
+    gl::SAMPLER_2D_ARRAY_SHADOW,
+    gl::SAMPLER_CUBE_SHADOW,
+    gl::SAMPLER_2D_MULTISAMPLE,
+    gl::INT_SAMPLER_2D,
+    gl::UNSIGNED_INT_SAMPLER_2D,
+    gl::IMAGE_2D,
+    gl::IMAGE_3D,
+];
+
+pub fn glsl_type_name(kind: GLenum) -> &'static str {
+    match kind {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        gl::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+        gl::SAMPLER_2D_ARRAY_SHADOW => "sampler2DArrayShadow",
+        gl::SAMPLER_CUBE_SHADOW => "samplerCubeShadow",
+        gl::SAMPLER_2D_MULTISAMPLE => "sampler2DMS",
+        gl::INT_SAMPLER_2D => "isampler2D",
+        gl::UNSIGNED_INT_SAMPLER_2D => "usampler2D",
+        gl::IMAGE_2D => "image2D",
+        gl::IMAGE_3D => "image3D",
+        _ => "unknown",
+    }
+}
+
+/// how often the shader files are checked for changes
+const SHADER_POLL_INTERVAL: f32 = 0.5;
+
+/// rebuilds programs when their shader files change, a program that fails to compile or link
+/// keeps running the last version that worked
+pub struct ShaderRegistry {
+    pub auto_reload: bool,
+    /// the files of a program that failed to rebuild, and the error
+    pub errors: Vec<(String, String)>,
+    last_poll: Instant,
+}
+
+impl ShaderRegistry {
+    pub fn new() -> Self {
+        Self {
+            auto_reload: true,
+            errors: Vec::new(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// call once a frame, reloads programs whose files changed since they were last built
+    pub fn poll(&mut self) {
+        if !self.auto_reload || self.last_poll.elapsed().as_secs_f32() < SHADER_POLL_INTERVAL {
+            return;
+        }
+        self.last_poll = Instant::now();
+        self.reload(false);
+    }
+
+    /// rebuilds every program, changed or not
+    pub fn reload_all(&mut self) {
+        self.reload(true);
+    }
+
+    fn reload(&mut self, force: bool) {
+        let programs: Vec<Rc<ProgramInner>> = PROGRAMS.with(|programs| {
+            let mut programs = programs.borrow_mut();
+            programs.retain(|program| program.strong_count() > 0);
+            programs
+                .iter()
+                .filter_map(|program| program.upgrade())
+                .collect()
+        });
+        for program in programs {
+            let mut changed = force;
+            for source in &program.sources {
+                let modified = source_modified_time(&source.path);
+                if modified != source.modified.get() {
+                    source.modified.set(modified);
+                    changed = true;
+                }
+            }
+            if !changed {
+                continue;
+            }
+            let name: Vec<String> = program
+                .sources
+                .iter()
+                .map(|source| source.path.display().to_string())
+                .collect();
+            let name = name.join(", ");
+            self.errors.retain(|(error_name, _)| *error_name != name);
+            match build_program(&program.sources) {
+                Ok(id) => {
+                    program.swap(id);
+                    println!("reloaded shaders {}", name);
+                }
+                Err(error) => {
+                    println!("{}", error);
+                    self.errors.push((name, error.to_string()));
+                }
+            }
+        }
+    }
+}
+
+impl Widget for &mut ShaderRegistry {
+    fn ui(self, ui: &mut egui_sdl2_gl::egui::Ui) -> egui_sdl2_gl::egui::Response {
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Reload shaders").clicked() {
+                    self.reload_all();
+                }
+                ui.checkbox(&mut self.auto_reload, "Auto Reload");
+            });
+            ui.collapsing("Uniforms", |ui| {
+                let programs: Vec<Program> = PROGRAMS.with(|programs| {
+                    programs
+                        .borrow()
+                        .iter()
+                        .filter_map(|program| program.upgrade())
+                        .map(|inner| Program { inner })
+                        .collect()
+                });
+                for (index, program) in programs.iter().enumerate() {
+                    egui::CollapsingHeader::new(format!("{} {}", program.id(), program.name()))
+                        .id_source(("program_uniforms", index))
+                        .show(ui, |ui| {
+                            egui::Grid::new(("program_uniform_grid", index))
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    ui.label("Location");
+                                    ui.label("Type");
+                                    ui.label("Name");
+                                    ui.end_row();
+                                    for (name, uniform) in program.uniforms() {
+                                        if uniform.array_element {
+                                            continue;
+                                        }
+                                        ui.label(uniform.location.to_string());
+                                        if uniform.size > 1 {
+                                            ui.label(format!(
+                                                "{}[{}]",
+                                                glsl_type_name(uniform.kind),
+                                                uniform.size
+                                            ));
+                                        } else {
+                                            ui.label(glsl_type_name(uniform.kind));
+                                        }
+                                        ui.label(name);
+                                        ui.end_row();
+                                    }
+                                });
+                            for warning in program.inner.warnings.borrow().iter() {
+                                ui.label(RichText::new(warning).color(Color32::YELLOW));
+                            }
+                        });
+                }
+            });
+            if !self.errors.is_empty() {
+                ui.label(RichText::new("Shader Errors").color(Color32::RED));
+                egui::ScrollArea::vertical()
+                    .id_source("shader_error_scroll_area")
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        for (name, error) in &self.errors {
+                            ui.label(name);
+                            ui.label(RichText::new(error).monospace().color(Color32::LIGHT_RED));
+                        }
+                    });
+            }
+        })
+        .response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lighting_passes_include_the_shared_shading_once() {
+        for pass in [
+            "PointLightingPass",
+            "SpotLightingPass",
+            "DirectionalLightingPass",
+            "ClusteredLightingPass",
+        ] {
+            let path = PathBuf::from(format!("./shaders/{}/shader.frag", pass));
+            let text = fs::read_to_string(&path).unwrap();
+            let resolved = resolve_includes(&path, &text).unwrap();
+            assert!(resolved.starts_with("#version "), "{}", pass);
+            assert!(
+                !resolved.lines().any(|line| line.starts_with("#include")),
+                "{}",
+                pass
+            );
+            assert_eq!(resolved.matches("vec3 Shade(").count(), 1, "{}", pass);
+            assert_eq!(
+                resolved.matches("Surface ReadSurface(").count(),
+                1,
+                "{}",
+                pass
+            );
+            // the include is line 2, so the line after it is line 3 again
+            assert!(resolved.contains("\n#line 3\n"), "{}", pass);
+        }
+    }
+
+    #[test]
+    fn missing_includes_are_errors() {
+        let path = Path::new("./shaders/PointLightingPass/shader.frag");
+        let text = "#version 330 core\n#include \"Missing.glsl\"\n";
+        match resolve_includes(path, text) {
+            Err(EngineError::Io { path, .. }) => {
+                assert_eq!(path, Path::new("./shaders/PointLightingPass/Missing.glsl"))
+            }
+            other => panic!(
+                "expected a missing include error, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+    }
 }
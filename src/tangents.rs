@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector3, Vector4, Zero};
+
+use crate::vertex_layout::Vertex;
+
+fn normalize_or_zero(vector: Vector3<f32>) -> Vector3<f32> {
+    let length = vector.magnitude();
+    if length > 0.0 && length.is_finite() {
+        return vector / length;
+    }
+    return Vector3::zero();
+}
+
+/// the part of vector in the plane that normal faces, normalized
+fn orthogonalize(vector: Vector3<f32>, normal: Vector3<f32>) -> Vector3<f32> {
+    normalize_or_zero(vector - normal * normal.dot(vector))
+}
+
+/// any direction in the plane normal faces, for vertices the uvs can't give a tangent to
+fn any_perpendicular(normal: Vector3<f32>) -> Vector3<f32> {
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let perpendicular = orthogonalize(axis, normal);
+    if perpendicular.is_zero() {
+        return Vector3::unit_x();
+    }
+    return perpendicular;
+}
+
+/// the triangles of an indexed mesh as mikktspace reads them
+struct TangentGeometry<'a> {
+    vertices: &'a [Vertex],
+    indices: &'a [u32],
+    /// what mikktspace gives every corner, one for each index
+    corner_tangents: Vec<Vector4<f32>>,
+}
+
+impl TangentGeometry<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> &Vertex {
+        &self.vertices[self.indices[face * 3 + vert] as usize]
+    }
+}
+
+impl mikktspace::Geometry for TangentGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).position.into()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        normalize_or_zero(self.vertex(face, vert).normal).into()
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertex(face, vert).uv.into()
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.corner_tangents[face * 3 + vert] = tangent.into();
+    }
+}
+
+/// the corner's tangent made orthogonal to the normal, w is only ever 1 or -1
+fn fit_tangent(tangent: Vector4<f32>, normal: Vector3<f32>) -> Vector4<f32> {
+    let normal = normalize_or_zero(normal);
+    let mut tangent_xyz = orthogonalize(tangent.truncate(), normal);
+    if tangent_xyz.is_zero() {
+        tangent_xyz = any_perpendicular(normal);
+    }
+    let sign = if tangent.w < 0.0 { -1.0 } else { 1.0 };
+    return tangent_xyz.extend(sign);
+}
+
+fn same_tangent(a: Vector4<f32>, b: Vector4<f32>) -> bool {
+    a.w == b.w && (a.truncate() - b.truncate()).magnitude2() < 1e-10
+}
+
+/// MikkTSpace tangents for normal mapping, so normal maps baked in blender or made for gltf line up.
+/// mikktspace gives every corner of every triangle a tangent, a vertex whose corners get different
+/// ones, like where mirrored uvs meet, is split into a copy for each and the indices are moved to
+/// the copies. w holds which way the bitangent points, bitangent = cross(normal, tangent.xyz) * w
+pub fn generate_tangents(vertices: &mut Vec<Vertex>, indices: &mut [u32]) {
+    let corner_tangents = {
+        let mut geometry = TangentGeometry {
+            vertices,
+            indices,
+            corner_tangents: vec![Vector4::zero(); indices.len()],
+        };
+        // this only fails when there are no triangles, and then every vertex gets the fallback below
+        mikktspace::generate_tangents(&mut geometry);
+        geometry.corner_tangents
+    };
+
+    let original_count = vertices.len();
+    let mut assigned = vec![false; original_count];
+    // the copies of each vertex that was split, by the vertex they were copied from
+    let mut splits: HashMap<usize, Vec<u32>> = HashMap::new();
+    for (corner, index) in indices.iter_mut().enumerate() {
+        let vertex = *index as usize;
+        let tangent = fit_tangent(corner_tangents[corner], vertices[vertex].normal);
+        if !assigned[vertex] {
+            vertices[vertex].tangent = tangent;
+            assigned[vertex] = true;
+            continue;
+        }
+        if same_tangent(vertices[vertex].tangent, tangent) {
+            continue;
+        }
+        let copies = splits.entry(vertex).or_default();
+        if let Some(copy) = copies
+            .iter()
+            .find(|copy| same_tangent(vertices[**copy as usize].tangent, tangent))
+        {
+            *index = *copy;
+            continue;
+        }
+        let mut copy = vertices[vertex];
+        copy.tangent = tangent;
+        vertices.push(copy);
+        *index = (vertices.len() - 1) as u32;
+        copies.push(*index);
+    }
+    // no triangle uses these, they only need a tangent that fits their normal
+    for vertex in vertices[..original_count]
+        .iter_mut()
+        .zip(assigned)
+        .filter(|(_, assigned)| !assigned)
+        .map(|(vertex, _)| vertex)
+    {
+        vertex.tangent = any_perpendicular(normalize_or_zero(vertex.normal)).extend(1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector2;
+
+    use super::*;
+
+    fn vertex(position: Vector3<f32>, normal: Vector3<f32>, uv: Vector2<f32>) -> Vertex {
+        Vertex {
+            position,
+            color: Vector3::new(1.0, 1.0, 1.0),
+            normal,
+            uv,
+            tangent: Vector4::zero(),
+        }
+    }
+
+    fn load_fixture(path: &str) -> Vec<(Vector3<f32>, Vector3<f32>, Vector4<f32>)> {
+        let load_options = tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        };
+        let (models, _) = tobj::load_obj(path, &load_options).unwrap();
+        let mut fixture = Vec::new();
+        for model in models {
+            let mesh = &model.mesh;
+            assert_eq!(mesh.normals.len(), mesh.positions.len());
+            assert_eq!(mesh.texcoords.len() / 2, mesh.positions.len() / 3);
+            let mut vertices: Vec<Vertex> = (0..mesh.positions.len() / 3)
+                .map(|i| {
+                    vertex(
+                        Vector3::new(
+                            mesh.positions[3 * i],
+                            mesh.positions[3 * i + 1],
+                            mesh.positions[3 * i + 2],
+                        ),
+                        Vector3::new(
+                            mesh.normals[3 * i],
+                            mesh.normals[3 * i + 1],
+                            mesh.normals[3 * i + 2],
+                        ),
+                        Vector2::new(mesh.texcoords[2 * i], mesh.texcoords[2 * i + 1]),
+                    )
+                })
+                .collect();
+            let mut indices = mesh.indices.clone();
+            generate_tangents(&mut vertices, &mut indices);
+            fixture.extend(
+                vertices
+                    .into_iter()
+                    .map(|vertex| (vertex.position, vertex.normal, vertex.tangent)),
+            );
+        }
+        return fixture;
+    }
+
+    fn assert_orthonormal(vertices: &[(Vector3<f32>, Vector3<f32>, Vector4<f32>)]) {
+        assert!(!vertices.is_empty());
+        for (position, normal, tangent) in vertices {
+            let tangent_xyz = tangent.truncate();
+            assert!(
+                (tangent_xyz.magnitude() - 1.0).abs() < 1e-4,
+                "tangent {:?} at {:?} isn't unit length",
+                tangent,
+                position
+            );
+            assert!(
+                tangent_xyz.dot(normal.normalize()).abs() < 1e-4,
+                "tangent {:?} at {:?} isn't orthogonal to {:?}",
+                tangent,
+                position,
+                normal
+            );
+            assert!(tangent.w == 1.0 || tangent.w == -1.0);
+        }
+    }
+
+    #[test]
+    fn cube_tangents_are_orthonormal() {
+        assert_orthonormal(&load_fixture("./models/Cube.obj"));
+    }
+
+    #[test]
+    fn plane_tangents_are_orthonormal() {
+        // the plane's sides have no uv area, so they test the fallback too
+        let vertices = load_fixture("./models/Plane.obj");
+        assert_orthonormal(&vertices);
+        // u runs along +x on the top, v along -z, so the bitangent is cross(+y, +x) without flipping
+        let top: Vec<_> = vertices
+            .iter()
+            .filter(|(position, normal, _)| position.y == 0.0 && normal.y > 0.5)
+            .collect();
+        assert_eq!(top.len(), 4);
+        for (_, _, tangent) in top {
+            assert!((tangent - Vector4::new(1.0, 0.0, 0.0, 1.0)).magnitude() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_split_the_seam() {
+        // two quads side by side with the right one's u mirrored, so u runs +x on the left and -x
+        // on the right and the middle column has a tangent on each side that can't be shared
+        let up = Vector3::unit_y();
+        let mut vertices = vec![
+            vertex(Vector3::new(-1.0, 0.0, 0.0), up, Vector2::new(0.0, 0.0)),
+            vertex(Vector3::new(0.0, 0.0, 0.0), up, Vector2::new(1.0, 0.0)),
+            vertex(Vector3::new(1.0, 0.0, 0.0), up, Vector2::new(0.0, 0.0)),
+            vertex(Vector3::new(-1.0, 0.0, -1.0), up, Vector2::new(0.0, 1.0)),
+            vertex(Vector3::new(0.0, 0.0, -1.0), up, Vector2::new(1.0, 1.0)),
+            vertex(Vector3::new(1.0, 0.0, -1.0), up, Vector2::new(0.0, 1.0)),
+        ];
+        let mut indices = vec![0, 1, 4, 0, 4, 3, 1, 2, 5, 1, 5, 4];
+        generate_tangents(&mut vertices, &mut indices);
+
+        // the two middle vertices are copied for the right quad, the rest are left alone
+        assert_eq!(vertices.len(), 8);
+        // u runs along +x on the left and -x on the right while v runs along -z on both, so the
+        // right quad's bitangent is flipped too
+        let left = Vector4::new(1.0, 0.0, 0.0, 1.0);
+        let right = Vector4::new(-1.0, 0.0, 0.0, -1.0);
+        for (corner, index) in indices.iter().enumerate() {
+            let vertex = &vertices[*index as usize];
+            let expected = if corner < 6 { left } else { right };
+            assert!(
+                (vertex.tangent - expected).magnitude() < 1e-4,
+                "vertex at {:?} has tangent {:?}",
+                vertex.position,
+                vertex.tangent
+            );
+        }
+        // the copies keep everything but the tangent
+        assert_eq!(vertices[6].position, vertices[1].position);
+        assert_eq!(vertices[7].position, vertices[4].position);
+    }
+}
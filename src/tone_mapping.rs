@@ -0,0 +1,283 @@
+use egui_sdl2_gl::egui::{self, Widget};
+
+/// the darkest and brightest luminance the auto exposure histogram tells apart, as log2 luminance
+const HISTOGRAM_MIN_LOG_LUMINANCE: f32 = -12.0;
+const HISTOGRAM_MAX_LOG_LUMINANCE: f32 = 8.0;
+const HISTOGRAM_BINS: usize = 64;
+/// the darkest and brightest pixels left out of the average, so a few lights or shadows don't
+/// swing the exposure
+const HISTOGRAM_LOW_PERCENTILE: f32 = 0.1;
+const HISTOGRAM_HIGH_PERCENTILE: f32 = 0.9;
+/// the luminance auto exposure brings the average to
+const MIDDLE_GREY: f32 = 0.18;
+/// auto exposure reads a mip level of the hdr buffer no bigger than this on either side
+pub const AUTO_EXPOSURE_SIZE: u32 = 64;
+
+/// maps the hdr colors of the scene into the 0-1 the window can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapper {
+    /// only clamps
+    None,
+    Reinhard,
+    /// Narkowicz's fit of the aces filmic curve
+    Aces,
+    /// Hable's filmic curve from Uncharted 2
+    Uncharted2,
+}
+
+impl ToneMapper {
+    pub const ALL: [Self; 4] = [Self::None, Self::Reinhard, Self::Aces, Self::Uncharted2];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Reinhard => "Reinhard",
+            Self::Aces => "ACES",
+            Self::Uncharted2 => "Uncharted 2",
+        }
+    }
+
+    /// the toneMapper uniform of the tone mapping shader
+    pub fn shader_value(self) -> i32 {
+        match self {
+            Self::None => 0,
+            Self::Reinhard => 1,
+            Self::Aces => 2,
+            Self::Uncharted2 => 3,
+        }
+    }
+}
+
+/// tone mapping, exposure and gamma settings, and the exposure auto exposure has adapted to
+pub struct ToneMapping {
+    pub tone_mapper: ToneMapper,
+    /// in stops, every stop doubles the brightness. with auto exposure it is added on top
+    pub exposure: f32,
+    /// the gamma of the window, the colors are raised to 1 / gamma
+    pub gamma: f32,
+    pub auto_exposure: bool,
+    /// how quickly auto exposure moves to a new brightness, higher is faster
+    pub adaptation_speed: f32,
+    /// the range of stops auto exposure stays in
+    pub auto_exposure_range: (f32, f32),
+    /// the stops auto exposure has adapted to so far
+    adapted_exposure: f32,
+}
+
+impl ToneMapping {
+    pub fn new() -> Self {
+        Self {
+            tone_mapper: ToneMapper::Aces,
+            exposure: 0.0,
+            gamma: 2.2,
+            auto_exposure: false,
+            adaptation_speed: 2.0,
+            auto_exposure_range: (-6.0, 6.0),
+            adapted_exposure: 0.0,
+        }
+    }
+
+    /// what the hdr colors are multiplied by before tone mapping
+    pub fn exposure_scale(&self) -> f32 {
+        let mut exposure = self.exposure;
+        if self.auto_exposure {
+            exposure += self.adapted_exposure;
+        }
+        return exposure.exp2();
+    }
+
+    /// moves the adapted exposure towards the one that brings the average luminance of colors to
+    /// middle grey. colors is rgba, from a downsampled hdr buffer
+    pub fn adapt_exposure(&mut self, colors: &[f32], deltatime: f32) {
+        let histogram = luminance_histogram(colors);
+        let Some(average) = average_log_luminance(&histogram) else {
+            return;
+        };
+        // the ends can be set in either order, clamp panics if min is above max
+        let (a, b) = self.auto_exposure_range;
+        let (min, max) = (a.min(b), a.max(b));
+        let target = (MIDDLE_GREY.log2() - average).clamp(min, max);
+        let blend = 1.0 - (-deltatime * self.adaptation_speed).exp();
+        self.adapted_exposure += (target - self.adapted_exposure) * blend;
+    }
+}
+
+/// counts the pixels of rgba colors into bins of log2 luminance, pixels darker than the first bin
+/// go in the first bin and brighter than the last in the last
+pub fn luminance_histogram(colors: &[f32]) -> [u32; HISTOGRAM_BINS] {
+    let mut histogram = [0; HISTOGRAM_BINS];
+    let range = HISTOGRAM_MAX_LOG_LUMINANCE - HISTOGRAM_MIN_LOG_LUMINANCE;
+    for color in colors.chunks_exact(4) {
+        let luminance = 0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2];
+        let log_luminance = luminance.max(f32::MIN_POSITIVE).log2();
+        let position = (log_luminance - HISTOGRAM_MIN_LOG_LUMINANCE) / range;
+        let bin = (position * HISTOGRAM_BINS as f32).clamp(0.0, (HISTOGRAM_BINS - 1) as f32);
+        histogram[bin as usize] += 1;
+    }
+    return histogram;
+}
+
+/// the average log2 luminance of the pixels between the low and high percentiles, None without
+/// pixels
+pub fn average_log_luminance(histogram: &[u32; HISTOGRAM_BINS]) -> Option<f32> {
+    let total: u32 = histogram.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let low = total as f32 * HISTOGRAM_LOW_PERCENTILE;
+    let high = total as f32 * HISTOGRAM_HIGH_PERCENTILE;
+    let bin_size =
+        (HISTOGRAM_MAX_LOG_LUMINANCE - HISTOGRAM_MIN_LOG_LUMINANCE) / HISTOGRAM_BINS as f32;
+    let (mut counted, mut sum, mut weight) = (0.0, 0.0, 0.0);
+    for (bin, count) in histogram.iter().enumerate() {
+        // the part of this bin that is between the percentiles
+        let start = counted;
+        counted += *count as f32;
+        let inside = counted.min(high) - start.max(low);
+        if inside > 0.0 {
+            let log_luminance = HISTOGRAM_MIN_LOG_LUMINANCE + (bin as f32 + 0.5) * bin_size;
+            sum += log_luminance * inside;
+            weight += inside;
+        }
+    }
+    if weight == 0.0 {
+        return None;
+    }
+    return Some(sum / weight);
+}
+
+/// the mip level of a texture of size whose sides are at most AUTO_EXPOSURE_SIZE
+pub fn auto_exposure_mip_level(size: (u32, u32)) -> i32 {
+    let mut level = 0;
+    let mut largest = size.0.max(size.1);
+    while largest > AUTO_EXPOSURE_SIZE {
+        largest /= 2;
+        level += 1;
+    }
+    return level;
+}
+
+impl Widget for &mut ToneMapping {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        ui.vertical(|ui| {
+            egui::ComboBox::from_label("Tone Mapper")
+                .selected_text(self.tone_mapper.name())
+                .show_ui(ui, |ui| {
+                    for tone_mapper in ToneMapper::ALL {
+                        ui.selectable_value(&mut self.tone_mapper, tone_mapper, tone_mapper.name());
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut self.exposure)
+                        .prefix("Exposure ")
+                        .suffix(" EV")
+                        .range(-16.0..=16.0)
+                        .speed(0.05),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut self.gamma)
+                        .prefix("Gamma ")
+                        .range(0.5..=4.0)
+                        .speed(0.01),
+                );
+            });
+            ui.checkbox(&mut self.auto_exposure, "Auto Exposure");
+            if self.auto_exposure {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut self.adaptation_speed)
+                            .prefix("Adaptation Speed ")
+                            .range(0.01..=20.0)
+                            .speed(0.01),
+                    );
+                    let (min, max) = self.auto_exposure_range;
+                    ui.add(
+                        egui::DragValue::new(&mut self.auto_exposure_range.0)
+                            .prefix("Min ")
+                            .range(-16.0..=max)
+                            .suffix(" EV")
+                            .speed(0.05),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut self.auto_exposure_range.1)
+                            .prefix("Max ")
+                            .range(min..=16.0)
+                            .suffix(" EV")
+                            .speed(0.05),
+                    );
+                });
+                ui.label(format!("Adapted Exposure: {:.2} EV", self.adapted_exposure));
+            }
+        })
+        .response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey_pixels(luminances: &[f32]) -> Vec<f32> {
+        luminances
+            .iter()
+            .flat_map(|luminance| [*luminance, *luminance, *luminance, 1.0])
+            .collect()
+    }
+
+    #[test]
+    fn the_average_leaves_out_the_brightest_and_darkest_pixels() {
+        // 8 pixels at 0.5, one black and one very bright
+        let mut luminances = vec![0.5; 8];
+        luminances.push(0.0);
+        luminances.push(10000.0);
+        let histogram = luminance_histogram(&grey_pixels(&luminances));
+        assert_eq!(histogram.iter().sum::<u32>(), 10);
+        assert_eq!(histogram[0], 1);
+        assert_eq!(histogram[HISTOGRAM_BINS - 1], 1);
+
+        let average = average_log_luminance(&histogram).unwrap();
+        let bin_size =
+            (HISTOGRAM_MAX_LOG_LUMINANCE - HISTOGRAM_MIN_LOG_LUMINANCE) / HISTOGRAM_BINS as f32;
+        assert!((average - 0.5f32.log2()).abs() <= bin_size, "{}", average);
+        assert_eq!(average_log_luminance(&[0; HISTOGRAM_BINS]), None);
+    }
+
+    #[test]
+    fn auto_exposure_brings_the_scene_to_middle_grey() {
+        let mut tone_mapping = ToneMapping::new();
+        tone_mapping.auto_exposure = true;
+        let dark = grey_pixels(&[0.01; 16]);
+        for _ in 0..200 {
+            tone_mapping.adapt_exposure(&dark, 0.1);
+        }
+        let exposed = 0.01 * tone_mapping.exposure_scale();
+        assert!((exposed / MIDDLE_GREY).log2().abs() < 0.2, "{}", exposed);
+
+        // a stop of exposure on top of what auto exposure picked
+        let adapted = tone_mapping.exposure_scale();
+        tone_mapping.exposure = 1.0;
+        assert!((tone_mapping.exposure_scale() - adapted * 2.0).abs() < 1e-4);
+
+        // an extremely dark scene stops at the end of the range
+        let black = grey_pixels(&[1e-6; 16]);
+        for _ in 0..200 {
+            tone_mapping.adapt_exposure(&black, 0.1);
+        }
+        assert!((tone_mapping.adapted_exposure - 6.0).abs() < 1e-3);
+
+        // a range set the wrong way around is used as if it was sorted
+        tone_mapping.auto_exposure_range = (2.0, -2.0);
+        for _ in 0..200 {
+            tone_mapping.adapt_exposure(&black, 0.1);
+        }
+        assert!((tone_mapping.adapted_exposure - 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn auto_exposure_reads_a_small_mip_level() {
+        assert_eq!(auto_exposure_mip_level((64, 32)), 0);
+        assert_eq!(auto_exposure_mip_level((1920, 1052)), 5);
+        assert_eq!(auto_exposure_mip_level((100, 1000)), 4);
+    }
+}
@@ -0,0 +1,298 @@
+use std::mem::size_of;
+
+use cgmath::{Matrix4, SquareMatrix, Vector2, Vector3, Vector4, Zero};
+
+use crate::{buffers::UniformBuffer, models::MAX_CASCADES};
+
+//// binding points, every program gets its blocks bound to these after linking
+pub const CAMERA_BINDING: u32 = 0;
+pub const POINT_LIGHTS_BINDING: u32 = 1;
+pub const DIRECTIONAL_LIGHTS_BINDING: u32 = 2;
+pub const SPOT_LIGHTS_BINDING: u32 = 3;
+
+/// block name in glsl and the binding point it is bound to
+pub const UNIFORM_BLOCK_BINDINGS: &[(&str, u32)] = &[
+    ("Camera", CAMERA_BINDING),
+    ("PointLights", POINT_LIGHTS_BINDING),
+    ("DirectionalLights", DIRECTIONAL_LIGHTS_BINDING),
+    ("SpotLights", SPOT_LIGHTS_BINDING),
+];
+
+//// these have to match the defines in the lighting pass shaders
+pub const MAX_POINT_LIGHTS: usize = 64;
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+pub const MAX_SPOT_LIGHTS: usize = 16;
+
+/// the Camera block, updated once a frame
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CameraBlock {
+    pub view: Matrix4<f32>,
+    pub projection: Matrix4<f32>,
+    pub inverse_view: Matrix4<f32>,
+    pub view_pos: Vector3<f32>,
+    /// seconds since the engine started
+    pub time: f32,
+    pub screen_size: Vector2<f32>,
+    pub _padding: Vector2<f32>,
+}
+
+/// one element of the PointLights block
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PointLightData {
+    pub position: Vector3<f32>,
+    pub radius: f32,
+    pub color: Vector3<f32>,
+    pub shadow_bias: f32,
+}
+
+/// one element of the DirectionalLights block
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLightData {
+    pub light_space_matrices: [Matrix4<f32>; MAX_CASCADES],
+    /// std140 pads every float of an array to 16 bytes, so the distances are packed four to a vec4
+    pub cascade_plane_distances: [Vector4<f32>; MAX_CASCADES / 4],
+    pub position: Vector3<f32>,
+    pub cascade_count: i32,
+    pub direction: Vector3<f32>,
+    /// a glsl bool is 4 bytes in std140
+    pub debug_cascades: i32,
+    pub color: Vector3<f32>,
+    pub _padding: f32,
+}
+
+/// one element of the SpotLights block
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SpotLightData {
+    pub light_space_matrix: Matrix4<f32>,
+    pub position: Vector3<f32>,
+    pub radius: f32,
+    pub direction: Vector3<f32>,
+    pub fov: f32,
+    pub color: Vector3<f32>,
+    pub near_plane: f32,
+}
+
+/// a fixed size array of lights and how many of them are used, the layout of every light block
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LightsBlock<T: Copy, const N: usize> {
+    pub lights: [T; N],
+    pub count: i32,
+    pub _padding: [i32; 3],
+}
+
+impl<T: Copy, const N: usize> LightsBlock<T, N> {
+    /// lights past N are left out, upload them in another block
+    pub fn new(lights: &[T], empty: T) -> Self {
+        let mut block = Self {
+            lights: [empty; N],
+            count: lights.len().min(N) as i32,
+            _padding: [0; 3],
+        };
+        block.lights[..block.count as usize].copy_from_slice(&lights[..block.count as usize]);
+        return block;
+    }
+}
+
+pub type PointLightsBlock = LightsBlock<PointLightData, MAX_POINT_LIGHTS>;
+pub type DirectionalLightsBlock = LightsBlock<DirectionalLightData, MAX_DIRECTIONAL_LIGHTS>;
+pub type SpotLightsBlock = LightsBlock<SpotLightData, MAX_SPOT_LIGHTS>;
+
+impl PointLightData {
+    pub fn empty() -> Self {
+        Self {
+            position: Vector3::zero(),
+            radius: 0.0,
+            color: Vector3::zero(),
+            shadow_bias: 0.0,
+        }
+    }
+}
+
+impl DirectionalLightData {
+    pub fn empty() -> Self {
+        Self {
+            light_space_matrices: [Matrix4::identity(); MAX_CASCADES],
+            cascade_plane_distances: [Vector4::zero(); MAX_CASCADES / 4],
+            position: Vector3::zero(),
+            cascade_count: 0,
+            direction: Vector3::zero(),
+            debug_cascades: 0,
+            color: Vector3::zero(),
+            _padding: 0.0,
+        }
+    }
+}
+
+impl SpotLightData {
+    pub fn empty() -> Self {
+        Self {
+            light_space_matrix: Matrix4::identity(),
+            position: Vector3::zero(),
+            radius: 0.0,
+            direction: Vector3::zero(),
+            fov: 0.0,
+            color: Vector3::zero(),
+            near_plane: 0.0,
+        }
+    }
+}
+
+/// the buffers behind the shared uniform blocks, bound to their binding points once when created
+pub struct UniformBlocks {
+    pub camera: UniformBuffer,
+    pub point_lights: UniformBuffer,
+    pub directional_lights: UniformBuffer,
+    pub spot_lights: UniformBuffer,
+}
+
+impl UniformBlocks {
+    pub fn new() -> Self {
+        let camera = UniformBuffer::new();
+        camera.allocate(size_of::<CameraBlock>());
+        camera.bind_base(CAMERA_BINDING);
+        let point_lights = UniformBuffer::new();
+        point_lights.allocate(size_of::<PointLightsBlock>());
+        point_lights.bind_base(POINT_LIGHTS_BINDING);
+        let directional_lights = UniformBuffer::new();
+        directional_lights.allocate(size_of::<DirectionalLightsBlock>());
+        directional_lights.bind_base(DIRECTIONAL_LIGHTS_BINDING);
+        let spot_lights = UniformBuffer::new();
+        spot_lights.allocate(size_of::<SpotLightsBlock>());
+        spot_lights.bind_base(SPOT_LIGHTS_BINDING);
+        Self {
+            camera,
+            point_lights,
+            directional_lights,
+            spot_lights,
+        }
+    }
+
+    pub fn update_camera(
+        &self,
+        view: Matrix4<f32>,
+        projection: Matrix4<f32>,
+        view_pos: Vector3<f32>,
+        screen_size: Vector2<f32>,
+        time: f32,
+    ) {
+        self.camera.set(&CameraBlock {
+            view,
+            projection,
+            inverse_view: view.invert().unwrap_or(Matrix4::identity()),
+            view_pos,
+            time,
+            screen_size,
+            _padding: Vector2::zero(),
+        });
+    }
+
+    pub fn update_lights(
+        &self,
+        point_lights: &[PointLightData],
+        directional_lights: &[DirectionalLightData],
+        spot_lights: &[SpotLightData],
+    ) {
+        self.update_point_lights(point_lights);
+        self.update_directional_lights(directional_lights);
+        self.update_spot_lights(spot_lights);
+    }
+
+    //// the lighting passes draw one light at a time, lights past the size of a block are drawn
+    //// after uploading them over the lights before them
+
+    /// uploads the first MAX_POINT_LIGHTS of point_lights
+    pub fn update_point_lights(&self, point_lights: &[PointLightData]) {
+        self.point_lights.set(&PointLightsBlock::new(
+            point_lights,
+            PointLightData::empty(),
+        ));
+    }
+
+    /// uploads the first MAX_DIRECTIONAL_LIGHTS of directional_lights
+    pub fn update_directional_lights(&self, directional_lights: &[DirectionalLightData]) {
+        self.directional_lights.set(&DirectionalLightsBlock::new(
+            directional_lights,
+            DirectionalLightData::empty(),
+        ));
+    }
+
+    /// uploads the first MAX_SPOT_LIGHTS of spot_lights
+    pub fn update_spot_lights(&self, spot_lights: &[SpotLightData]) {
+        self.spot_lights
+            .set(&SpotLightsBlock::new(spot_lights, SpotLightData::empty()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::{offset_of, size_of};
+
+    use super::*;
+
+    // offsets worked out by hand from the std140 rules, they have to match the glsl blocks
+
+    #[test]
+    fn camera_block_matches_std140() {
+        assert_eq!(offset_of!(CameraBlock, view), 0);
+        assert_eq!(offset_of!(CameraBlock, projection), 64);
+        assert_eq!(offset_of!(CameraBlock, inverse_view), 128);
+        assert_eq!(offset_of!(CameraBlock, view_pos), 192);
+        assert_eq!(offset_of!(CameraBlock, time), 204);
+        assert_eq!(offset_of!(CameraBlock, screen_size), 208);
+        assert_eq!(size_of::<CameraBlock>(), 224);
+    }
+
+    #[test]
+    fn light_data_matches_std140() {
+        assert_eq!(offset_of!(PointLightData, position), 0);
+        assert_eq!(offset_of!(PointLightData, radius), 12);
+        assert_eq!(offset_of!(PointLightData, color), 16);
+        assert_eq!(offset_of!(PointLightData, shadow_bias), 28);
+        assert_eq!(size_of::<PointLightData>(), 32);
+
+        assert_eq!(offset_of!(DirectionalLightData, light_space_matrices), 0);
+        assert_eq!(
+            offset_of!(DirectionalLightData, cascade_plane_distances),
+            512
+        );
+        assert_eq!(offset_of!(DirectionalLightData, position), 544);
+        assert_eq!(offset_of!(DirectionalLightData, cascade_count), 556);
+        assert_eq!(offset_of!(DirectionalLightData, direction), 560);
+        assert_eq!(offset_of!(DirectionalLightData, debug_cascades), 572);
+        assert_eq!(offset_of!(DirectionalLightData, color), 576);
+        assert_eq!(size_of::<DirectionalLightData>(), 592);
+
+        assert_eq!(offset_of!(SpotLightData, light_space_matrix), 0);
+        assert_eq!(offset_of!(SpotLightData, position), 64);
+        assert_eq!(offset_of!(SpotLightData, radius), 76);
+        assert_eq!(offset_of!(SpotLightData, direction), 80);
+        assert_eq!(offset_of!(SpotLightData, fov), 92);
+        assert_eq!(offset_of!(SpotLightData, color), 96);
+        assert_eq!(offset_of!(SpotLightData, near_plane), 108);
+        assert_eq!(size_of::<SpotLightData>(), 112);
+    }
+
+    #[test]
+    fn light_blocks_match_std140() {
+        // arrays of structs have a stride of the struct size rounded up to 16, the count follows the array
+        assert_eq!(offset_of!(PointLightsBlock, count), 32 * MAX_POINT_LIGHTS);
+        assert_eq!(size_of::<PointLightsBlock>(), 32 * MAX_POINT_LIGHTS + 16);
+        assert_eq!(
+            offset_of!(DirectionalLightsBlock, count),
+            592 * MAX_DIRECTIONAL_LIGHTS
+        );
+        assert_eq!(offset_of!(SpotLightsBlock, count), 112 * MAX_SPOT_LIGHTS);
+    }
+
+    #[test]
+    fn lights_block_clamps_to_capacity() {
+        let lights = vec![PointLightData::empty(); MAX_POINT_LIGHTS + 5];
+        let block = PointLightsBlock::new(&lights, PointLightData::empty());
+        assert_eq!(block.count, MAX_POINT_LIGHTS as i32);
+    }
+}
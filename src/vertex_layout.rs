@@ -0,0 +1,210 @@
+use std::{mem::offset_of, os::raw::c_void};
+
+use cgmath::{Matrix4, Vector2, Vector3, Vector4};
+
+/// one input of the vertex shader and where it sits inside a vertex
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VertexAttribute {
+    /// the layout (location = n) the shader reads it from
+    pub location: u32,
+    /// 1 to 4
+    pub components: i32,
+    /// gl::FLOAT, gl::UNSIGNED_BYTE, gl::SHORT and so on
+    pub data_type: gl::types::GLenum,
+    /// integer data becomes a float from 0 to 1, or -1 to 1 when signed. integer data that isn't
+    /// normalized stays an integer, so the shader reads it as an ivec or uvec, like joint indices
+    pub normalized: bool,
+    /// bytes from the start of the vertex
+    pub offset: usize,
+}
+
+impl VertexAttribute {
+    /// whether the shader sees an ivec or uvec rather than a vec
+    fn reads_as_integer(&self) -> bool {
+        !self.normalized
+            && matches!(
+                self.data_type,
+                gl::BYTE
+                    | gl::UNSIGNED_BYTE
+                    | gl::SHORT
+                    | gl::UNSIGNED_SHORT
+                    | gl::INT
+                    | gl::UNSIGNED_INT
+            )
+    }
+}
+
+/// how the attributes of one vertex are laid out in a vertex buffer
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexLayout {
+    /// bytes from one vertex to the next
+    pub stride: usize,
+    pub attributes: Vec<VertexAttribute>,
+    /// 0 moves to the next vertex every vertex, 1 every instance of an instanced draw
+    pub divisor: u32,
+}
+
+impl VertexLayout {
+    /// a layout for tightly packed V
+    pub fn new<V: VertexFormat>(attributes: Vec<VertexAttribute>) -> Self {
+        Self {
+            stride: size_of::<V>(),
+            attributes,
+            divisor: 0,
+        }
+    }
+
+    /// for a buffer with one element per instance rather than per vertex
+    pub fn per_instance(mut self) -> Self {
+        self.divisor = 1;
+        return self;
+    }
+
+    /// points the bound vertex array at the bound vertex buffer
+    pub fn apply(&self) {
+        for attribute in &self.attributes {
+            unsafe {
+                if attribute.reads_as_integer() {
+                    gl::VertexAttribIPointer(
+                        attribute.location,
+                        attribute.components,
+                        attribute.data_type,
+                        self.stride as i32,
+                        attribute.offset as *const c_void,
+                    );
+                } else {
+                    gl::VertexAttribPointer(
+                        attribute.location,
+                        attribute.components,
+                        attribute.data_type,
+                        if attribute.normalized {
+                            gl::TRUE
+                        } else {
+                            gl::FALSE
+                        },
+                        self.stride as i32,
+                        attribute.offset as *const c_void,
+                    );
+                }
+                gl::VertexAttribDivisor(attribute.location, self.divisor);
+                gl::EnableVertexAttribArray(attribute.location);
+            }
+        }
+    }
+}
+
+/// a #[repr(C)] vertex struct that can describe its own layout, so it can go in a VertexBuffer
+pub trait VertexFormat: Copy {
+    fn layout() -> VertexLayout;
+}
+
+/// the vertex every model uses
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vertex {
+    pub position: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub uv: Vector2<f32>,
+    /// w is the sign of the bitangent
+    pub tangent: Vector4<f32>,
+}
+
+impl VertexFormat for Vertex {
+    fn layout() -> VertexLayout {
+        let float = |location: u32, components: i32, offset: usize| VertexAttribute {
+            location,
+            components,
+            data_type: gl::FLOAT,
+            normalized: false,
+            offset,
+        };
+        VertexLayout::new::<Self>(vec![
+            float(0, 3, offset_of!(Vertex, position)),
+            float(1, 3, offset_of!(Vertex, color)),
+            float(2, 3, offset_of!(Vertex, normal)),
+            float(3, 2, offset_of!(Vertex, uv)),
+            float(4, 4, offset_of!(Vertex, tangent)),
+        ])
+    }
+}
+
+/// the first location of the instance's model matrix, right after the Vertex attributes
+pub const INSTANCE_MODEL_LOCATION: u32 = 5;
+
+/// what every instance of an InstancedModel gets, a mat4 takes up 4 locations, one per column
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InstanceData {
+    pub model: Matrix4<f32>,
+}
+
+impl VertexFormat for InstanceData {
+    fn layout() -> VertexLayout {
+        let column_size = size_of::<Vector4<f32>>();
+        VertexLayout::new::<Self>(
+            (0..4)
+                .map(|column| VertexAttribute {
+                    location: INSTANCE_MODEL_LOCATION + column,
+                    components: 4,
+                    data_type: gl::FLOAT,
+                    normalized: false,
+                    offset: offset_of!(InstanceData, model) + column as usize * column_size,
+                })
+                .collect(),
+        )
+        .per_instance()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vertex_layout_is_tightly_packed() {
+        let layout = Vertex::layout();
+        assert_eq!(layout.stride, 15 * size_of::<f32>());
+        let offsets: Vec<usize> = layout.attributes.iter().map(|a| a.offset).collect();
+        assert_eq!(offsets, vec![0, 12, 24, 36, 44]);
+        // every attribute ends where the next one starts
+        for pair in layout.attributes.windows(2) {
+            assert_eq!(
+                pair[0].offset + pair[0].components as usize * size_of::<f32>(),
+                pair[1].offset
+            );
+        }
+    }
+
+    #[test]
+    fn instance_layout_covers_the_matrix() {
+        let layout = InstanceData::layout();
+        assert_eq!(layout.stride, 16 * size_of::<f32>());
+        assert_eq!(layout.divisor, 1);
+        let locations: Vec<u32> = layout.attributes.iter().map(|a| a.location).collect();
+        assert_eq!(locations, vec![5, 6, 7, 8]);
+        let offsets: Vec<usize> = layout.attributes.iter().map(|a| a.offset).collect();
+        assert_eq!(offsets, vec![0, 16, 32, 48]);
+        // the per vertex attributes come before the instance ones
+        let vertex_layout = Vertex::layout();
+        assert_eq!(vertex_layout.divisor, 0);
+        assert!(vertex_layout
+            .attributes
+            .iter()
+            .all(|a| a.location < INSTANCE_MODEL_LOCATION));
+    }
+
+    #[test]
+    fn only_unnormalized_integers_stay_integers() {
+        let attribute = |data_type, normalized| VertexAttribute {
+            location: 0,
+            components: 4,
+            data_type,
+            normalized,
+            offset: 0,
+        };
+        assert!(attribute(gl::UNSIGNED_BYTE, false).reads_as_integer());
+        assert!(!attribute(gl::UNSIGNED_BYTE, true).reads_as_integer());
+        assert!(!attribute(gl::FLOAT, false).reads_as_integer());
+    }
+}
@@ -1,49 +1,120 @@
+use std::env;
+
 use sdl2::{
     video::{gl_attr::GLAttr, GLContext, SwapInterval, Window},
     EventPump, Sdl,
 };
 
+use crate::{
+    config::{Config, Vsync, WindowMode, GL_VERSIONS},
+    errors::EngineError,
+};
+
 pub struct WinSdl {
     pub _sdl_context: Sdl,
     pub window: Window,
     pub _gl_context: GLContext,
     pub _gl: (),
     pub event_pump: EventPump,
+    /// the version of the context that was made, major and minor
+    pub gl_version: (u8, u8),
 }
 
 impl WinSdl {
-    pub fn new(width: usize, height: usize) -> Result<Self, &'static str> {
-        let sdl_context = sdl2::init().unwrap();
-        let video_subsystem = sdl_context.video().unwrap();
+    /// makes the window and a gl context for it, trying older gl versions when a newer one can't
+    /// be made
+    pub fn new(config: &Config) -> Result<Self, EngineError> {
+        let window_error = |message: String| EngineError::Window { message };
+        // without a display sdl can still make a gl context through egl, mesa's llvmpipe renders
+        // it on the cpu when there is no gpu
+        let has_display =
+            env::var_os("DISPLAY").is_some() || env::var_os("WAYLAND_DISPLAY").is_some();
+        if config.headless && cfg!(target_os = "linux") && !has_display {
+            sdl2::hint::set("SDL_VIDEODRIVER", "offscreen");
+        }
+        let sdl_context = sdl2::init().map_err(window_error)?;
+        let video_subsystem = sdl_context.video().map_err(window_error)?;
 
         let gl_attr: GLAttr<'_> = video_subsystem.gl_attr();
         gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
-        gl_attr.set_context_version(4, 6);
         gl_attr.set_double_buffer(true);
         gl_attr.set_framebuffer_srgb_compatible(true);
+        if config.msaa_samples > 1 {
+            gl_attr.set_multisample_buffers(1);
+            gl_attr.set_multisample_samples(config.msaa_samples as u8);
+        }
 
-        let window = video_subsystem
-            .window("AMTF Engine", width as u32, height as u32)
-            .opengl()
-            .resizable()
-            .position_centered()
+        let mut window_builder = video_subsystem.window("AMTF Engine", config.width, config.height);
+        window_builder.opengl();
+        match config.window_mode {
+            _ if config.headless => window_builder.hidden(),
+            WindowMode::Windowed => window_builder.resizable().position_centered(),
+            WindowMode::Fullscreen => window_builder.fullscreen(),
+            WindowMode::Borderless => window_builder.fullscreen_desktop(),
+        };
+        let window = window_builder
             .build()
-            .unwrap();
+            .map_err(|error| window_error(error.to_string()))?;
 
-        let gl_context = window.gl_create_context().unwrap();
+        let versions = match config.gl_version {
+            Some(version) => vec![version],
+            None => GL_VERSIONS.to_vec(),
+        };
+        let mut failures: Vec<String> = Vec::new();
+        let mut context = None;
+        for (major, minor) in versions {
+            gl_attr.set_context_version(major, minor);
+            match window.gl_create_context() {
+                Ok(gl_context) => {
+                    context = Some((gl_context, (major, minor)));
+                    break;
+                }
+                Err(error) => failures.push(format!("gl {}.{}: {}", major, minor, error)),
+            }
+        }
+        let (gl_context, gl_version) = context.ok_or_else(|| {
+            window_error(format!(
+                "couldn't make a gl context ({})",
+                failures.join(", ")
+            ))
+        })?;
+        if !failures.is_empty() {
+            println!(
+                "warning: using gl {}.{} ({})",
+                gl_version.0,
+                gl_version.1,
+                failures.join(", ")
+            );
+        }
         let gl = gl::load_with(|s| {
             video_subsystem.gl_get_proc_address(s) as *const std::os::raw::c_void
         });
+        if config.msaa_samples > 1 {
+            unsafe { gl::Enable(gl::MULTISAMPLE) };
+        }
 
-        // Enable vsync
-        if let Err(error) = window.subsystem().gl_set_swap_interval(SwapInterval::VSync) {
+        let swap_interval = match config.vsync {
+            _ if config.headless => SwapInterval::Immediate,
+            Vsync::Off => SwapInterval::Immediate,
+            Vsync::On => SwapInterval::VSync,
+            Vsync::Adaptive => SwapInterval::LateSwapTearing,
+        };
+        if let Err(error) = window.subsystem().gl_set_swap_interval(swap_interval) {
             println!(
-                "Failed to gl_set_swap_interval(SwapInterval::VSync): {}",
-                error
+                "Failed to gl_set_swap_interval({:?}): {}",
+                swap_interval, error
             );
+            if config.vsync == Vsync::Adaptive {
+                if let Err(error) = window.subsystem().gl_set_swap_interval(SwapInterval::VSync) {
+                    println!(
+                        "Failed to gl_set_swap_interval(SwapInterval::VSync): {}",
+                        error
+                    );
+                }
+            }
         };
 
-        let event_pump = sdl_context.event_pump().unwrap();
+        let event_pump = sdl_context.event_pump().map_err(window_error)?;
 
         return Ok(WinSdl {
             _sdl_context: sdl_context,
@@ -51,6 +122,7 @@ impl WinSdl {
             _gl_context: gl_context,
             _gl: gl,
             event_pump,
+            gl_version,
         });
     }
 }